@@ -0,0 +1,768 @@
+//! Agent Opcodes
+//!
+//! `AGENT_CALL`, `AGENT_DEPLOY` and `AGENT_QUERY` delegate to an `AgentHost` reached through
+//! `Host::agent_host`. Names, inputs and outputs are `offset, size` pairs into memory, first
+//! operand on top of the stack:
+//!
+//! | Opcode         | Operands                                                  | Result                  |
+//! |----------------|-----------------------------------------------------------|-------------------------|
+//! | `AGENT_CALL`   | agent, function, input, output (`offset, size` each)      | length, success on top  |
+//! | `AGENT_DEPLOY` | agent, definition (`offset, size` each)                   | 1 if deployed           |
+//! | `AGENT_QUERY`  | agent, key, output (`offset, size` each)                  | length, success on top  |
+//!
+//! Responses are copied into the output buffer, truncated to its size; the pushed length is
+//! the full response length. The agent host reports the gas its work consumed and the
+//! interpreter charges it to the frame, so agent work counts against the gas limit. Agents
+//! are deployed by the executing contract, and deployments are journaled with the host's
+//! checkpoints.
+//!
+//! Agent results are not reproducible across nodes, so the first executor can run with a
+//! `RecordingAgentHost`, which stores the outcome of every call in an `AgentWitness`, in call
+//! order per `agent_call_hash`. Validators execute with a `ReplayAgentHost` over that witness
+//! and never contact the agents; each call is answered with the next outcome recorded for its
+//! hash, and a call with none left fails the frame making it.
+
+use crate::{
+    core::ExecutionResult,
+    crypto::RvmCrypto,
+    custom_opcodes::{OpcodeContext, OpcodeHandler, OpcodeRegistry},
+    error::RvmError,
+    ghostchain_services::GhostChainServices,
+    host::{CallInputs, Host, Log},
+    opcodes::Opcode,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+
+/// Gas per 32-byte word of request data sent to an agent
+pub const AGENT_WORD_GAS: u64 = 3;
+
+/// Request made to an agent host
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum AgentRequest {
+    /// Invoke a function of a named agent
+    Call { agent: String, function: String, input: Vec<u8> },
+    /// Deploy a new named agent
+    Deploy { agent: String, definition: Vec<u8> },
+    /// Read a state entry of a named agent
+    Query { agent: String, key: String },
+}
+
+/// Response of an agent host
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AgentResponse {
+    /// Response payload
+    pub output: Vec<u8>,
+    /// Gas consumed by the agent's work
+    pub gas_used: u64,
+}
+
+/// Agents available to executing contracts
+pub trait AgentHost: Send {
+    /// Serve a request from `caller`; `gas_limit` is the gas left in the calling frame
+    fn handle(&mut self, caller: [u8; 20], request: &AgentRequest, gas_limit: u64) -> Result<AgentResponse, RvmError>;
+
+    /// Open a journal checkpoint
+    fn checkpoint(&mut self) -> usize {
+        0
+    }
+
+    /// Undo every change made since the checkpoint
+    fn revert_to(&mut self, _checkpoint: usize) {}
+
+    /// Discard the journal once an execution is final
+    fn commit(&mut self) {}
+}
+
+/// Hash identifying an agent request made by `caller`
+pub fn agent_call_hash(caller: &[u8; 20], request: &AgentRequest) -> [u8; 32] {
+    let mut preimage = caller.to_vec();
+    preimage.extend(serde_json::to_vec(request).expect("agent requests serialize"));
+    RvmCrypto::keccak256(&preimage)
+}
+
+/// Outcome of an agent request as stored in a witness
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AgentRecord {
+    /// The agent responded
+    Response(AgentResponse),
+    /// The agent rejected the request
+    Failed(String),
+}
+
+/// Agent outcomes of one execution, keyed by hex-encoded call hash
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AgentWitness {
+    /// Recorded outcomes of each call hash, in call order
+    pub records: BTreeMap<String, Vec<AgentRecord>>,
+}
+
+impl AgentWitness {
+    /// Create an empty witness
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Outcomes recorded for a call hash, in call order
+    pub fn get(&self, call_hash: &[u8; 32]) -> &[AgentRecord] {
+        self.records.get(&hex::encode(call_hash)).map_or(&[], Vec::as_slice)
+    }
+
+    /// Record the outcome of the next call with this hash
+    pub fn insert(&mut self, call_hash: [u8; 32], record: AgentRecord) {
+        self.records.entry(hex::encode(call_hash)).or_default().push(record);
+    }
+
+    /// Number of recorded calls
+    pub fn len(&self) -> usize {
+        self.records.values().map(Vec::len).sum()
+    }
+
+    /// Whether no call was recorded
+    pub fn is_empty(&self) -> bool {
+        self.records.is_empty()
+    }
+}
+
+impl AgentRecord {
+    /// Outcome as returned by an agent host
+    fn to_result(&self) -> Result<AgentResponse, RvmError> {
+        match self {
+            AgentRecord::Response(response) => Ok(response.clone()),
+            AgentRecord::Failed(message) => Err(RvmError::AgentOperationFailed(message.clone())),
+        }
+    }
+}
+
+/// Agent host wrapper recording every agent outcome into a witness
+pub struct RecordingAgentHost<'a> {
+    /// Live agents
+    inner: &'a mut dyn AgentHost,
+    /// Outcomes recorded so far
+    witness: AgentWitness,
+}
+
+impl<'a> RecordingAgentHost<'a> {
+    /// Record the outcomes of a live agent host
+    pub fn new(inner: &'a mut dyn AgentHost) -> Self {
+        Self { inner, witness: AgentWitness::new() }
+    }
+
+    /// The recorded witness
+    pub fn into_witness(self) -> AgentWitness {
+        self.witness
+    }
+}
+
+impl AgentHost for RecordingAgentHost<'_> {
+    /// Every call reaches the live host, since a reverted frame may have undone what an
+    /// earlier identical call did
+    fn handle(&mut self, caller: [u8; 20], request: &AgentRequest, gas_limit: u64) -> Result<AgentResponse, RvmError> {
+        let call_hash = agent_call_hash(&caller, request);
+        let record = match self.inner.handle(caller, request, gas_limit) {
+            Ok(response) => AgentRecord::Response(response),
+            Err(e) if is_rejection(&e) => AgentRecord::Failed(e.to_string()),
+            Err(e) => return Err(e),
+        };
+        let result = record.to_result();
+        self.witness.insert(call_hash, record);
+        result
+    }
+
+    fn checkpoint(&mut self) -> usize {
+        self.inner.checkpoint()
+    }
+
+    fn revert_to(&mut self, checkpoint: usize) {
+        self.inner.revert_to(checkpoint)
+    }
+
+    fn commit(&mut self) {
+        self.inner.commit()
+    }
+}
+
+/// Agent host answering only from a recorded witness
+#[derive(Debug, Clone, Default)]
+pub struct ReplayAgentHost {
+    witness: AgentWitness,
+    /// Outcomes already served, by call hash
+    served: HashMap<[u8; 32], usize>,
+}
+
+impl ReplayAgentHost {
+    /// Replay the outcomes of a witness
+    pub fn new(witness: AgentWitness) -> Self {
+        Self { witness, served: HashMap::new() }
+    }
+}
+
+impl AgentHost for ReplayAgentHost {
+    fn handle(&mut self, caller: [u8; 20], request: &AgentRequest, _gas_limit: u64) -> Result<AgentResponse, RvmError> {
+        let call_hash = agent_call_hash(&caller, request);
+        let served = self.served.entry(call_hash).or_default();
+        let record = self.witness
+            .get(&call_hash)
+            .get(*served)
+            .ok_or_else(|| RvmError::AgentApiError(format!("witness has no response for call {}", hex::encode(call_hash))))?;
+        *served += 1;
+        record.to_result()
+    }
+}
+
+/// A named agent of a `ScriptedAgentHost`
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ScriptedAgent {
+    /// Account that deployed the agent
+    pub owner: [u8; 20],
+    /// Definition supplied at deployment
+    pub definition: Vec<u8>,
+    /// Responses by function name and input
+    pub responses: HashMap<String, HashMap<Vec<u8>, AgentResponse>>,
+    /// State readable through AGENT_QUERY
+    pub state: HashMap<String, Vec<u8>>,
+}
+
+/// In-memory agent host answering from recorded responses, so executions are deterministic
+#[derive(Debug, Clone, Default)]
+pub struct ScriptedAgentHost {
+    agents: HashMap<String, ScriptedAgent>,
+    /// Deployments since the last commit, with the agent they replaced
+    journal: Vec<(String, Option<ScriptedAgent>)>,
+}
+
+impl ScriptedAgentHost {
+    /// Create a host without agents
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add or replace an agent
+    pub fn insert_agent(&mut self, name: &str, agent: ScriptedAgent) {
+        self.agents.insert(name.to_string(), agent);
+    }
+
+    /// Look up an agent
+    pub fn agent(&self, name: &str) -> Option<&ScriptedAgent> {
+        self.agents.get(name)
+    }
+
+    /// Record the response of an agent function to an input
+    pub fn record_response(&mut self, name: &str, function: &str, input: &[u8], response: AgentResponse) -> Result<(), RvmError> {
+        let agent = self.agents.get_mut(name).ok_or_else(|| RvmError::AgentNotFound(name.to_string()))?;
+        agent.responses.entry(function.to_string()).or_default().insert(input.to_vec(), response);
+        Ok(())
+    }
+
+    /// Set a state entry of an agent
+    pub fn set_state(&mut self, name: &str, key: &str, value: Vec<u8>) -> Result<(), RvmError> {
+        let agent = self.agents.get_mut(name).ok_or_else(|| RvmError::AgentNotFound(name.to_string()))?;
+        agent.state.insert(key.to_string(), value);
+        Ok(())
+    }
+}
+
+impl AgentHost for ScriptedAgentHost {
+    fn handle(&mut self, caller: [u8; 20], request: &AgentRequest, _gas_limit: u64) -> Result<AgentResponse, RvmError> {
+        match request {
+            AgentRequest::Call { agent, function, input } => {
+                let scripted = self.agents.get(agent).ok_or_else(|| RvmError::AgentNotFound(agent.clone()))?;
+                scripted.responses
+                    .get(function)
+                    .and_then(|responses| responses.get(input))
+                    .cloned()
+                    .ok_or_else(|| RvmError::AgentOperationFailed(format!("no recorded response for {}.{}", agent, function)))
+            }
+            AgentRequest::Deploy { agent, definition } => {
+                if agent.is_empty() || self.agents.contains_key(agent) {
+                    return Err(RvmError::AgentDeploymentFailed(format!("agent name '{}' is unavailable", agent)));
+                }
+                let deployed = ScriptedAgent { owner: caller, definition: definition.clone(), ..Default::default() };
+                let previous = self.agents.insert(agent.clone(), deployed);
+                self.journal.push((agent.clone(), previous));
+                Ok(AgentResponse::default())
+            }
+            AgentRequest::Query { agent, key } => {
+                let scripted = self.agents.get(agent).ok_or_else(|| RvmError::AgentNotFound(agent.clone()))?;
+                let output = scripted.state
+                    .get(key)
+                    .cloned()
+                    .ok_or_else(|| RvmError::AgentQueryFailed(format!("{} has no state '{}'", agent, key)))?;
+                Ok(AgentResponse { output, gas_used: 0 })
+            }
+        }
+    }
+
+    fn checkpoint(&mut self) -> usize {
+        self.journal.len()
+    }
+
+    fn revert_to(&mut self, checkpoint: usize) {
+        while self.journal.len() > checkpoint {
+            let Some((name, previous)) = self.journal.pop() else { break };
+            match previous {
+                Some(agent) => {
+                    self.agents.insert(name, agent);
+                }
+                None => {
+                    self.agents.remove(&name);
+                }
+            }
+        }
+    }
+
+    fn commit(&mut self) {
+        self.journal.clear();
+    }
+}
+
+/// Host wrapper that exposes an agent host to the interpreter
+pub struct AgentEnabledHost<'a> {
+    /// Wrapped world state
+    inner: &'a mut dyn Host,
+    /// Agents
+    agents: &'a mut dyn AgentHost,
+    /// Open checkpoints as (inner checkpoint, agent checkpoint)
+    checkpoints: Vec<(usize, usize)>,
+}
+
+impl<'a> AgentEnabledHost<'a> {
+    /// Wrap a host with an agent host
+    pub fn new(inner: &'a mut dyn Host, agents: &'a mut dyn AgentHost) -> Self {
+        Self { inner, agents, checkpoints: Vec::new() }
+    }
+}
+
+impl Host for AgentEnabledHost<'_> {
+    fn balance(&self, address: &[u8; 20]) -> u64 {
+        self.inner.balance(address)
+    }
+
+    fn transfer(&mut self, from: [u8; 20], to: [u8; 20], amount: u64) -> Result<(), RvmError> {
+        self.inner.transfer(from, to, amount)
+    }
+
+    fn nonce(&self, address: &[u8; 20]) -> u64 {
+        self.inner.nonce(address)
+    }
+
+    fn increment_nonce(&mut self, address: [u8; 20]) {
+        self.inner.increment_nonce(address)
+    }
+
+    fn code(&self, address: &[u8; 20]) -> Option<Vec<u8>> {
+        self.inner.code(address)
+    }
+
+    fn set_code(&mut self, address: [u8; 20], code: Vec<u8>) {
+        self.inner.set_code(address, code)
+    }
+
+    fn sload(&self, address: &[u8; 20], key: u64) -> u64 {
+        self.inner.sload(address, key)
+    }
+
+    fn sstore(&mut self, address: [u8; 20], key: u64, value: u64) {
+        self.inner.sstore(address, key, value)
+    }
+
+    fn block_hash(&self, number: u64) -> [u8; 32] {
+        self.inner.block_hash(number)
+    }
+
+    fn log(&mut self, log: Log) {
+        self.inner.log(log)
+    }
+
+    fn wrapped(&self) -> Option<&dyn Host> {
+        Some(&*self.inner)
+    }
+
+    fn wrapped_mut(&mut self) -> Option<&mut dyn Host> {
+        Some(&mut *self.inner)
+    }
+
+    fn call(&mut self, inputs: &CallInputs) -> Option<ExecutionResult> {
+        self.inner.call(inputs)
+    }
+
+    fn checkpoint(&mut self) -> usize {
+        self.checkpoints.push((self.inner.checkpoint(), self.agents.checkpoint()));
+        self.checkpoints.len() - 1
+    }
+
+    fn revert_to(&mut self, checkpoint: usize) {
+        if let Some(&(inner, agents)) = self.checkpoints.get(checkpoint) {
+            self.inner.revert_to(inner);
+            self.agents.revert_to(agents);
+            self.checkpoints.truncate(checkpoint);
+        }
+    }
+
+    fn ghostchain_services(&mut self) -> Option<&mut GhostChainServices> {
+        self.inner.ghostchain_services()
+    }
+
+    fn agent_host(&mut self) -> Option<&mut dyn AgentHost> {
+        Some(&mut *self.agents)
+    }
+}
+
+/// AGENT_CALL handler
+pub struct AgentCall;
+
+/// AGENT_DEPLOY handler
+pub struct AgentDeploy;
+
+/// AGENT_QUERY handler
+pub struct AgentQuery;
+
+/// Bind the agent opcode handlers
+pub fn register_agent_opcodes(registry: &mut OpcodeRegistry) -> Result<(), RvmError> {
+    registry.register(Opcode::AGENT_CALL, AgentCall)?;
+    registry.register(Opcode::AGENT_DEPLOY, AgentDeploy)?;
+    registry.register(Opcode::AGENT_QUERY, AgentQuery)?;
+    Ok(())
+}
+
+/// Gas for sending the request data held in the given `offset, size` pairs
+fn request_gas(pairs: &[u64]) -> u64 {
+    pairs
+        .chunks(2)
+        .map(|pair| pair[1].div_ceil(32))
+        .fold(0u64, u64::saturating_add)
+        .saturating_mul(AGENT_WORD_GAS)
+}
+
+/// Send a request to the agent host and charge the agent's gas; agent failures are `None`
+fn dispatch(context: &mut OpcodeContext<'_>, request: AgentRequest) -> Result<Option<Vec<u8>>, RvmError> {
+    let caller = context.env.contract_address;
+    let gas_limit = context.gas_remaining();
+    let agents = context.host
+        .agent_host()
+        .ok_or_else(|| RvmError::AgentApiError("host provides no agent host".to_string()))?;
+    match agents.handle(caller, &request, gas_limit) {
+        Ok(response) => {
+            context.consume_gas(response.gas_used)?;
+            Ok(Some(response.output))
+        }
+        Err(e) if is_rejection(&e) => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+/// Whether an agent host error is a rejection of the request rather than a host failure
+fn is_rejection(error: &RvmError) -> bool {
+    matches!(
+        error,
+        RvmError::AgentNotFound(_)
+            | RvmError::AgentOperationFailed(_)
+            | RvmError::AgentDeploymentFailed(_)
+            | RvmError::AgentQueryFailed(_)
+    )
+}
+
+/// Copy a response into the output buffer and push its length and the success flag
+fn respond(context: &mut OpcodeContext<'_>, output: Option<Vec<u8>>, offset: u64, size: u64) -> Result<Vec<u64>, RvmError> {
+    let Some(output) = output else {
+        return Ok(vec![0, 0]);
+    };
+    let copied = output.len().min(size as usize);
+    context.write_memory(offset, &output[..copied])?;
+    Ok(vec![output.len() as u64, 1])
+}
+
+impl OpcodeHandler for AgentCall {
+    fn stack_io(&self) -> (usize, usize) {
+        (8, 2)
+    }
+
+    fn gas(&self, inputs: &[u64]) -> u64 {
+        request_gas(&inputs[..6])
+    }
+
+    fn execute(&self, context: &mut OpcodeContext<'_>) -> Result<Vec<u64>, RvmError> {
+        let [agent_offset, agent_size, function_offset, function_size, input_offset, input_size, output_offset, output_size] =
+            context.inputs[..]
+        else {
+            return Err(RvmError::StackUnderflow);
+        };
+        context.ensure_mutable()?;
+        let agent = context.read_string(agent_offset, agent_size)?;
+        let function = context.read_string(function_offset, function_size)?;
+        let input = context.read_memory(input_offset, input_size)?;
+        let (Some(agent), Some(function)) = (agent, function) else {
+            return Ok(vec![0, 0]);
+        };
+        let output = dispatch(context, AgentRequest::Call { agent, function, input })?;
+        respond(context, output, output_offset, output_size)
+    }
+}
+
+impl OpcodeHandler for AgentDeploy {
+    fn stack_io(&self) -> (usize, usize) {
+        (4, 1)
+    }
+
+    fn gas(&self, inputs: &[u64]) -> u64 {
+        request_gas(inputs)
+    }
+
+    fn execute(&self, context: &mut OpcodeContext<'_>) -> Result<Vec<u64>, RvmError> {
+        let [agent_offset, agent_size, definition_offset, definition_size] = context.inputs[..] else {
+            return Err(RvmError::StackUnderflow);
+        };
+        context.ensure_mutable()?;
+        let agent = context.read_string(agent_offset, agent_size)?;
+        let definition = context.read_memory(definition_offset, definition_size)?;
+        let Some(agent) = agent else {
+            return Ok(vec![0]);
+        };
+        let deployed = dispatch(context, AgentRequest::Deploy { agent, definition })?;
+        Ok(vec![deployed.is_some() as u64])
+    }
+}
+
+impl OpcodeHandler for AgentQuery {
+    fn stack_io(&self) -> (usize, usize) {
+        (6, 2)
+    }
+
+    fn gas(&self, inputs: &[u64]) -> u64 {
+        request_gas(&inputs[..4])
+    }
+
+    fn execute(&self, context: &mut OpcodeContext<'_>) -> Result<Vec<u64>, RvmError> {
+        let [agent_offset, agent_size, key_offset, key_size, output_offset, output_size] = context.inputs[..] else {
+            return Err(RvmError::StackUnderflow);
+        };
+        let agent = context.read_string(agent_offset, agent_size)?;
+        let key = context.read_string(key_offset, key_size)?;
+        let (Some(agent), Some(key)) = (agent, key) else {
+            return Ok(vec![0, 0]);
+        };
+        let output = dispatch(context, AgentRequest::Query { agent, key })?;
+        respond(context, output, output_offset, output_size)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        asm::assemble,
+        core::{bytes32_to_word, ExecutionEnvironment},
+        runtime::{RuntimeConfig, RvmRuntime},
+        test_utils::{store_bytes, words},
+    };
+
+    #[tokio::test]
+    async fn test_agent_opcodes() {
+        // A runtime whose oracle reports `gas_used` for price("GCC")
+        let oracle_runtime = |gas_used| {
+            let mut agents = ScriptedAgentHost::new();
+            agents.insert_agent("oracle", ScriptedAgent::default());
+            agents
+                .record_response("oracle", "price", b"GCC", AgentResponse { output: b"1.25".to_vec(), gas_used })
+                .unwrap();
+            agents.set_state("oracle", "model", b"v2".to_vec()).unwrap();
+            let mut runtime = RvmRuntime::new(RuntimeConfig::default());
+            runtime.set_agent_host(agents).unwrap();
+            runtime
+        };
+        let mut runtime = oracle_runtime(700);
+
+        // Names at 256.., call price("GCC") into 160, query "model" into 192, deploy
+        // "scribe", then return the call length and flag, query length and flag, the
+        // deploy flag and both outputs
+        let source = format!(
+            "{}{}{}{}{}
+             PUSH 32
+             PUSH 160
+             PUSH 3
+             PUSH 272
+             PUSH 5
+             PUSH 264
+             PUSH 6
+             PUSH 256
+             AGENT_CALL
+             PUSH 32
+             MSTORE
+             PUSH 0
+             MSTORE
+             PUSH 32
+             PUSH 192
+             PUSH 5
+             PUSH 280
+             PUSH 6
+             PUSH 256
+             AGENT_QUERY
+             PUSH 96
+             MSTORE
+             PUSH 64
+             MSTORE
+             PUSH 0
+             PUSH 0
+             PUSH 6
+             PUSH 288
+             AGENT_DEPLOY
+             PUSH 128
+             MSTORE
+             PUSH 224
+             PUSH 0
+             RETURN",
+            store_bytes(256, b"oracle"),
+            store_bytes(264, b"price"),
+            store_bytes(272, b"GCC"),
+            store_bytes(280, b"model"),
+            store_bytes(288, b"scribe"),
+        );
+        let code = assemble(&source).unwrap();
+        let result = runtime.execute(&code, ExecutionEnvironment::default()).await.unwrap();
+        assert!(result.success, "{:?}", result.error);
+        assert_eq!(words(&result.return_data[..160]), vec![4, 1, 2, 1, 1]);
+        assert_eq!(&result.return_data[160..164], b"1.25");
+        assert_eq!(&result.return_data[192..194], b"v2");
+
+        // A second deployment of "scribe" fails but costs the same; the agent's reported
+        // gas is charged exactly
+        let replay = runtime.execute(&code, ExecutionEnvironment::default()).await.unwrap();
+        assert_eq!(bytes32_to_word(replay.return_data[128..160].try_into().unwrap()), 0);
+        assert_eq!(replay.gas_used, result.gas_used);
+        let free = oracle_runtime(0).execute(&code, ExecutionEnvironment::default()).await.unwrap();
+        assert!(free.success, "{:?}", free.error);
+        assert_eq!(result.gas_used - free.gas_used, 700);
+
+        // Unrecorded calls push 0 and a host without agents halts execution
+        let unknown = assemble("PUSH 0\nPUSH 0\nPUSH 0\nPUSH 0\nPUSH 1\nPUSH 0\nPUSH 1\nPUSH 0\nAGENT_CALL\nSTOP").unwrap();
+        let result = runtime.execute(&unknown, ExecutionEnvironment::default()).await.unwrap();
+        assert!(result.success, "{:?}", result.error);
+
+        let mut runtime = RvmRuntime::new(RuntimeConfig::default());
+        runtime.register_opcode(Opcode::AGENT_CALL, AgentCall).unwrap();
+        let result = runtime.execute(&unknown, ExecutionEnvironment::default()).await.unwrap();
+        assert!(result.error.unwrap().contains("no agent host"));
+    }
+
+    #[tokio::test]
+    async fn test_agent_witness_record_and_replay() {
+        let mut agents = ScriptedAgentHost::new();
+        agents.insert_agent("oracle", ScriptedAgent::default());
+        agents
+            .record_response("oracle", "price", b"GCC", AgentResponse { output: b"1.25".to_vec(), gas_used: 700 })
+            .unwrap();
+        let mut executor = RvmRuntime::new(RuntimeConfig::default());
+        executor.set_agent_host(agents).unwrap();
+        executor.set_record_agent_witness(true);
+
+        // Call price("GCC") into 64 and the unknown price("SPIRIT") into 96, then return
+        // both success flags and both outputs
+        let source = format!(
+            "{}{}{}{}
+             PUSH 32
+             PUSH 64
+             PUSH 3
+             PUSH 272
+             PUSH 5
+             PUSH 264
+             PUSH 6
+             PUSH 256
+             AGENT_CALL
+             PUSH 0
+             MSTORE
+             POP
+             PUSH 32
+             PUSH 96
+             PUSH 6
+             PUSH 280
+             PUSH 5
+             PUSH 264
+             PUSH 6
+             PUSH 256
+             AGENT_CALL
+             PUSH 32
+             MSTORE
+             POP
+             PUSH 128
+             PUSH 0
+             RETURN",
+            store_bytes(256, b"oracle"),
+            store_bytes(264, b"price"),
+            store_bytes(272, b"GCC"),
+            store_bytes(280, b"SPIRIT"),
+        );
+        let code = assemble(&source).unwrap();
+        let recorded = executor.execute(&code, ExecutionEnvironment::default()).await.unwrap();
+        assert!(recorded.success, "{:?}", recorded.error);
+        assert_eq!(bytes32_to_word(recorded.return_data[..32].try_into().unwrap()), 1);
+        assert_eq!(bytes32_to_word(recorded.return_data[32..64].try_into().unwrap()), 0);
+        let witness = recorded.agent_witness.clone().unwrap();
+        assert_eq!(witness.len(), 2);
+        let call = AgentRequest::Call { agent: "oracle".to_string(), function: "price".to_string(), input: b"GCC".to_vec() };
+        assert!(matches!(
+            witness.get(&agent_call_hash(&[0u8; 20], &call)),
+            [AgentRecord::Response(response)] if response.gas_used == 700
+        ));
+
+        // A validator without agents reproduces the result from the witness
+        let witness: AgentWitness = serde_json::from_str(&serde_json::to_string(&witness).unwrap()).unwrap();
+        let mut validator = RvmRuntime::new(RuntimeConfig::default());
+        let replayed = validator.execute_replaying(&code, ExecutionEnvironment::default(), witness).await.unwrap();
+        assert!(replayed.success, "{:?}", replayed.error);
+        assert_eq!(replayed.return_data, recorded.return_data);
+        assert_eq!(replayed.gas_used, recorded.gas_used);
+        assert!(replayed.agent_witness.is_none());
+
+        // Replay never falls back to live agents
+        let missing = validator
+            .execute_replaying(&code, ExecutionEnvironment::default(), AgentWitness::new())
+            .await
+            .unwrap();
+        assert!(!missing.success);
+        assert!(missing.error.unwrap().contains("witness has no response"));
+    }
+
+    #[test]
+    fn test_scripted_deployments_are_journaled() {
+        let mut agents = ScriptedAgentHost::new();
+        let deploy = AgentRequest::Deploy { agent: "scribe".to_string(), definition: b"model".to_vec() };
+        let checkpoint = agents.checkpoint();
+        agents.handle([1u8; 20], &deploy, 0).unwrap();
+        assert_eq!(agents.agent("scribe").unwrap().owner, [1u8; 20]);
+        assert!(matches!(agents.handle([2u8; 20], &deploy, 0), Err(RvmError::AgentDeploymentFailed(_))));
+
+        agents.revert_to(checkpoint);
+        assert!(agents.agent("scribe").is_none());
+    }
+
+    #[test]
+    fn test_recording_reaches_agents_after_revert() {
+        let mut agents = ScriptedAgentHost::new();
+        let deploy = AgentRequest::Deploy { agent: "scribe".to_string(), definition: b"model".to_vec() };
+        let call_hash = agent_call_hash(&[1u8; 20], &deploy);
+
+        // A deployment undone by a revert is made again, then found taken
+        let witness = {
+            let mut recorder = RecordingAgentHost::new(&mut agents);
+            let checkpoint = recorder.checkpoint();
+            recorder.handle([1u8; 20], &deploy, 0).unwrap();
+            recorder.revert_to(checkpoint);
+            recorder.handle([1u8; 20], &deploy, 0).unwrap();
+            assert!(recorder.handle([1u8; 20], &deploy, 0).is_err());
+            recorder.into_witness()
+        };
+        assert!(agents.agent("scribe").is_some());
+        assert_eq!(witness.len(), 3);
+        assert!(matches!(
+            witness.get(&call_hash),
+            [AgentRecord::Response(_), AgentRecord::Response(_), AgentRecord::Failed(_)]
+        ));
+
+        // Replay serves the outcomes in call order
+        let mut replay = ReplayAgentHost::new(witness);
+        assert!(replay.handle([1u8; 20], &deploy, 0).is_ok());
+        assert!(replay.handle([1u8; 20], &deploy, 0).is_ok());
+        assert!(matches!(replay.handle([1u8; 20], &deploy, 0), Err(RvmError::AgentOperationFailed(_))));
+        assert!(matches!(replay.handle([1u8; 20], &deploy, 0), Err(RvmError::AgentApiError(_))));
+    }
+}
@@ -0,0 +1,339 @@
+//! Assembler
+//!
+//! Turns mnemonic text into RVM bytecode, one instruction per line:
+//!
+//! ```text
+//! ; count down from 3
+//!         PUSH 3
+//! loop:   JUMPDEST
+//!         PUSH 1
+//!         SUB
+//!         DUP1
+//!         PUSH loop       // auto-sized to the label's offset
+//!         JUMPI
+//!         STOP
+//! ```
+//!
+//! `;` and `//` start comments and `name:` defines a label at the current offset.
+//! `PUSH <value>` picks the smallest PUSHn that fits while `PUSHn <value>` keeps the
+//! size as written. Values are decimal, 0x-prefixed hex of up to 32 bytes, or label
+//! names. `.data 0x...` emits raw bytes. Mnemonics come from `Opcode`, so the GhostChain
+//! opcodes assemble like any other; EOF opcodes take their 16-bit immediate as operand.
+
+use crate::{
+    error::RvmError,
+    opcodes::{DecodedOpcode, Opcode},
+};
+use std::collections::HashMap;
+
+/// PUSH immediate
+#[derive(Debug, Clone)]
+enum Operand {
+    /// Big-endian value without leading zero bytes (at least one byte)
+    Literal(Vec<u8>),
+    /// Offset of a label
+    Label(String),
+}
+
+/// One assembled line
+#[derive(Debug, Clone)]
+enum Item {
+    /// Instruction with a fixed-size immediate (empty for most opcodes)
+    Op(DecodedOpcode, Vec<u8>),
+    /// PUSH with an explicit size, or `None` to auto-size
+    Push { size: Option<usize>, operand: Operand, line: usize },
+    /// Raw bytes
+    Data(Vec<u8>),
+}
+
+/// Assemble source text into bytecode
+pub fn assemble(source: &str) -> Result<Vec<u8>, RvmError> {
+    let mut items = Vec::new();
+    // Label name to the index of the item it precedes
+    let mut labels: HashMap<String, usize> = HashMap::new();
+
+    for (index, raw) in source.lines().enumerate() {
+        let line = index + 1;
+        let mut text = strip_comment(raw).trim();
+
+        while let Some((label, rest)) = split_label(text) {
+            if labels.insert(label.to_string(), items.len()).is_some() {
+                return Err(error(line, format!("duplicate label `{}`", label)));
+            }
+            text = rest.trim();
+        }
+        if text.is_empty() {
+            continue;
+        }
+
+        let mut tokens = text.split_whitespace();
+        let mnemonic = tokens.next().unwrap_or_default();
+        let operand = tokens.next();
+        if let Some(extra) = tokens.next() {
+            return Err(error(line, format!("unexpected `{}`", extra)));
+        }
+        items.push(parse_item(mnemonic, operand, line)?);
+    }
+
+    // Sizes of auto-sized label pushes only ever grow, so this reaches a fixed point
+    let mut sizes: Vec<usize> = items.iter().map(initial_size).collect();
+    let offsets = loop {
+        let offsets = item_offsets(&items, &sizes);
+        let mut grown = false;
+        for (item, size) in items.iter().zip(sizes.iter_mut()) {
+            if let Item::Push { size: None, operand: Operand::Label(label), line } = item {
+                let needed = minimal_bytes(resolve(&labels, &offsets, label, *line)?).len();
+                if needed > *size {
+                    *size = needed;
+                    grown = true;
+                }
+            }
+        }
+        if !grown {
+            break offsets;
+        }
+    };
+
+    let mut code = Vec::with_capacity(offsets.last().copied().unwrap_or(0));
+    for (item, size) in items.iter().zip(&sizes) {
+        match item {
+            Item::Op(opcode, immediate) => {
+                code.push(opcode.byte());
+                code.extend_from_slice(immediate);
+            }
+            Item::Push { operand, line, .. } => {
+                let value = match operand {
+                    Operand::Literal(bytes) => bytes.clone(),
+                    Operand::Label(label) => minimal_bytes(resolve(&labels, &offsets, label, *line)?),
+                };
+                if value.len() > *size {
+                    return Err(error(*line, format!("value does not fit in PUSH{}", size)));
+                }
+                code.push(Opcode::PUSH1 as u8 + (*size as u8 - 1));
+                code.extend(std::iter::repeat_n(0, size - value.len()));
+                code.extend_from_slice(&value);
+            }
+            Item::Data(bytes) => code.extend_from_slice(bytes),
+        }
+    }
+    Ok(code)
+}
+
+/// Parse a mnemonic and its operand
+fn parse_item(mnemonic: &str, operand: Option<&str>, line: usize) -> Result<Item, RvmError> {
+    if mnemonic.eq_ignore_ascii_case(".data") {
+        let text = operand.ok_or_else(|| error(line, ".data needs a hex operand".to_string()))?;
+        let hex_text = text.strip_prefix("0x").unwrap_or(text);
+        let bytes = hex::decode(hex_text).map_err(|e| error(line, format!("invalid hex `{}`: {}", text, e)))?;
+        return Ok(Item::Data(bytes));
+    }
+
+    let size = if mnemonic.eq_ignore_ascii_case("PUSH") {
+        None
+    } else {
+        let opcode = Opcode::from_name(mnemonic)
+            .ok_or_else(|| error(line, format!("unknown mnemonic `{}`", mnemonic)))?;
+        match opcode.opcode() {
+            Some(push) if push.is_push() => Some(push.push_bytes()),
+            _ => return parse_op(opcode, operand, line),
+        }
+    };
+
+    let text = operand.ok_or_else(|| error(line, format!("{} needs an operand", mnemonic)))?;
+    let operand = parse_operand(text, line)?;
+    if let (Some(size), Operand::Literal(bytes)) = (size, &operand) {
+        if bytes.len() > size {
+            return Err(error(line, format!("`{}` does not fit in PUSH{}", text, size)));
+        }
+    }
+    Ok(Item::Push { size, operand, line })
+}
+
+/// Parse a non-PUSH instruction and its immediate, if the opcode has one.
+///
+/// RJUMPV takes its jump table as comma-separated offsets, e.g. `RJUMPV 3,-5`.
+fn parse_op(opcode: DecodedOpcode, operand: Option<&str>, line: usize) -> Result<Item, RvmError> {
+    let size = opcode.immediate_bytes();
+    let immediate = match (size, operand) {
+        (0, None) => Vec::new(),
+        (0, Some(operand)) => {
+            return Err(error(line, format!("{:?} takes no operand, found `{}`", opcode, operand)));
+        }
+        (_, None) => return Err(error(line, format!("{:?} needs an operand", opcode))),
+        (_, Some(text)) if opcode == Opcode::RJUMPV => {
+            let offsets = text
+                .split(',')
+                .map(|offset| parse_immediate16(offset, line))
+                .collect::<Result<Vec<_>, _>>()?;
+            let max_index = u8::try_from(offsets.len() - 1)
+                .map_err(|_| error(line, format!("RJUMPV takes at most 256 offsets, found {}", offsets.len())))?;
+            std::iter::once(max_index).chain(offsets.into_iter().flatten()).collect()
+        }
+        (_, Some(text)) => parse_immediate16(text, line)?.to_vec(),
+    };
+    Ok(Item::Op(opcode, immediate))
+}
+
+/// Parse a 16-bit EOF immediate; negative values are relative jump offsets
+fn parse_immediate16(text: &str, line: usize) -> Result<[u8; 2], RvmError> {
+    let value: i64 = match text.strip_prefix("0x") {
+        Some(hex_text) => i64::from_str_radix(hex_text, 16),
+        None => text.parse(),
+    }
+    .map_err(|e| error(line, format!("invalid number `{}`: {}", text, e)))?;
+    let value = i16::try_from(value)
+        .map(|value| value as u16)
+        .or_else(|_| u16::try_from(value))
+        .map_err(|_| error(line, format!("`{}` does not fit in 16 bits", text)))?;
+    Ok(value.to_be_bytes())
+}
+
+/// Parse a numeric literal or label reference
+fn parse_operand(text: &str, line: usize) -> Result<Operand, RvmError> {
+    if let Some(hex_text) = text.strip_prefix("0x") {
+        let padded = if hex_text.len() % 2 == 1 { format!("0{}", hex_text) } else { hex_text.to_string() };
+        let bytes = hex::decode(&padded).map_err(|e| error(line, format!("invalid hex `{}`: {}", text, e)))?;
+        let bytes = trim_leading_zeros(&bytes);
+        if bytes.len() > 32 {
+            return Err(error(line, format!("`{}` is wider than 32 bytes", text)));
+        }
+        return Ok(Operand::Literal(bytes));
+    }
+    if text.starts_with(|c: char| c.is_ascii_digit()) {
+        let value: u128 = text.parse().map_err(|e| error(line, format!("invalid number `{}`: {}", text, e)))?;
+        return Ok(Operand::Literal(trim_leading_zeros(&value.to_be_bytes())));
+    }
+    if is_identifier(text) {
+        return Ok(Operand::Label(text.to_string()));
+    }
+    Err(error(line, format!("invalid operand `{}`", text)))
+}
+
+/// Offset of a label
+fn resolve(labels: &HashMap<String, usize>, offsets: &[usize], label: &str, line: usize) -> Result<u64, RvmError> {
+    labels
+        .get(label)
+        .map(|index| offsets[*index] as u64)
+        .ok_or_else(|| error(line, format!("undefined label `{}`", label)))
+}
+
+/// Start offset of every item, plus the total length at the end
+fn item_offsets(items: &[Item], sizes: &[usize]) -> Vec<usize> {
+    let mut offsets = Vec::with_capacity(items.len() + 1);
+    let mut offset = 0;
+    for (item, size) in items.iter().zip(sizes) {
+        offsets.push(offset);
+        // Every item but `.data` has an opcode byte before its immediate
+        offset += size + usize::from(!matches!(item, Item::Data(_)));
+    }
+    offsets.push(offset);
+    offsets
+}
+
+/// Immediate size of an item before label resolution
+fn initial_size(item: &Item) -> usize {
+    match item {
+        Item::Op(_, immediate) => immediate.len(),
+        Item::Push { size: Some(size), .. } => *size,
+        Item::Push { size: None, operand: Operand::Literal(bytes), .. } => bytes.len(),
+        Item::Push { size: None, operand: Operand::Label(_), .. } => 1,
+        Item::Data(bytes) => bytes.len(),
+    }
+}
+
+/// Big-endian bytes of a value without leading zeros
+fn minimal_bytes(value: u64) -> Vec<u8> {
+    trim_leading_zeros(&value.to_be_bytes())
+}
+
+/// Drop leading zero bytes, keeping at least one byte
+fn trim_leading_zeros(bytes: &[u8]) -> Vec<u8> {
+    let start = bytes.iter().position(|b| *b != 0).unwrap_or(bytes.len().saturating_sub(1));
+    bytes[start..].to_vec()
+}
+
+/// Split `name: rest` into the label name and the rest of the line
+fn split_label(text: &str) -> Option<(&str, &str)> {
+    let (label, rest) = text.split_once(':')?;
+    is_identifier(label).then_some((label, rest))
+}
+
+/// Whether `text` is a valid label name
+fn is_identifier(text: &str) -> bool {
+    let mut chars = text.chars();
+    matches!(chars.next(), Some(c) if c.is_ascii_alphabetic() || c == '_' || c == '.')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.')
+}
+
+/// Remove a trailing `;` or `//` comment
+fn strip_comment(line: &str) -> &str {
+    let end = [line.find(';'), line.find("//")].into_iter().flatten().min().unwrap_or(line.len());
+    &line[..end]
+}
+
+fn error(line: usize, message: String) -> RvmError {
+    RvmError::AssemblyError { line, message }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_assemble_labels_and_push_sizing() {
+        let source = "
+            ; count down from 3
+                    PUSH 3
+            loop:   JUMPDEST
+                    push1 1
+                    SUB
+                    DUP1
+                    PUSH loop       // back to the JUMPDEST
+                    JUMPI
+                    PUSH 0x0100
+                    PUSH4 7
+                    PUSH end
+                    JUMP
+                    .data 0xdead
+            end:    JUMPDEST
+                    GHOST_ID_VERIFY
+                    RJUMP -3
+                    DATALOADN 0x20
+                    AGENT_CALL
+        ";
+        let code = assemble(source).unwrap();
+        assert_eq!(
+            code,
+            vec![
+                0x60, 0x03, 0x5b, 0x60, 0x01, 0x03, 0x80, 0x60, 0x02, 0x57,
+                0x61, 0x01, 0x00, 0x63, 0x00, 0x00, 0x00, 0x07, 0x60, 0x17, 0x56,
+                0xde, 0xad, 0x5b, Opcode::GHOST_ID_VERIFY as u8,
+                0xe0, 0xff, 0xfd, 0xd1, 0x00, 0x20, 0xd0,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_assemble_label_push_grows() {
+        let mut source = String::from("PUSH far\nJUMP\n");
+        source.push_str(&".data 0x00\n".repeat(300));
+        source.push_str("far: JUMPDEST\n");
+        let code = assemble(&source).unwrap();
+        assert_eq!(&code[..4], &[0x61, 0x01, 0x30, 0x56]);
+        assert_eq!(code[0x130], 0x5b);
+    }
+
+    #[test]
+    fn test_assemble_errors() {
+        let line = |source: &str| match assemble(source) {
+            Err(RvmError::AssemblyError { line, .. }) => line,
+            other => panic!("expected assembly error, got {:?}", other),
+        };
+        assert_eq!(line("ADD\nFOO"), 2);
+        assert_eq!(line("PUSH1 0x0100"), 1);
+        assert_eq!(line("STOP\nADD 1"), 2);
+        assert_eq!(line("PUSH nowhere"), 1);
+        assert_eq!(line("a: STOP\na: STOP"), 2);
+        assert_eq!(line("\n\nPUSH"), 3);
+    }
+}
@@ -0,0 +1,282 @@
+//! Cross-Chain Bridge Opcodes
+//!
+//! `BRIDGE_SEND` appends a message to the chain's outbox; a relayer carries it to the
+//! destination chain, signs it and delivers it to that chain's inbox, where `BRIDGE_RECEIVE`
+//! consumes it. Operands, first on top of the stack:
+//!
+//! | Opcode           | Operands                                              | Result                 |
+//! |------------------|-------------------------------------------------------|------------------------|
+//! | `BRIDGE_SEND`    | destination chain, recipient, payload (`offset, size`) | message nonce          |
+//! | `BRIDGE_RECEIVE` | inbox index, output (`offset, size`)                  | length, success on top |
+//!
+//! A message is received only by its recipient contract on its destination chain, and only
+//! with a secp256k1 signature by a signer the host trusts for the source chain, checked with
+//! `GhostChainCrypto::verify_cross_chain_signature`. Each message id is consumed at most once,
+//! so replays push 0. The payload is copied to the output buffer, truncated to its size.
+
+use crate::{
+    core::word_to_address,
+    crypto::RvmCrypto,
+    custom_opcodes::{OpcodeContext, OpcodeHandler, OpcodeRegistry},
+    error::RvmError,
+    ghostchain_crypto::{CryptoAlgorithm, GhostChainCrypto, GhostSignature},
+    opcodes::Opcode,
+};
+use k256::ecdsa::SigningKey;
+use serde::{Deserialize, Serialize};
+
+/// Gas per payload byte of a sent message
+pub const BRIDGE_PAYLOAD_BYTE_GAS: u64 = 16;
+
+/// Cross-chain message
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BridgeMessage {
+    /// Chain the message was sent from
+    pub source_chain: u64,
+    /// Chain the message is addressed to
+    pub destination_chain: u64,
+    /// Position in the source chain's outbox
+    pub nonce: u64,
+    /// Contract that sent the message
+    pub sender: [u8; 20],
+    /// Contract allowed to receive the message
+    pub recipient: [u8; 20],
+    /// Message payload
+    pub payload: Vec<u8>,
+}
+
+impl BridgeMessage {
+    /// Canonical encoding, as signed by relayers
+    pub fn encode(&self) -> Vec<u8> {
+        let mut encoded = Vec::with_capacity(64 + self.payload.len());
+        encoded.extend_from_slice(&self.source_chain.to_be_bytes());
+        encoded.extend_from_slice(&self.destination_chain.to_be_bytes());
+        encoded.extend_from_slice(&self.nonce.to_be_bytes());
+        encoded.extend_from_slice(&self.sender);
+        encoded.extend_from_slice(&self.recipient);
+        encoded.extend_from_slice(&self.payload);
+        encoded
+    }
+
+    /// Message id used for replay protection
+    pub fn id(&self) -> [u8; 32] {
+        RvmCrypto::keccak256(&self.encode())
+    }
+}
+
+/// Message delivered to an inbox together with its relayer signature
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SignedBridgeMessage {
+    /// The message
+    pub message: BridgeMessage,
+    /// Relayer signature over the source chain id and the encoded message
+    pub signature: GhostSignature,
+}
+
+impl SignedBridgeMessage {
+    /// Sign a message with a relayer's secp256k1 secret key
+    pub fn sign(message: BridgeMessage, secret_key: &[u8; 32]) -> Result<Self, RvmError> {
+        let signing_key = SigningKey::from_slice(secret_key)
+            .map_err(|e| RvmError::CryptoError(format!("Invalid secret key: {}", e)))?;
+        let mut chain_message = message.source_chain.to_be_bytes().to_vec();
+        chain_message.extend(message.encode());
+        let (signature, recovery_id) = signing_key
+            .sign_prehash_recoverable(&RvmCrypto::keccak256(&chain_message))
+            .map_err(|e| RvmError::CryptoError(format!("Signing failed: {}", e)))?;
+        let public_key = signing_key.verifying_key().to_encoded_point(false).as_bytes()[1..].to_vec();
+        Ok(Self {
+            message,
+            signature: GhostSignature {
+                signature: signature.to_bytes().to_vec(),
+                algorithm: CryptoAlgorithm::Secp256k1,
+                public_key: Some(public_key),
+                recovery_id: Some(recovery_id.to_byte()),
+            },
+        })
+    }
+}
+
+/// BRIDGE_SEND handler
+pub struct BridgeSend;
+
+/// BRIDGE_RECEIVE handler
+pub struct BridgeReceive;
+
+/// Bind the bridge opcode handlers
+pub fn register_bridge_opcodes(registry: &mut OpcodeRegistry) -> Result<(), RvmError> {
+    registry.register(Opcode::BRIDGE_SEND, BridgeSend)?;
+    registry.register(Opcode::BRIDGE_RECEIVE, BridgeReceive)?;
+    Ok(())
+}
+
+impl OpcodeHandler for BridgeSend {
+    fn stack_io(&self) -> (usize, usize) {
+        (4, 1)
+    }
+
+    fn gas(&self, inputs: &[u64]) -> u64 {
+        inputs[3].saturating_mul(BRIDGE_PAYLOAD_BYTE_GAS)
+    }
+
+    fn execute(&self, context: &mut OpcodeContext<'_>) -> Result<Vec<u64>, RvmError> {
+        let [destination_chain, recipient, payload_offset, payload_size] = context.inputs[..] else {
+            return Err(RvmError::StackUnderflow);
+        };
+        context.ensure_mutable()?;
+        if destination_chain == context.env.chain_id {
+            return Err(RvmError::BridgeOperationFailed(format!(
+                "destination chain {} is the current chain",
+                destination_chain
+            )));
+        }
+        let payload = context.read_memory(payload_offset, payload_size)?;
+        let message = BridgeMessage {
+            source_chain: context.env.chain_id,
+            destination_chain,
+            nonce: context.host.bridge_nonce(),
+            sender: context.env.contract_address,
+            recipient: word_to_address(recipient),
+            payload,
+        };
+        let nonce = message.nonce;
+        context.host.bridge_send(message)?;
+        Ok(vec![nonce])
+    }
+}
+
+impl OpcodeHandler for BridgeReceive {
+    fn stack_io(&self) -> (usize, usize) {
+        (3, 2)
+    }
+
+    fn execute(&self, context: &mut OpcodeContext<'_>) -> Result<Vec<u64>, RvmError> {
+        let [index, output_offset, output_size] = context.inputs[..] else {
+            return Err(RvmError::StackUnderflow);
+        };
+        context.ensure_mutable()?;
+        let Some(signed) = context.host.bridge_message(index) else {
+            return Ok(vec![0, 0]);
+        };
+        let message = &signed.message;
+        let addressed_here = message.destination_chain == context.env.chain_id
+            && message.recipient == context.env.contract_address;
+        let trusted_signer = signed.signature.public_key
+            .as_deref()
+            .is_some_and(|key| context.host.is_bridge_signer(message.source_chain, key));
+        if !addressed_here || !trusted_signer {
+            return Ok(vec![0, 0]);
+        }
+        let verified = GhostChainCrypto::new()?
+            .verify_cross_chain_signature(message.source_chain, &message.encode(), &signed.signature)
+            .unwrap_or(false);
+        if !verified || !context.host.bridge_consume(message.id()) {
+            return Ok(vec![0, 0]);
+        }
+        let copied = message.payload.len().min(output_size as usize);
+        context.write_memory(output_offset, &message.payload[..copied])?;
+        Ok(vec![message.payload.len() as u64, 1])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        asm::assemble,
+        core::{address_to_word, bytes32_to_word},
+        host::Host,
+        revm::{REvm, DEFAULT_GAS_PRICE},
+        test_utils::words,
+    };
+
+    #[tokio::test]
+    async fn test_bridge_between_chains() {
+        let relayer_key = [0x5au8; 32];
+        let relayer = SigningKey::from_slice(&relayer_key).unwrap().verifying_key().to_encoded_point(false).as_bytes()[1..].to_vec();
+        let mut chain_a = REvm::new(1);
+        let mut chain_b = REvm::new(2);
+        let sender = word_to_address(0xa1);
+        let receiver = word_to_address(0xb2);
+        let user = [9u8; 20];
+        chain_a.set_account_balance(user, 1_000_000 * DEFAULT_GAS_PRICE).await;
+        chain_b.set_account_balance(user, 1_000_000 * DEFAULT_GAS_PRICE).await;
+
+        // Chain A: send 0x2a to chain 2's receiver, returning the nonce
+        let send = assemble(&format!(
+            "PUSH 42
+             PUSH 0
+             MSTORE
+             PUSH 8
+             PUSH 24
+             PUSH {}
+             PUSH 2
+             BRIDGE_SEND
+             PUSH 0
+             MSTORE
+             PUSH 32
+             PUSH 0
+             RETURN",
+            address_to_word(&receiver)
+        ))
+        .unwrap();
+        chain_a.storage().write().await.set_code(sender, send);
+        for nonce in 0..2 {
+            let result = chain_a.call_contract(sender, vec![], user, 0, 100_000).await.unwrap();
+            assert!(result.result.success, "{:?}", result.result.error);
+            assert_eq!(bytes32_to_word(&result.result.return_data.try_into().unwrap()), nonce);
+        }
+        let outbox = chain_a.bridge_outbox().await;
+        assert_eq!(outbox.len(), 2);
+        assert_eq!(outbox[0].source_chain, 1);
+        assert_eq!(outbox[0].sender, sender);
+        assert_eq!(outbox[0].payload, 42u64.to_be_bytes());
+
+        // Relayer: sign both messages and deliver them, plus a forgery, to chain B
+        chain_b.add_bridge_signer(1, relayer).await;
+        for (index, message) in outbox.iter().enumerate() {
+            let signed = SignedBridgeMessage::sign(message.clone(), &relayer_key).unwrap();
+            assert_eq!(chain_b.deliver_bridge_message(signed).await, index as u64);
+        }
+        let forged = SignedBridgeMessage::sign(outbox[0].clone(), &[0x77; 32]).unwrap();
+        assert_eq!(chain_b.deliver_bridge_message(forged).await, 2);
+
+        // Deliveries land with the next transaction
+        assert!(chain_b.storage().read().await.bridge_inbox().is_empty());
+
+        // Chain B: receive inbox message N, returning the payload word and the flag
+        let receive = |index: u64| {
+            assemble(&format!(
+                "PUSH 8
+                 PUSH 24
+                 PUSH {index}
+                 BRIDGE_RECEIVE
+                 PUSH 32
+                 MSTORE
+                 POP
+                 PUSH 64
+                 PUSH 0
+                 RETURN"
+            ))
+            .unwrap()
+        };
+        chain_b.storage().write().await.set_code(receiver, receive(0));
+        let result = chain_b.call_contract(receiver, vec![], user, 0, 100_000).await.unwrap();
+        assert!(result.result.success, "{:?}", result.result.error);
+        assert_eq!(words(&result.result.return_data), vec![42, 1]);
+
+        // Each message is consumed once; untrusted signatures are ignored
+        for (index, expected) in [(0, 0), (2, 0), (1, 1), (1, 0), (3, 0)] {
+            chain_b.storage().write().await.set_code(receiver, receive(index));
+            let result = chain_b.call_contract(receiver, vec![], user, 0, 100_000).await.unwrap();
+            assert!(result.result.success, "{:?}", result.result.error);
+            let flag = bytes32_to_word(result.result.return_data[32..].try_into().unwrap());
+            assert_eq!(flag, expected, "inbox message {}", index);
+        }
+        assert!(chain_b.storage().read().await.is_bridge_message_consumed(&outbox[1].id()));
+
+        // Messages for another recipient cannot be taken
+        chain_b.storage().write().await.set_code(word_to_address(0xc3), receive(0));
+        let result = chain_b.call_contract(word_to_address(0xc3), vec![], user, 0, 100_000).await.unwrap();
+        assert_eq!(bytes32_to_word(result.result.return_data[32..].try_into().unwrap()), 0);
+    }
+}
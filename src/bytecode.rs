@@ -0,0 +1,509 @@
+//! Bytecode Decoding
+//!
+//! Translates raw bytecode once into a compact instruction stream for the interpreter.
+//! PUSH immediates are pre-parsed, JUMPDEST offsets are resolved to instruction indices
+//! and the static gas of every basic block is pre-summed, so the dispatch loop charges a
+//! whole block with a single check. Decoded code is cached by code hash, evicting the least
+//! recently used programs beyond the cache capacity.
+//!
+//! Valid EOF containers decode their code sections into one stream: relative jumps are
+//! resolved to instruction indices, RJUMPV refers to a table of them and CALLF carries the
+//! index of the called section.
+
+use crate::{
+    crypto::RvmCrypto,
+    eof::EofContainer,
+    opcodes::{DataOpcode, DecodedOpcode, Opcode},
+};
+use parking_lot::Mutex;
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::Arc,
+};
+
+/// Programs kept by a `CodeCache` unless configured otherwise
+pub const DEFAULT_CODE_CACHE_CAPACITY: usize = 1024;
+
+/// Jump table entry for byte offsets that are not a JUMPDEST
+const NO_JUMPDEST: u32 = u32::MAX;
+
+/// A decoded instruction
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Instruction {
+    /// Opcode (`INVALID` for undefined bytes)
+    pub opcode: DecodedOpcode,
+    /// Byte offset in the original code
+    pub pc: u32,
+    /// PUSH value (low-order 8 bytes), DUP/SWAP depth, raw byte of an undefined opcode,
+    /// RJUMP/RJUMPI target instruction, RJUMPV jump table, CALLF section or DATALOADN offset
+    pub immediate: u64,
+    /// Static gas of the instruction
+    pub gas: u64,
+    /// Whether this instruction starts a basic block
+    pub block_start: bool,
+    /// Static gas of the whole basic block (set on block starts only)
+    pub block_gas: u64,
+    /// Static gas of the instructions following this one in its basic block
+    pub block_gas_after: u64,
+}
+
+/// Bytecode decoded into an instruction stream
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DecodedCode {
+    /// Instructions in code order
+    instructions: Vec<Instruction>,
+    /// Byte offset to instruction index, for JUMPDESTs
+    jump_table: Vec<u32>,
+    /// EOF code sections (empty for legacy code)
+    sections: Vec<EofSection>,
+    /// Target instructions of each RJUMPV
+    rjumpv_tables: Vec<Vec<usize>>,
+    /// EOF data section
+    data: Vec<u8>,
+}
+
+/// Code section of decoded EOF code
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EofSection {
+    /// Index of the section's first instruction
+    pub start: usize,
+    /// Stack items the section takes
+    pub inputs: u8,
+    /// Stack items the section returns
+    pub outputs: u8,
+    /// Highest stack height the section reaches
+    pub max_stack_height: u16,
+}
+
+impl DecodedCode {
+    /// Decode bytecode.
+    ///
+    /// Truncated PUSH data at the end of the code is zero-padded, and bytes that are not
+    /// a defined opcode decode to `INVALID` carrying the raw byte as immediate. Code that
+    /// starts with the EOF magic but is not a valid container decodes as legacy code, so
+    /// it fails on its first byte.
+    pub fn decode(code: &[u8]) -> Self {
+        if EofContainer::is_eof(code) {
+            if let Some(container) = EofContainer::parse(code).ok().filter(|c| c.validate().is_ok()) {
+                return Self::decode_eof(code, &container);
+            }
+        }
+
+        let mut instructions: Vec<Instruction> = Vec::with_capacity(code.len());
+        let mut jump_table = vec![NO_JUMPDEST; code.len()];
+
+        let mut pc = 0;
+        while pc < code.len() {
+            let byte = code[pc];
+            let (opcode, immediate, size) = match Opcode::from_byte(byte) {
+                Ok(opcode) => {
+                    let (immediate, size) = Self::read_immediate(opcode.into(), code, pc);
+                    (opcode, immediate, size)
+                }
+                Err(_) => (Opcode::INVALID, byte as u64, 1),
+            };
+
+            if opcode == Opcode::JUMPDEST {
+                jump_table[pc] = instructions.len() as u32;
+            }
+            let block_start = match instructions.last() {
+                None => true,
+                Some(previous) => opcode == Opcode::JUMPDEST || Self::ends_block(previous.opcode),
+            };
+            instructions.push(Instruction {
+                opcode: opcode.into(),
+                pc: pc as u32,
+                immediate,
+                gas: opcode.gas_cost(),
+                block_start,
+                block_gas: 0,
+                block_gas_after: 0,
+            });
+            pc += size;
+        }
+
+        Self::sum_block_gas(&mut instructions);
+        Self { instructions, jump_table, sections: Vec::new(), rjumpv_tables: Vec::new(), data: Vec::new() }
+    }
+
+    /// Decode the code sections of a validated EOF container
+    fn decode_eof(code: &[u8], container: &EofContainer) -> Self {
+        let mut instructions: Vec<Instruction> = Vec::with_capacity(code.len());
+        let mut sections = Vec::with_capacity(container.code_sections.len());
+        let mut relative_jumps = Vec::new();
+        let mut rjumpv_tables: Vec<Vec<usize>> = Vec::new();
+
+        for (index, (section, signature)) in container.code_sections.iter().zip(&container.types).enumerate() {
+            let base = container.code_section_offset(index);
+            sections.push(EofSection {
+                start: instructions.len(),
+                inputs: signature.inputs,
+                outputs: signature.outputs,
+                max_stack_height: signature.max_stack_height,
+            });
+
+            let mut pc = 0;
+            let mut block_start = true;
+            while pc < section.len() {
+                let opcode = Opcode::from_eof_byte(section[pc]).unwrap_or(Opcode::INVALID.into());
+                let (mut immediate, mut size) = Self::read_immediate(opcode, section, pc);
+                match opcode.opcode() {
+                    Some(Opcode::RJUMP | Opcode::RJUMPI) => {
+                        let target = (base + pc + size) as isize + immediate as u16 as i16 as isize;
+                        relative_jumps.push((instructions.len(), target as usize));
+                    }
+                    Some(Opcode::RJUMPV) => {
+                        // Table entries hold target offsets until they are resolved below
+                        size = 1 + opcode.immediate_size(section, pc);
+                        let end = (base + pc + size) as isize;
+                        let targets = section[pc + 2..pc + size]
+                            .chunks(2)
+                            .map(|offset| (end + i16::from_be_bytes([offset[0], offset[1]]) as isize) as usize)
+                            .collect();
+                        immediate = rjumpv_tables.len() as u64;
+                        rjumpv_tables.push(targets);
+                    }
+                    _ => {}
+                }
+                instructions.push(Instruction {
+                    opcode,
+                    pc: (base + pc) as u32,
+                    immediate,
+                    gas: opcode.gas_cost(),
+                    block_start,
+                    block_gas: 0,
+                    block_gas_after: 0,
+                });
+                block_start = Self::ends_block(opcode);
+                pc += size;
+            }
+        }
+
+        // Validation guarantees every target is an instruction; each starts a block
+        for (index, target_pc) in relative_jumps {
+            if let Some(target) = Self::resolve_target(&mut instructions, target_pc) {
+                instructions[index].immediate = target as u64;
+            }
+        }
+        for target in rjumpv_tables.iter_mut().flatten() {
+            if let Some(resolved) = Self::resolve_target(&mut instructions, *target) {
+                *target = resolved;
+            }
+        }
+
+        Self::sum_block_gas(&mut instructions);
+        Self {
+            instructions,
+            jump_table: vec![NO_JUMPDEST; code.len()],
+            sections,
+            rjumpv_tables,
+            data: container.data.clone(),
+        }
+    }
+
+    /// Index of the instruction at byte offset `target_pc`, marking it as a block start
+    fn resolve_target(instructions: &mut [Instruction], target_pc: usize) -> Option<usize> {
+        let target = instructions.binary_search_by_key(&(target_pc as u32), |instruction| instruction.pc).ok()?;
+        instructions[target].block_start = true;
+        Some(target)
+    }
+
+    /// Immediate value and total size of the instruction at `pc`
+    fn read_immediate(opcode: DecodedOpcode, code: &[u8], pc: usize) -> (u64, usize) {
+        let n = opcode.immediate_bytes();
+        if n > 0 {
+            let value = (0..n).fold(0u64, |value, i| {
+                (value << 8) | code.get(pc + 1 + i).copied().unwrap_or(0) as u64
+            });
+            return (value, 1 + n);
+        }
+        match code[pc] {
+            byte @ 0x80..=0x8f => ((byte - 0x7f) as u64, 1),
+            byte @ 0x90..=0x9f => ((byte - 0x8f) as u64, 1),
+            _ => (0, 1),
+        }
+    }
+
+    /// Pre-sum static gas, walking each block backwards from its end
+    fn sum_block_gas(instructions: &mut [Instruction]) {
+        let mut after = 0u64;
+        for instruction in instructions.iter_mut().rev() {
+            instruction.block_gas_after = after;
+            after += instruction.gas;
+            if instruction.block_start {
+                instruction.block_gas = after;
+                after = 0;
+            }
+        }
+    }
+
+    /// Whether a basic block ends after this opcode.
+    ///
+    /// Besides control flow, blocks end after every instruction that charges dynamic gas
+    /// or observes remaining gas, so those always see exactly the gas per-opcode metering
+    /// would leave them.
+    fn ends_block(opcode: DecodedOpcode) -> bool {
+        let Some(opcode) = opcode.opcode() else {
+            return opcode == DataOpcode::DATACOPY;
+        };
+        matches!(
+            opcode,
+            Opcode::STOP | Opcode::JUMP | Opcode::JUMPI | Opcode::RETURN | Opcode::REVERT |
+            Opcode::INVALID | Opcode::SELFDESTRUCT | Opcode::GAS | Opcode::EXP |
+            Opcode::KECCAK256 | Opcode::MLOAD | Opcode::MSTORE | Opcode::MSTORE8 |
+            Opcode::CALLDATACOPY | Opcode::CODECOPY | Opcode::EXTCODECOPY |
+            Opcode::LOG0 | Opcode::LOG1 | Opcode::LOG2 | Opcode::LOG3 | Opcode::LOG4 |
+            Opcode::CREATE | Opcode::CALL | Opcode::CALLCODE | Opcode::DELEGATECALL |
+            Opcode::CREATE2 | Opcode::STATICCALL | Opcode::RJUMP | Opcode::RJUMPI | Opcode::RJUMPV |
+            Opcode::CALLF | Opcode::RETF
+        ) || opcode.is_ghostchain()
+    }
+
+    /// Decoded instructions
+    pub fn instructions(&self) -> &[Instruction] {
+        &self.instructions
+    }
+
+    /// Resolve a jump destination to an instruction index, if it is a JUMPDEST
+    pub fn jump_target(&self, dest: u64) -> Option<usize> {
+        let index = *self.jump_table.get(usize::try_from(dest).ok()?)?;
+        (index != NO_JUMPDEST).then_some(index as usize)
+    }
+
+    /// Size of the original code in bytes
+    pub fn code_size(&self) -> usize {
+        self.jump_table.len()
+    }
+
+    /// Whether this is a validated EOF container
+    pub fn is_eof(&self) -> bool {
+        !self.sections.is_empty()
+    }
+
+    /// EOF code section by index
+    pub fn eof_section(&self, index: usize) -> Option<&EofSection> {
+        self.sections.get(index)
+    }
+
+    /// Target instructions of an RJUMPV, by the table index in its immediate
+    pub fn rjumpv_targets(&self, table: usize) -> &[usize] {
+        self.rjumpv_tables.get(table).map(Vec::as_slice).unwrap_or_default()
+    }
+
+    /// EOF data section (empty for legacy code)
+    pub fn eof_data(&self) -> &[u8] {
+        &self.data
+    }
+}
+
+/// Cache of decoded code, keyed by code hash, holding at most `capacity` programs
+#[derive(Debug)]
+pub struct CodeCache {
+    state: Mutex<CacheState>,
+    capacity: usize,
+}
+
+/// Cached programs with their use order
+#[derive(Debug, Default)]
+struct CacheState {
+    /// Programs with the clock value of their last use
+    entries: HashMap<[u8; 32], (Arc<DecodedCode>, u64)>,
+    /// Code hashes by last use, oldest first, for O(log n) LRU eviction
+    order: BTreeMap<u64, [u8; 32]>,
+    /// Use counter stamped on entries
+    clock: u64,
+}
+
+impl CacheState {
+    /// Stamp a use of `hash` and return its program, if cached
+    fn touch(&mut self, hash: &[u8; 32]) -> Option<Arc<DecodedCode>> {
+        let now = self.tick();
+        let (code, last_used) = self.entries.get_mut(hash)?;
+        self.order.remove(last_used);
+        self.order.insert(now, *hash);
+        *last_used = now;
+        Some(Arc::clone(code))
+    }
+
+    /// Advance the use counter
+    fn tick(&mut self) -> u64 {
+        self.clock += 1;
+        self.clock
+    }
+}
+
+impl Default for CodeCache {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CodeCache {
+    /// Create an empty cache with the default capacity
+    pub fn new() -> Self {
+        Self::with_capacity(DEFAULT_CODE_CACHE_CAPACITY)
+    }
+
+    /// Create an empty cache holding at most `capacity` programs (at least one)
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            state: Mutex::new(CacheState::default()),
+            capacity: capacity.max(1),
+        }
+    }
+
+    /// Get decoded code, decoding and caching it on first use
+    pub fn get_or_decode(&self, code: &[u8]) -> Arc<DecodedCode> {
+        let hash = RvmCrypto::keccak256(code);
+        if let Some(decoded) = self.state.lock().touch(&hash) {
+            return decoded;
+        }
+
+        // Decode without holding the lock; another thread may have cached it meanwhile
+        let decoded = Arc::new(DecodedCode::decode(code));
+        let mut state = self.state.lock();
+        if let Some(cached) = state.touch(&hash) {
+            return cached;
+        }
+        while state.entries.len() >= self.capacity {
+            match state.order.pop_first() {
+                Some((_, oldest)) => { state.entries.remove(&oldest); }
+                None => break,
+            }
+        }
+        let now = state.tick();
+        state.order.insert(now, hash);
+        state.entries.insert(hash, (Arc::clone(&decoded), now));
+        decoded
+    }
+
+    /// Maximum number of cached programs
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Number of cached programs
+    pub fn len(&self) -> usize {
+        self.state.lock().entries.len()
+    }
+
+    /// Whether the cache is empty
+    pub fn is_empty(&self) -> bool {
+        self.state.lock().entries.is_empty()
+    }
+
+    /// Drop all cached programs
+    pub fn clear(&self) {
+        let mut state = self.state.lock();
+        state.entries.clear();
+        state.order.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_push_immediates() {
+        // PUSH2 0x0102, PUSH32 (low-order 8 bytes kept), PUSH3 truncated
+        let mut code = vec![0x61, 0x01, 0x02, 0x7f];
+        code.extend_from_slice(&[0xff; 24]);
+        code.extend_from_slice(&0x1122334455667788u64.to_be_bytes());
+        code.extend_from_slice(&[0x62, 0xaa]);
+
+        let decoded = DecodedCode::decode(&code);
+        let instructions = decoded.instructions();
+        assert_eq!(instructions.len(), 3);
+        assert_eq!(instructions[0].immediate, 0x0102);
+        assert_eq!(instructions[1].immediate, 0x1122334455667788);
+        assert_eq!(instructions[1].pc, 3);
+        assert_eq!(instructions[2].immediate, 0xaa0000);
+    }
+
+    #[test]
+    fn test_jump_targets_and_block_gas() {
+        // PUSH1 4, JUMP, INVALID byte 0x0c, JUMPDEST, PUSH1 0x5b, POP, STOP
+        let code = vec![0x60, 0x04, 0x56, 0x0c, 0x5b, 0x60, 0x5b, 0x50, 0x00];
+        let decoded = DecodedCode::decode(&code);
+        let instructions = decoded.instructions();
+
+        assert_eq!(decoded.jump_target(4), Some(3));
+        assert_eq!(decoded.jump_target(6), None); // PUSH data, not a JUMPDEST
+        assert_eq!(decoded.jump_target(100), None);
+        assert_eq!(instructions[2].opcode, Opcode::INVALID);
+        assert_eq!(instructions[2].immediate, 0x0c);
+
+        // PUSH1 + JUMP
+        assert!(instructions[0].block_start);
+        assert_eq!(instructions[0].block_gas, 3 + 8);
+        assert_eq!(instructions[0].block_gas_after, 8);
+        // JUMPDEST + PUSH1 + POP + STOP
+        assert!(instructions[3].block_start);
+        assert_eq!(instructions[3].block_gas, 1 + 3 + 2);
+        assert_eq!(instructions[5].block_gas_after, 0);
+    }
+
+    #[test]
+    fn test_decode_eof_sections() {
+        use crate::eof::{TypeSection, NON_RETURNING};
+
+        // DATALOADN 0, RJUMPI +3 (over CALLF), CALLF 1, STOP | RETF
+        let container = EofContainer {
+            types: vec![
+                TypeSection { inputs: 0, outputs: NON_RETURNING, max_stack_height: 1 },
+                TypeSection { inputs: 0, outputs: 0, max_stack_height: 0 },
+            ],
+            code_sections: vec![vec![0xd1, 0x00, 0x00, 0xe1, 0x00, 0x03, 0xe3, 0x00, 0x01, 0x00], vec![0xe4]],
+            data: vec![0; 32],
+        };
+        let code = container.encode();
+        let decoded = DecodedCode::decode(&code);
+        let instructions = decoded.instructions();
+
+        assert!(decoded.is_eof());
+        assert_eq!(decoded.eof_data(), &[0; 32]);
+        assert_eq!(instructions[0].opcode, DataOpcode::DATALOADN);
+        assert_eq!(instructions[0].pc as usize, container.code_section_offset(0));
+        assert_eq!(instructions[1].immediate, 3);
+        assert!(instructions[2].block_start);
+        assert_eq!(instructions[2].immediate, 1);
+        assert!(instructions[3].block_start);
+        assert_eq!(decoded.eof_section(1).unwrap().start, 4);
+        assert_eq!(instructions[4].opcode, Opcode::RETF);
+
+        // Legacy code keeps the AGENT_* opcodes at the data section encodings
+        let legacy = DecodedCode::decode(&[0xd1, 0xd3]);
+        assert!(!legacy.is_eof());
+        assert_eq!(legacy.instructions()[0].opcode, Opcode::AGENT_DEPLOY);
+        assert_eq!(legacy.instructions()[1].opcode, Opcode::INVALID);
+        assert_eq!(legacy.instructions()[1].immediate, 0xd3);
+    }
+
+    #[test]
+    fn test_code_cache_reuses_decoded_code() {
+        let cache = CodeCache::new();
+        let first = cache.get_or_decode(&[0x60, 0x01, 0x00]);
+        let second = cache.get_or_decode(&[0x60, 0x01, 0x00]);
+        assert!(Arc::ptr_eq(&first, &second));
+        assert_eq!(cache.len(), 1);
+    }
+
+    #[test]
+    fn test_code_cache_evicts_least_recently_used() {
+        let cache = CodeCache::with_capacity(2);
+        let a = cache.get_or_decode(&[0x60, 0x01]);
+        cache.get_or_decode(&[0x60, 0x02]);
+        // Touch the first program so the second becomes the oldest
+        cache.get_or_decode(&[0x60, 0x01]);
+        cache.get_or_decode(&[0x60, 0x03]);
+        assert_eq!(cache.len(), 2);
+        assert!(Arc::ptr_eq(&a, &cache.get_or_decode(&[0x60, 0x01])));
+
+        // Unique code never grows the cache past its capacity
+        for i in 0..=255u8 {
+            cache.get_or_decode(&[0x60, i, 0x00]);
+        }
+        assert_eq!(cache.len(), 2);
+    }
+}
@@ -0,0 +1,177 @@
+//! Call Tracer
+//!
+//! Nested call tree of a transaction in the JSON shape of geth's `callTracer`.
+
+use crate::{
+    core::ExecutionResult,
+    host::{CallInputs, CallKind},
+    inspector::Inspector,
+};
+use serde::{Deserialize, Serialize};
+
+/// Selector of the Solidity `Error(string)` revert payload
+const ERROR_SELECTOR: [u8; 4] = [0x08, 0xc3, 0x79, 0xa0];
+
+/// One call frame of the call tree
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CallFrame {
+    /// Frame type: CALL, STATICCALL or CREATE
+    #[serde(rename = "type")]
+    pub call_type: String,
+    /// Caller address (hex)
+    pub from: String,
+    /// Callee or created address (hex)
+    pub to: String,
+    /// Value transferred (hex), absent for STATICCALL
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub value: Option<String>,
+    /// Gas available to the frame (hex)
+    pub gas: String,
+    /// Gas used by the frame (hex)
+    pub gas_used: String,
+    /// Call data or init code (hex)
+    pub input: String,
+    /// Return data, or deployed code for CREATE (hex)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub output: Option<String>,
+    /// Error message, if the frame failed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// Decoded `Error(string)` revert reason
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub revert_reason: Option<String>,
+    /// Nested frames, in call order
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub calls: Vec<CallFrame>,
+}
+
+impl CallFrame {
+    /// Open a frame from call inputs
+    fn enter(inputs: &CallInputs) -> Self {
+        let (call_type, value) = match inputs.kind {
+            CallKind::Call => ("CALL", Some(format!("{:#x}", inputs.value))),
+            CallKind::StaticCall => ("STATICCALL", None),
+            CallKind::Create => ("CREATE", Some(format!("{:#x}", inputs.value))),
+        };
+        Self {
+            call_type: call_type.to_string(),
+            from: hex_bytes(&inputs.caller),
+            to: hex_bytes(&inputs.target),
+            value,
+            gas: format!("{:#x}", inputs.gas_limit),
+            gas_used: "0x0".to_string(),
+            input: hex_bytes(&inputs.input),
+            output: None,
+            error: None,
+            revert_reason: None,
+            calls: Vec::new(),
+        }
+    }
+
+    /// Close a frame with its result
+    fn exit(&mut self, result: &ExecutionResult) {
+        self.gas_used = format!("{:#x}", result.gas_used);
+        if !result.return_data.is_empty() {
+            self.output = Some(hex_bytes(&result.return_data));
+        }
+        if !result.success {
+            self.error = Some(result.error.clone().unwrap_or_else(|| "execution failed".to_string()));
+            self.revert_reason = decode_revert_reason(&result.return_data);
+        }
+    }
+}
+
+/// Decode the message of a Solidity `Error(string)` revert payload
+pub fn decode_revert_reason(data: &[u8]) -> Option<String> {
+    let payload = data.strip_prefix(&ERROR_SELECTOR)?;
+    let word = |offset: usize| -> Option<usize> {
+        let bytes = payload.get(offset..offset.checked_add(32)?)?;
+        if bytes[..24].iter().any(|b| *b != 0) {
+            return None;
+        }
+        usize::try_from(u64::from_be_bytes(bytes[24..].try_into().ok()?)).ok()
+    };
+    let offset = word(0)?;
+    let length = word(offset)?;
+    let start = offset.checked_add(32)?;
+    let message = payload.get(start..start.checked_add(length)?)?;
+    String::from_utf8(message.to_vec()).ok()
+}
+
+/// Hex-encode bytes with a 0x prefix
+fn hex_bytes(bytes: &[u8]) -> String {
+    format!("0x{}", hex::encode(bytes))
+}
+
+/// Inspector building the call tree
+#[derive(Debug, Default)]
+pub struct CallTracer {
+    /// Frames entered but not yet exited, outermost first
+    open: Vec<CallFrame>,
+    /// Completed outermost frame
+    root: Option<CallFrame>,
+}
+
+impl CallTracer {
+    /// Create an empty call tracer
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Completed call tree, once the outermost frame has exited
+    pub fn into_call_frame(self) -> Option<CallFrame> {
+        self.root
+    }
+
+    /// Record a frame exit
+    fn exit(&mut self, result: &ExecutionResult) {
+        if let Some(mut frame) = self.open.pop() {
+            frame.exit(result);
+            match self.open.last_mut() {
+                Some(parent) => parent.calls.push(frame),
+                None => self.root = Some(frame),
+            }
+        }
+    }
+}
+
+impl Inspector for CallTracer {
+    fn call(&mut self, inputs: &CallInputs) {
+        self.open.push(CallFrame::enter(inputs));
+    }
+
+    fn call_end(&mut self, _inputs: &CallInputs, result: &ExecutionResult) {
+        self.exit(result);
+    }
+
+    fn create(&mut self, inputs: &CallInputs) {
+        self.open.push(CallFrame::enter(inputs));
+    }
+
+    fn create_end(&mut self, _inputs: &CallInputs, result: &ExecutionResult) {
+        self.exit(result);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_revert_reason() {
+        let mut data = ERROR_SELECTOR.to_vec();
+        let mut word = [0u8; 32];
+        word[31] = 0x20;
+        data.extend_from_slice(&word);
+        word[31] = 4;
+        data.extend_from_slice(&word);
+        let mut message = [0u8; 32];
+        message[..4].copy_from_slice(b"nope");
+        data.extend_from_slice(&message);
+
+        assert_eq!(decode_revert_reason(&data), Some("nope".to_string()));
+        assert_eq!(decode_revert_reason(&data[..40]), None);
+        assert_eq!(decode_revert_reason(b"plain"), None);
+    }
+}
@@ -2,26 +2,64 @@
 //! 
 //! Stack-based bytecode interpreter with gas metering and state management.
 
-use crate::{opcodes::Opcode, gas::GasMeter, storage::Storage, error::RvmError};
+use crate::{
+    opcodes::{DataOpcode, DecodedOpcode, Opcode},
+    agents::AgentWitness,
+    bytecode::{CodeCache, DecodedCode, Instruction},
+    custom_opcodes::{OpcodeContext, OpcodeRegistry},
+    gas::{GasMeter, GasMetering},
+    ghostchain_gas::FeeReceipt,
+    host::{Host, Log, CallInputs, CallKind},
+    inspector::{Inspector, NoopInspector, Step},
+    crypto::RvmCrypto,
+    eof::MAX_RETURN_STACK,
+    error::RvmError,
+};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
-use tokio::sync::RwLock;
+use std::{collections::HashMap, sync::Arc};
 
-/// Core RVM execution context and state
+/// Upper bound on addressable memory (offsets beyond this are rejected before pricing)
+const MAX_MEMORY_SIZE: u64 = u32::MAX as u64;
+
+/// Core RVM execution context and state, metering gas with `G`
 #[derive(Debug, Clone)]
-pub struct RvmCore {
+pub struct RvmCore<G = GasMeter> {
     /// Execution stack
     pub stack: Vec<u64>,
+    /// Linear memory
+    pub memory: Vec<u8>,
+    /// Instructions to resume at after RETF (EOF code)
+    pub return_stack: Vec<usize>,
     /// Program counter
     pub pc: usize,
     /// Gas meter for execution costs
-    pub gas: GasMeter,
-    /// Contract storage
-    pub storage: Storage,
+    pub gas: G,
     /// Call depth tracking
     pub call_depth: usize,
+    /// Whether state modifications are forbidden (STATICCALL)
+    pub is_static: bool,
+    /// Data produced by RETURN or REVERT
+    pub output: Vec<u8>,
     /// Execution environment
     pub env: ExecutionEnvironment,
+    /// Decoded code shared with child frames
+    pub code_cache: Arc<CodeCache>,
+    /// Handlers for GhostChain opcodes, shared with child frames
+    pub opcode_registry: Arc<OpcodeRegistry>,
+}
+
+/// Control flow after an instruction
+enum Flow {
+    /// Continue with the next instruction
+    Continue,
+    /// Continue at an instruction index
+    Jump(usize),
+    /// Enter an EOF code section starting at an instruction index
+    Call(usize),
+    /// Return from an EOF code section
+    Return,
+    /// Stop execution
+    Halt,
 }
 
 /// Execution environment containing context data
@@ -33,12 +71,21 @@ pub struct ExecutionEnvironment {
     pub caller: [u8; 20],
     /// Call value
     pub value: u64,
+    /// Call data
+    #[serde(default)]
+    pub input: Vec<u8>,
     /// Gas price
     pub gas_price: u64,
     /// Block number
     pub block_number: u64,
     /// Block timestamp
     pub timestamp: u64,
+    /// Chain the execution belongs to
+    #[serde(default)]
+    pub chain_id: u64,
+    /// Block coinbase, paid the validator fees
+    #[serde(default)]
+    pub coinbase: [u8; 20],
 }
 
 /// Contract deployment information
@@ -61,103 +108,290 @@ pub struct ExecutionResult {
     pub return_data: Vec<u8>,
     /// Gas used
     pub gas_used: u64,
+    /// Gas refund earned by the execution, zero if it failed; the transaction applies it
+    #[serde(default)]
+    pub gas_refunded: u64,
     /// Execution success
     pub success: bool,
     /// Error message if failed
     pub error: Option<String>,
+    /// Agent responses recorded during execution, for replay by validators
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub agent_witness: Option<AgentWitness>,
+    /// Token fees settled for the execution, when the gas meter settles fees
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fee_receipt: Option<FeeReceipt>,
+    /// Logs emitted by the execution, drained from the host by the top-level caller
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub logs: Vec<Log>,
+}
+
+/// Convert an address to a stack word (its low-order 8 bytes)
+pub fn address_to_word(address: &[u8; 20]) -> u64 {
+    let mut bytes = [0u8; 8];
+    bytes.copy_from_slice(&address[12..20]);
+    u64::from_be_bytes(bytes)
+}
+
+/// Convert a stack word to an address (zero-extended on the left)
+pub fn word_to_address(word: u64) -> [u8; 20] {
+    let mut address = [0u8; 20];
+    address[12..20].copy_from_slice(&word.to_be_bytes());
+    address
+}
+
+/// Convert a stack word to a 32-byte big-endian value
+pub fn word_to_bytes32(word: u64) -> [u8; 32] {
+    let mut bytes = [0u8; 32];
+    bytes[24..32].copy_from_slice(&word.to_be_bytes());
+    bytes
+}
+
+/// Convert a 32-byte big-endian value to a stack word (its low-order 8 bytes)
+pub fn bytes32_to_word(bytes: &[u8; 32]) -> u64 {
+    let mut word = [0u8; 8];
+    word.copy_from_slice(&bytes[24..32]);
+    u64::from_be_bytes(word)
 }
 
 impl RvmCore {
     /// Create a new RVM core instance
     pub fn new(gas_limit: u64) -> Self {
+        Self::with_code_cache(gas_limit, Arc::new(CodeCache::new()))
+    }
+
+    /// Create a new RVM core instance using a shared code cache
+    pub fn with_code_cache(gas_limit: u64, code_cache: Arc<CodeCache>) -> Self {
+        Self::with_gas_meter(GasMeter::new(gas_limit), code_cache)
+    }
+}
+
+impl<G: GasMetering> RvmCore<G> {
+    /// Create a new RVM core instance metering with `gas`, using a shared code cache
+    pub fn with_gas_meter(gas: G, code_cache: Arc<CodeCache>) -> Self {
         Self {
             stack: Vec::with_capacity(1024),
+            memory: Vec::new(),
+            return_stack: Vec::new(),
             pc: 0,
-            gas: GasMeter::new(gas_limit),
-            storage: Storage::new(),
+            gas,
             call_depth: 0,
+            is_static: false,
+            output: Vec::new(),
             env: ExecutionEnvironment::default(),
+            code_cache,
+            opcode_registry: Arc::new(OpcodeRegistry::new()),
         }
     }
 
-    /// Execute bytecode with the given environment
-    pub async fn execute(&mut self, bytecode: &[u8], env: ExecutionEnvironment) -> Result<ExecutionResult, RvmError> {
+    /// Use the given handlers for GhostChain opcodes
+    pub fn with_opcode_registry(mut self, opcode_registry: Arc<OpcodeRegistry>) -> Self {
+        self.opcode_registry = opcode_registry;
+        self
+    }
+
+    /// Execute bytecode with the given environment against a host
+    pub async fn execute(
+        &mut self,
+        bytecode: &[u8],
+        env: ExecutionEnvironment,
+        host: &mut dyn Host,
+    ) -> Result<ExecutionResult, RvmError> {
+        self.execute_with_inspector(bytecode, env, host, &mut NoopInspector).await
+    }
+
+    /// Execute bytecode against a host, reporting every step to an inspector
+    pub async fn execute_with_inspector<I: Inspector>(
+        &mut self,
+        bytecode: &[u8],
+        env: ExecutionEnvironment,
+        host: &mut dyn Host,
+        inspector: &mut I,
+    ) -> Result<ExecutionResult, RvmError> {
+        Ok(self.execute_frame(bytecode, env, host, inspector))
+    }
+
+    /// Run a frame, undoing its state changes if it fails
+    fn execute_frame<I: Inspector>(
+        &mut self,
+        bytecode: &[u8],
+        env: ExecutionEnvironment,
+        host: &mut dyn Host,
+        inspector: &mut I,
+    ) -> ExecutionResult {
+        let checkpoint = host.checkpoint();
+        let result = self.run(bytecode, env, host, inspector);
+        if !result.success {
+            host.revert_to(checkpoint);
+        }
+        result
+    }
+
+    /// Interpreter loop
+    fn run<I: Inspector>(
+        &mut self,
+        bytecode: &[u8],
+        env: ExecutionEnvironment,
+        host: &mut dyn Host,
+        inspector: &mut I,
+    ) -> ExecutionResult {
         self.env = env;
         self.pc = 0;
         self.stack.clear();
+        self.memory.clear();
+        self.return_stack.clear();
+        self.output.clear();
 
-        while self.pc < bytecode.len() {
-            let opcode = Opcode::from_byte(bytecode[self.pc])?;
-            
-            // Charge base gas for opcode
-            self.gas.consume(opcode.gas_cost())?;
-            
-            match self.execute_opcode(opcode, bytecode).await {
-                Ok(should_continue) => {
-                    if !should_continue {
-                        break;
-                    }
-                }
-                Err(e) => {
-                    return Ok(ExecutionResult {
-                        return_data: vec![],
-                        gas_used: self.gas.used(),
-                        success: false,
-                        error: Some(e.to_string()),
-                    });
-                }
-            }
+        let code = self.code_cache.get_or_decode(bytecode);
+        if let Err(e) = self.interpret(&code, host, inspector) {
+            let return_data = match e {
+                RvmError::Reverted => std::mem::take(&mut self.output),
+                _ => vec![],
+            };
+            return ExecutionResult {
+                return_data,
+                gas_used: self.gas.used(),
+                gas_refunded: 0,
+                success: false,
+                error: Some(e.to_string()),
+                agent_witness: None,
+                fee_receipt: None,
+                logs: Vec::new(),
+            };
         }
 
-        Ok(ExecutionResult {
-            return_data: vec![], // Will be set by RETURN opcode
+        ExecutionResult {
+            return_data: std::mem::take(&mut self.output),
             gas_used: self.gas.used(),
+            gas_refunded: self.gas.refunded(),
             success: true,
             error: None,
-        })
+            agent_witness: None,
+            fee_receipt: None,
+            logs: Vec::new(),
+        }
     }
 
-    /// Get gas cost for opcode
-    async fn execute_opcode(&mut self, opcode: Opcode, bytecode: &[u8]) -> Result<bool, RvmError> {
-        match opcode {
-            Opcode::PUSH1 => {
-                self.pc += 1;
-                if self.pc >= bytecode.len() {
-                    return Err(RvmError::InvalidBytecode("PUSH1 without data".into()));
-                }
-                self.stack_push(bytecode[self.pc] as u64)?;
-                self.pc += 1;
+    /// Dispatch loop over decoded instructions.
+    ///
+    /// Static gas is charged once per basic block. If the block does not fit in the
+    /// remaining gas, it is charged per instruction instead so execution stops at exactly
+    /// the instruction that runs out; gas pre-charged for instructions skipped by an
+    /// error is released.
+    fn interpret<I: Inspector>(
+        &mut self,
+        code: &DecodedCode,
+        host: &mut dyn Host,
+        inspector: &mut I,
+    ) -> Result<(), RvmError> {
+        let instructions = code.instructions();
+        let observes_steps = inspector.observes_steps();
+        let mut index = 0;
+        let mut metered_per_op = false;
+
+        while let Some(instruction) = instructions.get(index) {
+            self.pc = instruction.pc as usize;
+            if instruction.block_start {
+                metered_per_op = self.gas.consume(instruction.block_gas).is_err();
             }
-            Opcode::PUSH32 => {
-                if self.pc + 32 >= bytecode.len() {
-                    return Err(RvmError::InvalidBytecode("PUSH32 without enough data".into()));
+
+            // Report gas as per-opcode metering would see it, excluding block gas charged
+            // ahead for this instruction and the rest of its block
+            let (prepaid, pending) = if metered_per_op {
+                (0, 0)
+            } else {
+                (instruction.gas, instruction.block_gas_after)
+            };
+            let gas_before = if observes_steps {
+                let gas_before = self.gas.remaining() + prepaid + pending;
+                inspector.step(&self.step(instruction.opcode, gas_before));
+                gas_before
+            } else {
+                0
+            };
+
+            let flow = if metered_per_op { self.gas.consume(instruction.gas) } else { Ok(()) }
+                .and_then(|()| self.execute_instruction(instruction, code, host, inspector));
+
+            if observes_steps {
+                let gas_after = self.gas.remaining() + pending;
+                inspector.step_end(&self.step(instruction.opcode, gas_after), gas_before - gas_after);
+            }
+
+            match flow {
+                Ok(Flow::Continue) => index += 1,
+                Ok(Flow::Jump(target)) => index = target,
+                Ok(Flow::Call(target)) => {
+                    self.return_stack.push(index + 1);
+                    index = target;
                 }
-                let mut value = 0u64;
-                for i in 1..=8 { // Take first 8 bytes for u64
-                    if self.pc + i < bytecode.len() {
-                        value = (value << 8) | (bytecode[self.pc + i] as u64);
+                Ok(Flow::Return) => match self.return_stack.pop() {
+                    Some(next) => index = next,
+                    None => break,
+                },
+                Ok(Flow::Halt) => break,
+                Err(e) => {
+                    if !metered_per_op {
+                        self.gas.release(instruction.block_gas_after);
                     }
+                    return Err(e);
                 }
-                self.stack_push(value)?;
-                self.pc += 33;
+            }
+        }
+        Ok(())
+    }
+
+    /// Interpreter state for inspectors
+    fn step(&self, opcode: DecodedOpcode, gas_remaining: u64) -> Step<'_> {
+        Step {
+            pc: self.pc,
+            opcode,
+            stack: &self.stack,
+            memory: &self.memory,
+            gas_remaining,
+            gas_refunded: self.gas.refunded(),
+            depth: self.call_depth,
+            address: self.env.contract_address,
+        }
+    }
+
+    /// Execute a single instruction
+    fn execute_instruction<I: Inspector>(
+        &mut self,
+        instruction: &Instruction,
+        code: &DecodedCode,
+        host: &mut dyn Host,
+        inspector: &mut I,
+    ) -> Result<Flow, RvmError> {
+        let opcode = match instruction.opcode {
+            DecodedOpcode::Opcode(opcode) => opcode,
+            DecodedOpcode::Data(opcode) => return self.execute_data(opcode, instruction, code),
+        };
+        match opcode {
+            Opcode::PUSH1 | Opcode::PUSH2 | Opcode::PUSH3 | Opcode::PUSH4 |
+            Opcode::PUSH5 | Opcode::PUSH6 | Opcode::PUSH7 | Opcode::PUSH8 |
+            Opcode::PUSH9 | Opcode::PUSH10 | Opcode::PUSH11 | Opcode::PUSH12 |
+            Opcode::PUSH13 | Opcode::PUSH14 | Opcode::PUSH15 | Opcode::PUSH16 |
+            Opcode::PUSH17 | Opcode::PUSH18 | Opcode::PUSH19 | Opcode::PUSH20 |
+            Opcode::PUSH21 | Opcode::PUSH22 | Opcode::PUSH23 | Opcode::PUSH24 |
+            Opcode::PUSH25 | Opcode::PUSH26 | Opcode::PUSH27 | Opcode::PUSH28 |
+            Opcode::PUSH29 | Opcode::PUSH30 | Opcode::PUSH31 | Opcode::PUSH32 => {
+                self.stack_push(instruction.immediate)?;
             }
             Opcode::ADD => {
                 let b = self.stack_pop()?;
                 let a = self.stack_pop()?;
                 self.stack_push(a.wrapping_add(b))?;
-                self.pc += 1;
             }
             Opcode::SUB => {
                 let b = self.stack_pop()?;
                 let a = self.stack_pop()?;
                 self.stack_push(a.wrapping_sub(b))?;
-                self.pc += 1;
             }
             Opcode::MUL => {
                 let b = self.stack_pop()?;
                 let a = self.stack_pop()?;
                 self.stack_push(a.wrapping_mul(b))?;
-                self.pc += 1;
             }
             Opcode::DIV => {
                 let b = self.stack_pop()?;
@@ -167,67 +401,582 @@ impl RvmCore {
                 } else {
                     self.stack_push(a / b)?;
                 }
-                self.pc += 1;
             }
-            Opcode::DUP1 => {
-                let top = self.stack_top()?;
-                self.stack_push(top)?;
-                self.pc += 1;
+            Opcode::SDIV => {
+                let b = self.stack_pop()? as i64;
+                let a = self.stack_pop()? as i64;
+                let value = if b == 0 { 0 } else { a.wrapping_div(b) };
+                self.stack_push(value as u64)?;
             }
-            Opcode::SWAP1 => {
+            Opcode::MOD => {
+                let b = self.stack_pop()?;
+                let a = self.stack_pop()?;
+                self.stack_push(a.checked_rem(b).unwrap_or(0))?;
+            }
+            Opcode::SMOD => {
+                let b = self.stack_pop()? as i64;
+                let a = self.stack_pop()? as i64;
+                let value = if b == 0 { 0 } else { a.wrapping_rem(b) };
+                self.stack_push(value as u64)?;
+            }
+            Opcode::ADDMOD | Opcode::MULMOD => {
+                let n = self.stack_pop()? as u128;
+                let b = self.stack_pop()? as u128;
+                let a = self.stack_pop()? as u128;
+                let value = match (n, opcode) {
+                    (0, _) => 0,
+                    (_, Opcode::ADDMOD) => (a + b) % n,
+                    _ => (a * b) % n,
+                };
+                self.stack_push(value as u64)?;
+            }
+            Opcode::EXP => {
+                let exponent = self.stack_pop()?;
+                let base = self.stack_pop()?;
+                let exponent_bytes = 8 - exponent.leading_zeros() as u64 / 8;
+                self.gas.consume(50 * exponent_bytes)?;
+                self.stack_push(wrapping_pow(base, exponent))?;
+            }
+            Opcode::SIGNEXTEND => {
+                let x = self.stack_pop()?;
+                let byte = self.stack_pop()?;
+                let value = if byte < 7 {
+                    let bit = byte * 8 + 7;
+                    let mask = (1u64 << (bit + 1)) - 1;
+                    if x & (1 << bit) != 0 { x | !mask } else { x & mask }
+                } else {
+                    x
+                };
+                self.stack_push(value)?;
+            }
+            Opcode::LT | Opcode::GT | Opcode::SLT | Opcode::SGT | Opcode::EQ => {
+                let b = self.stack_pop()?;
+                let a = self.stack_pop()?;
+                let value = match opcode {
+                    Opcode::LT => a < b,
+                    Opcode::GT => a > b,
+                    Opcode::SLT => (a as i64) < (b as i64),
+                    Opcode::SGT => (a as i64) > (b as i64),
+                    _ => a == b,
+                };
+                self.stack_push(value as u64)?;
+            }
+            Opcode::ISZERO => {
+                let a = self.stack_pop()?;
+                self.stack_push((a == 0) as u64)?;
+            }
+            Opcode::AND | Opcode::OR | Opcode::XOR => {
+                let b = self.stack_pop()?;
+                let a = self.stack_pop()?;
+                let value = match opcode {
+                    Opcode::AND => a & b,
+                    Opcode::OR => a | b,
+                    _ => a ^ b,
+                };
+                self.stack_push(value)?;
+            }
+            Opcode::NOT => {
+                let a = self.stack_pop()?;
+                self.stack_push(!a)?;
+            }
+            Opcode::BYTE => {
+                let x = self.stack_pop()?;
+                let index = self.stack_pop()?;
+                // Byte `index` of the 32-byte word, 0 being the most significant
+                let value = if (24..32).contains(&index) { (x >> (8 * (31 - index))) & 0xff } else { 0 };
+                self.stack_push(value)?;
+            }
+            Opcode::KECCAK256 => {
+                let offset = self.stack_pop()?;
+                let size = self.stack_pop()?;
+                let data = self.memory_slice(offset, size)?;
+                self.gas.consume(GasMeter::keccak256_gas_cost(data.len()) - Opcode::KECCAK256.gas_cost())?;
+                self.stack_push(bytes32_to_word(&RvmCrypto::keccak256(&data)))?;
+            }
+            Opcode::DUP1 | Opcode::DUP2 | Opcode::DUP3 | Opcode::DUP4 |
+            Opcode::DUP5 | Opcode::DUP6 | Opcode::DUP7 | Opcode::DUP8 |
+            Opcode::DUP9 | Opcode::DUP10 | Opcode::DUP11 | Opcode::DUP12 |
+            Opcode::DUP13 | Opcode::DUP14 | Opcode::DUP15 | Opcode::DUP16 => {
+                let depth = instruction.immediate as usize;
                 let len = self.stack.len();
-                if len < 2 {
+                if len < depth {
                     return Err(RvmError::StackUnderflow);
                 }
-                self.stack.swap(len - 1, len - 2);
-                self.pc += 1;
+                self.stack_push(self.stack[len - depth])?;
+            }
+            Opcode::SWAP1 | Opcode::SWAP2 | Opcode::SWAP3 | Opcode::SWAP4 |
+            Opcode::SWAP5 | Opcode::SWAP6 | Opcode::SWAP7 | Opcode::SWAP8 |
+            Opcode::SWAP9 | Opcode::SWAP10 | Opcode::SWAP11 | Opcode::SWAP12 |
+            Opcode::SWAP13 | Opcode::SWAP14 | Opcode::SWAP15 | Opcode::SWAP16 => {
+                let depth = instruction.immediate as usize;
+                let len = self.stack.len();
+                if len <= depth {
+                    return Err(RvmError::StackUnderflow);
+                }
+                self.stack.swap(len - 1, len - 1 - depth);
+            }
+            Opcode::POP => {
+                self.stack_pop()?;
+            }
+            Opcode::ADDRESS => {
+                self.stack_push(address_to_word(&self.env.contract_address))?;
+            }
+            Opcode::BALANCE => {
+                let address = word_to_address(self.stack_pop()?);
+                self.stack_push(host.balance(&address))?;
+            }
+            Opcode::CALLER => {
+                self.stack_push(address_to_word(&self.env.caller))?;
+            }
+            Opcode::CALLVALUE => {
+                self.stack_push(self.env.value)?;
+            }
+            Opcode::CALLDATALOAD => {
+                let offset = self.stack_pop()?;
+                let mut word = [0u8; 32];
+                if offset < self.env.input.len() as u64 {
+                    let start = offset as usize;
+                    let end = (start + 32).min(self.env.input.len());
+                    word[..end - start].copy_from_slice(&self.env.input[start..end]);
+                }
+                self.stack_push(bytes32_to_word(&word))?;
+            }
+            Opcode::CALLDATASIZE => {
+                self.stack_push(self.env.input.len() as u64)?;
+            }
+            Opcode::EXTCODESIZE => {
+                let address = word_to_address(self.stack_pop()?);
+                let size = host.code(&address).map_or(0, |code| code.len());
+                self.stack_push(size as u64)?;
+            }
+            Opcode::BLOCKHASH => {
+                let number = self.stack_pop()?;
+                self.stack_push(bytes32_to_word(&host.block_hash(number)))?;
+            }
+            Opcode::TIMESTAMP => {
+                self.stack_push(self.env.timestamp)?;
+            }
+            Opcode::NUMBER => {
+                self.stack_push(self.env.block_number)?;
+            }
+            Opcode::GASPRICE => {
+                self.stack_push(self.env.gas_price)?;
+            }
+            Opcode::GAS => {
+                self.stack_push(self.gas.remaining())?;
+            }
+            Opcode::MLOAD => {
+                let offset = self.stack_pop()?;
+                self.expand_memory(offset, 32)?;
+                let mut word = [0u8; 32];
+                word.copy_from_slice(&self.memory[offset as usize..offset as usize + 32]);
+                self.stack_push(bytes32_to_word(&word))?;
+            }
+            Opcode::MSTORE => {
+                let offset = self.stack_pop()?;
+                let value = self.stack_pop()?;
+                self.expand_memory(offset, 32)?;
+                self.memory[offset as usize..offset as usize + 32].copy_from_slice(&word_to_bytes32(value));
+            }
+            Opcode::MSTORE8 => {
+                let offset = self.stack_pop()?;
+                let value = self.stack_pop()?;
+                self.expand_memory(offset, 1)?;
+                self.memory[offset as usize] = value as u8;
+            }
+            Opcode::MSIZE => {
+                self.stack_push(self.memory.len() as u64)?;
             }
             Opcode::SSTORE => {
+                if self.is_static {
+                    return Err(RvmError::StaticCallViolation);
+                }
                 let key = self.stack_pop()?;
                 let value = self.stack_pop()?;
-                self.storage.set(key, value).await?;
-                self.pc += 1;
+                let address = self.env.contract_address;
+                let current = host.sload(&address, key);
+                let (cost, refund) = GasMeter::sstore_gas_cost(current, value, host.sload_original(&address, key));
+                // The read cost was charged with the basic block
+                self.gas.consume(cost - Opcode::SSTORE.gas_cost())?;
+                if refund >= 0 {
+                    self.gas.refund(refund as u64);
+                } else {
+                    self.gas.remove_refund(refund.unsigned_abs());
+                }
+                inspector.storage_write(&address, key, value);
+                host.sstore(address, key, value);
             }
             Opcode::SLOAD => {
                 let key = self.stack_pop()?;
-                let value = self.storage.get(key).await?;
+                let value = host.sload(&self.env.contract_address, key);
                 self.stack_push(value)?;
-                self.pc += 1;
             }
             Opcode::JUMP => {
-                let dest = self.stack_pop()? as usize;
-                if dest >= bytecode.len() {
-                    return Err(RvmError::InvalidJump(dest));
-                }
-                self.pc = dest;
+                let dest = self.stack_pop()?;
+                return Self::jump(code, dest);
             }
             Opcode::JUMPI => {
-                let dest = self.stack_pop()? as usize;
+                let dest = self.stack_pop()?;
                 let condition = self.stack_pop()?;
                 if condition != 0 {
-                    if dest >= bytecode.len() {
-                        return Err(RvmError::InvalidJump(dest));
-                    }
-                    self.pc = dest;
-                } else {
-                    self.pc += 1;
+                    return Self::jump(code, dest);
+                }
+            }
+            Opcode::JUMPDEST => {}
+            Opcode::RJUMP => {
+                return Ok(Flow::Jump(instruction.immediate as usize));
+            }
+            Opcode::RJUMPI => {
+                let condition = self.stack_pop()?;
+                if condition != 0 {
+                    return Ok(Flow::Jump(instruction.immediate as usize));
                 }
             }
+            Opcode::RJUMPV => {
+                let case = self.stack_pop()?;
+                let targets = code.rjumpv_targets(instruction.immediate as usize);
+                if let Some(target) = usize::try_from(case).ok().and_then(|case| targets.get(case)) {
+                    return Ok(Flow::Jump(*target));
+                }
+            }
+            Opcode::CALLF => {
+                let section = code.eof_section(instruction.immediate as usize)
+                    .ok_or(RvmError::InvalidOpcode(opcode as u8))?;
+                if self.return_stack.len() >= MAX_RETURN_STACK {
+                    return Err(RvmError::CallStackOverflow(self.return_stack.len() + 1));
+                }
+                let needed = section.max_stack_height as usize - section.inputs as usize;
+                if self.stack.len() + needed > crate::MAX_STACK_SIZE {
+                    return Err(RvmError::StackOverflow);
+                }
+                return Ok(Flow::Call(section.start));
+            }
+            Opcode::RETF => {
+                return Ok(Flow::Return);
+            }
+            Opcode::PC => {
+                self.stack_push(instruction.pc as u64)?;
+            }
+            Opcode::CODESIZE => {
+                self.stack_push(code.code_size() as u64)?;
+            }
+            Opcode::LOG0 | Opcode::LOG1 | Opcode::LOG2 | Opcode::LOG3 | Opcode::LOG4 => {
+                if self.is_static {
+                    return Err(RvmError::StaticCallViolation);
+                }
+                let topic_count = (opcode as u8 - Opcode::LOG0 as u8) as usize;
+                let offset = self.stack_pop()?;
+                let size = self.stack_pop()?;
+                let mut topics = Vec::with_capacity(topic_count);
+                for _ in 0..topic_count {
+                    topics.push(word_to_bytes32(self.stack_pop()?));
+                }
+                let data = self.memory_slice(offset, size)?;
+                self.gas.consume(size.saturating_mul(8))?;
+                let log = Log {
+                    address: self.env.contract_address,
+                    topics,
+                    data,
+                };
+                inspector.log(&log);
+                host.log(log);
+            }
+            Opcode::CALL | Opcode::STATICCALL => {
+                let requested_gas = self.stack_pop()?;
+                let target = word_to_address(self.stack_pop()?);
+                let value = if opcode == Opcode::CALL { self.stack_pop()? } else { 0 };
+                let args_offset = self.stack_pop()?;
+                let args_size = self.stack_pop()?;
+                let ret_offset = self.stack_pop()?;
+                let ret_size = self.stack_pop()?;
+
+                if self.is_static && value > 0 {
+                    return Err(RvmError::StaticCallViolation);
+                }
+
+                let input = self.memory_slice(args_offset, args_size)?;
+                self.expand_memory(ret_offset, ret_size)?;
+
+                let kind = if opcode == Opcode::CALL { CallKind::Call } else { CallKind::StaticCall };
+                let inputs = CallInputs {
+                    kind,
+                    caller: self.env.contract_address,
+                    target,
+                    value,
+                    input,
+                    gas_limit: self.forwarded_gas(requested_gas),
+                    depth: self.call_depth + 1,
+                };
+                let result = self.call(inputs, host, inspector);
+                self.gas.consume(result.gas_used)?;
+
+                let copy_len = result.return_data.len().min(ret_size as usize);
+                if copy_len > 0 {
+                    let start = ret_offset as usize;
+                    self.memory[start..start + copy_len].copy_from_slice(&result.return_data[..copy_len]);
+                }
+                self.stack_push(result.success as u64)?;
+            }
+            Opcode::CREATE => {
+                if self.is_static {
+                    return Err(RvmError::StaticCallViolation);
+                }
+                let value = self.stack_pop()?;
+                let offset = self.stack_pop()?;
+                let size = self.stack_pop()?;
+                let init_code = self.memory_slice(offset, size)?;
+
+                let creator = self.env.contract_address;
+                let target = RvmCrypto::create_address(&creator, host.nonce(&creator));
+                host.increment_nonce(creator);
+
+                let remaining = self.gas.remaining();
+                let inputs = CallInputs {
+                    kind: CallKind::Create,
+                    caller: creator,
+                    target,
+                    value,
+                    input: init_code,
+                    gas_limit: remaining - remaining / 64,
+                    depth: self.call_depth + 1,
+                };
+                let result = self.call(inputs, host, inspector);
+                self.gas.consume(result.gas_used)?;
+
+                let address_word = if result.success { address_to_word(&target) } else { 0 };
+                self.stack_push(address_word)?;
+            }
             Opcode::STOP => {
-                return Ok(false);
+                return Ok(Flow::Halt);
             }
             Opcode::RETURN => {
-                // For now, just stop execution
-                return Ok(false);
+                let offset = self.stack_pop()?;
+                let size = self.stack_pop()?;
+                self.output = self.memory_slice(offset, size)?;
+                return Ok(Flow::Halt);
+            }
+            Opcode::REVERT => {
+                let offset = self.stack_pop()?;
+                let size = self.stack_pop()?;
+                self.output = self.memory_slice(offset, size)?;
+                return Err(RvmError::Reverted);
+            }
+            Opcode::INVALID => {
+                return Err(RvmError::InvalidOpcode(instruction.immediate as u8));
+            }
+            _ if opcode.is_ghostchain() => {
+                self.execute_custom(opcode, host)?;
             }
             _ => {
-                self.pc += 1;
+                return Err(RvmError::InvalidOpcode(opcode as u8));
+            }
+        }
+        Ok(Flow::Continue)
+    }
+
+    /// Execute an EOF data section access
+    fn execute_data(
+        &mut self,
+        opcode: DataOpcode,
+        instruction: &Instruction,
+        code: &DecodedCode,
+    ) -> Result<Flow, RvmError> {
+        match opcode {
+            DataOpcode::DATALOAD => {
+                let offset = self.stack_pop()?;
+                self.stack_push(Self::data_word(code.eof_data(), offset))?;
+            }
+            DataOpcode::DATALOADN => {
+                self.stack_push(Self::data_word(code.eof_data(), instruction.immediate))?;
+            }
+            DataOpcode::DATASIZE => {
+                self.stack_push(code.eof_data().len() as u64)?;
+            }
+            DataOpcode::DATACOPY => {
+                let mem_offset = self.stack_pop()?;
+                let offset = self.stack_pop()?;
+                let size = self.stack_pop()?;
+                self.expand_memory(mem_offset, size)?;
+                self.gas.consume(GasMeter::copy_gas_cost(size as usize))?;
+                let data = code.eof_data();
+                for i in 0..size as usize {
+                    let byte = usize::try_from(offset).ok()
+                        .and_then(|offset| data.get(offset.checked_add(i)?))
+                        .copied()
+                        .unwrap_or(0);
+                    self.memory[mem_offset as usize + i] = byte;
+                }
+            }
+        }
+        Ok(Flow::Continue)
+    }
+
+    /// Resolve a jump destination, which must be a JUMPDEST
+    fn jump(code: &DecodedCode, dest: u64) -> Result<Flow, RvmError> {
+        code.jump_target(dest)
+            .map(Flow::Jump)
+            .ok_or(RvmError::InvalidJump(dest as usize))
+    }
+
+    /// Run the registered handler of a GhostChain opcode
+    fn execute_custom(&mut self, opcode: Opcode, host: &mut dyn Host) -> Result<(), RvmError> {
+        let handler = self.opcode_registry
+            .get(opcode)
+            .cloned()
+            .ok_or(RvmError::InvalidOpcode(opcode as u8))?;
+        let (input_count, output_count) = handler.stack_io();
+        if self.stack.len() < input_count {
+            return Err(RvmError::StackUnderflow);
+        }
+        let inputs: Vec<u64> = self.stack.drain(self.stack.len() - input_count..).rev().collect();
+        // The base cost was charged with the basic block; price it as the opcode's, then
+        // charge the handler's own gas
+        self.gas.price_for(opcode, opcode.gas_cost())?;
+        self.gas.consume_for(opcode, handler.gas(&inputs))?;
+
+        let mut context = OpcodeContext {
+            opcode,
+            inputs,
+            env: &self.env,
+            host,
+            is_static: self.is_static,
+            depth: self.call_depth,
+            memory: &mut self.memory,
+            gas: &mut self.gas,
+        };
+        let outputs = handler.execute(&mut context)?;
+        if outputs.len() != output_count {
+            return Err(RvmError::ExecutionFailed(format!(
+                "{:?} handler returned {} values, {} declared",
+                opcode,
+                outputs.len(),
+                output_count
+            )));
+        }
+        for value in outputs {
+            self.stack_push(value)?;
+        }
+        Ok(())
+    }
+
+    /// 32-byte word of EOF data at `offset`, zero-padded past the end
+    fn data_word(data: &[u8], offset: u64) -> u64 {
+        let mut word = [0u8; 32];
+        if let Ok(offset) = usize::try_from(offset) {
+            for (i, byte) in word.iter_mut().enumerate() {
+                *byte = offset.checked_add(i).and_then(|index| data.get(index)).copied().unwrap_or(0);
+            }
+        }
+        bytes32_to_word(&word)
+    }
+
+    /// Execute a message call or contract creation in a child frame
+    fn call<I: Inspector>(&mut self, inputs: CallInputs, host: &mut dyn Host, inspector: &mut I) -> ExecutionResult {
+        match inputs.kind {
+            CallKind::Create => inspector.create(&inputs),
+            CallKind::Call | CallKind::StaticCall => inspector.call(&inputs),
+        }
+        let result = self.execute_call(&inputs, host, inspector);
+        match inputs.kind {
+            CallKind::Create => inspector.create_end(&inputs, &result),
+            CallKind::Call | CallKind::StaticCall => inspector.call_end(&inputs, &result),
+        }
+        result
+    }
+
+    /// Run the callee of a message call or contract creation
+    fn execute_call<I: Inspector>(
+        &mut self,
+        inputs: &CallInputs,
+        host: &mut dyn Host,
+        inspector: &mut I,
+    ) -> ExecutionResult {
+        let failed = |error: RvmError| ExecutionResult {
+            return_data: vec![],
+            gas_used: 0,
+            gas_refunded: 0,
+            success: false,
+            error: Some(error.to_string()),
+            agent_witness: None,
+            fee_receipt: None,
+            logs: Vec::new(),
+        };
+
+        if inputs.depth > crate::MAX_CALL_DEPTH {
+            return failed(RvmError::CallStackOverflow(inputs.depth));
+        }
+
+        if let Some(result) = host.call(inputs) {
+            return result;
+        }
+
+        let checkpoint = host.checkpoint();
+        if inputs.value > 0 {
+            if let Err(e) = host.transfer(inputs.caller, inputs.target, inputs.value) {
+                host.revert_to(checkpoint);
+                return failed(e);
+            }
+        }
+
+        let (code, input) = match inputs.kind {
+            CallKind::Create => (inputs.input.clone(), vec![]),
+            CallKind::Call | CallKind::StaticCall => {
+                (host.code(&inputs.target).unwrap_or_default(), inputs.input.clone())
             }
+        };
+
+        let env = ExecutionEnvironment {
+            contract_address: inputs.target,
+            caller: inputs.caller,
+            value: inputs.value,
+            input,
+            gas_price: self.env.gas_price,
+            block_number: self.env.block_number,
+            timestamp: self.env.timestamp,
+            chain_id: self.env.chain_id,
+            coinbase: self.env.coinbase,
+        };
+
+        let mut child = RvmCore::with_gas_meter(self.gas.child(inputs.gas_limit), Arc::clone(&self.code_cache))
+            .with_opcode_registry(Arc::clone(&self.opcode_registry));
+        child.call_depth = inputs.depth;
+        child.is_static = self.is_static || inputs.kind == CallKind::StaticCall;
+
+        let result = child.execute_frame(&code, env, host, inspector);
+        if result.success {
+            self.gas.merge_child(&child.gas);
+        }
+        if inputs.kind == CallKind::Create && result.success {
+            host.set_code(inputs.target, result.return_data.clone());
         }
-        Ok(true)
+        if !result.success {
+            host.revert_to(checkpoint);
+        }
+        result
+    }
+
+    /// Gas forwarded to a child frame, capped at all but one 64th of the remaining gas
+    fn forwarded_gas(&self, requested: u64) -> u64 {
+        let remaining = self.gas.remaining();
+        requested.min(remaining - remaining / 64)
+    }
+
+    /// Expand memory to cover `offset..offset + size`, charging expansion gas
+    fn expand_memory(&mut self, offset: u64, size: u64) -> Result<(), RvmError> {
+        expand_memory(&mut self.memory, &mut self.gas, offset, size)
+    }
+
+    /// Copy `size` bytes of memory starting at `offset`, expanding as needed
+    fn memory_slice(&mut self, offset: u64, size: u64) -> Result<Vec<u8>, RvmError> {
+        if size == 0 {
+            return Ok(vec![]);
+        }
+        self.expand_memory(offset, size)?;
+        Ok(self.memory[offset as usize..(offset + size) as usize].to_vec())
     }
 
     /// Push value onto stack
+    #[inline]
     fn stack_push(&mut self, value: u64) -> Result<(), RvmError> {
         if self.stack.len() >= crate::MAX_STACK_SIZE {
             return Err(RvmError::StackOverflow);
@@ -237,45 +986,79 @@ impl RvmCore {
     }
 
     /// Pop value from stack
+    #[inline]
     fn stack_pop(&mut self) -> Result<u64, RvmError> {
         self.stack.pop().ok_or(RvmError::StackUnderflow)
     }
 
-    /// Get top stack value without popping
-    fn stack_top(&self) -> Result<u64, RvmError> {
-        self.stack.last().copied().ok_or(RvmError::StackUnderflow)
-    }
-
     /// Deploy a contract
-    pub async fn deploy_contract(&mut self, bytecode: Vec<u8>, env: ExecutionEnvironment) -> Result<[u8; 20], RvmError> {
+    pub async fn deploy_contract(
+        &mut self,
+        bytecode: Vec<u8>,
+        env: ExecutionEnvironment,
+        host: &mut dyn Host,
+    ) -> Result<[u8; 20], RvmError> {
         // Simple address generation (in production, use CREATE2 or similar)
         let mut address = [0u8; 20];
         address[0..8].copy_from_slice(&env.block_number.to_be_bytes());
         address[8..16].copy_from_slice(&env.timestamp.to_be_bytes());
-        
-        let contract = Contract {
-            bytecode,
-            address,
-            storage: HashMap::new(),
-            balance: env.value,
-        };
 
-        // Store contract (simplified storage)
-        self.storage.set_contract(address, contract).await?;
-        
+        host.set_code(address, bytecode);
+
         Ok(address)
     }
 }
 
+/// Expand memory to cover `offset..offset + size`, charging expansion gas
+pub(crate) fn expand_memory<G: GasMetering + ?Sized>(
+    memory: &mut Vec<u8>,
+    gas: &mut G,
+    offset: u64,
+    size: u64,
+) -> Result<(), RvmError> {
+    if size == 0 {
+        return Ok(());
+    }
+    let end = offset.checked_add(size)
+        .filter(|end| *end <= MAX_MEMORY_SIZE)
+        .ok_or(RvmError::MemoryOutOfBounds {
+            offset: offset as usize,
+            size: size as usize,
+            memory_size: memory.len(),
+        })? as usize;
+    if end > memory.len() {
+        let new_size = end.div_ceil(32) * 32;
+        gas.consume(GasMeter::memory_gas_cost(memory.len(), new_size))?;
+        memory.resize(new_size, 0);
+    }
+    Ok(())
+}
+
+/// Exponentiation by squaring, modulo 2^64
+fn wrapping_pow(mut base: u64, mut exponent: u64) -> u64 {
+    let mut result = 1u64;
+    while exponent > 0 {
+        if exponent & 1 == 1 {
+            result = result.wrapping_mul(base);
+        }
+        base = base.wrapping_mul(base);
+        exponent >>= 1;
+    }
+    result
+}
+
 impl Default for ExecutionEnvironment {
     fn default() -> Self {
         Self {
             contract_address: [0u8; 20],
             caller: [0u8; 20],
             value: 0,
+            input: Vec::new(),
             gas_price: 1,
             block_number: 1,
             timestamp: 1640995200, // 2022-01-01
+            chain_id: 0,
+            coinbase: [0u8; 20],
         }
     }
 }
@@ -287,12 +1070,122 @@ impl ExecutionEnvironment {
             contract_address,
             caller,
             value,
+            input: Vec::new(),
             gas_price: 1,
             block_number: 1,
             timestamp: std::time::SystemTime::now()
                 .duration_since(std::time::UNIX_EPOCH)
                 .unwrap_or_default()
                 .as_secs(),
+            chain_id: 0,
+            coinbase: [0u8; 20],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{asm::assemble, storage::Storage};
+
+    const CONTRACT: [u8; 20] = [9u8; 20];
+
+    /// Run `code` as `CONTRACT`
+    async fn execute(code: &str, storage: &mut Storage) -> ExecutionResult {
+        let env = ExecutionEnvironment::new(CONTRACT, [1u8; 20], 0);
+        RvmCore::new(100_000).execute(&assemble(code).unwrap(), env, storage).await.unwrap()
+    }
+
+    /// Run `code` as `CONTRACT` with 0x42 holding `callee`
+    async fn run(code: &str, callee: &str, storage: &mut Storage) -> ExecutionResult {
+        storage.set_code(word_to_address(0x42), assemble(callee).unwrap());
+        execute(code, storage).await
+    }
+
+    /// Call 0x42 with `call`, then store its success + 5 in slot 0
+    fn caller(call: &str) -> String {
+        let value = if call == "CALL" { "PUSH 0\n" } else { "" };
+        format!("PUSH 0\nPUSH 0\nPUSH 0\nPUSH 0\n{}PUSH 0x42\nPUSH 50000\n{}\nPUSH 5\nADD\nPUSH 0\nSSTORE\nSTOP", value, call)
+    }
+
+    #[tokio::test]
+    async fn test_reverted_child_frame_leaves_no_state() {
+        let mut storage = Storage::new();
+        let result = run(&caller("CALL"), "PUSH 7\nPUSH 1\nSSTORE\nPUSH 0\nPUSH 0\nREVERT", &mut storage).await;
+        assert!(result.success, "{:?}", result.error);
+        assert_eq!(storage.get_storage(&CONTRACT, 0), 5);
+        assert_eq!(storage.get_storage(&word_to_address(0x42), 1), 0);
+
+        let result = run(&caller("CALL"), "PUSH 7\nPUSH 1\nSSTORE\nSTOP", &mut storage).await;
+        assert!(result.success, "{:?}", result.error);
+        assert_eq!(storage.get_storage(&CONTRACT, 0), 6);
+        assert_eq!(storage.get_storage(&word_to_address(0x42), 1), 7);
+    }
+
+    #[tokio::test]
+    async fn test_static_call_rejects_state_changes() {
+        let mut storage = Storage::new();
+        let result = run(&caller("STATICCALL"), "PUSH 7\nPUSH 1\nSSTORE\nSTOP", &mut storage).await;
+        assert!(result.success, "{:?}", result.error);
+        assert_eq!(storage.get_storage(&CONTRACT, 0), 5);
+        assert_eq!(storage.get_storage(&word_to_address(0x42), 1), 0);
+
+        // Reads are allowed
+        let result = run(&caller("STATICCALL"), "PUSH 1\nSLOAD\nPOP\nSTOP", &mut storage).await;
+        assert!(result.success, "{:?}", result.error);
+        assert_eq!(storage.get_storage(&CONTRACT, 0), 6);
+    }
+
+    #[tokio::test]
+    async fn test_calls_forward_all_but_one_64th() {
+        #[derive(Default)]
+        struct Forwarded(Vec<u64>);
+
+        impl Inspector for Forwarded {
+            fn observes_steps(&self) -> bool {
+                false
+            }
+
+            fn call(&mut self, inputs: &CallInputs) {
+                self.0.push(inputs.gas_limit);
+            }
+        }
+
+        let mut storage = Storage::new();
+        storage.set_code(word_to_address(0x42), vec![0x00]);
+        // CALL(gas=GAS or 1000, to=0x42, value=0, in=0/0, out=0/0)
+        let code = |gas: &str| assemble(&format!("PUSH 0\nPUSH 0\nPUSH 0\nPUSH 0\nPUSH 0\nPUSH 0x42\n{}\nCALL\nSTOP", gas)).unwrap();
+        let mut forwarded = Forwarded::default();
+        for gas in ["GAS", "PUSH 1000"] {
+            let env = ExecutionEnvironment::new(CONTRACT, [1u8; 20], 0);
+            let result = RvmCore::new(100_000)
+                .execute_with_inspector(&code(gas), env, &mut storage, &mut forwarded)
+                .await
+                .unwrap();
+            assert!(result.success, "{:?}", result.error);
         }
+
+        // Six pushes, GAS and CALL leave 99_880; a 64th of that is kept back
+        assert_eq!(forwarded.0, vec![99_880 - 99_880 / 64, 1000]);
+    }
+
+    #[tokio::test]
+    async fn test_sstore_priced_from_original_value() {
+        let mut storage = Storage::new();
+        storage.set_storage(CONTRACT, 1, 5);
+        storage.commit();
+
+        // Setting a fresh slot, then restoring it, refunds all but the read
+        let result = execute("PUSH 7\nPUSH 2\nSSTORE\nPUSH 0\nPUSH 2\nSSTORE\nSTOP", &mut storage).await;
+        assert_eq!((result.gas_used, result.gas_refunded), (3 + 3 + 20000 + 3 + 3 + 100, 19900));
+
+        // Clearing a slot set before the transaction, then restoring it, takes the clearing
+        // refund back and refunds the reset instead
+        let result = execute("PUSH 0\nPUSH 1\nSSTORE\nPUSH 5\nPUSH 1\nSSTORE\nSTOP", &mut storage).await;
+        assert_eq!((result.gas_used, result.gas_refunded), (3 + 3 + 2900 + 3 + 3 + 100, 2800));
+
+        // Clearing it for good refunds the clearing schedule
+        let result = execute("PUSH 0\nPUSH 1\nSSTORE\nSTOP", &mut storage).await;
+        assert_eq!((result.gas_used, result.gas_refunded), (3 + 3 + 2900, 4800));
     }
 }
@@ -267,6 +267,18 @@ impl Precompiles {
         Ok(input.to_vec())
     }
 
+    /// Gas cost of a precompiled contract call
+    pub fn gas_cost(address: u8, input: &[u8]) -> Result<u64, RvmError> {
+        let words = ((input.len() + 31) / 32) as u64;
+        match address {
+            1 => Ok(3000),              // ECRECOVER
+            2 => Ok(60 + 12 * words),   // SHA256
+            3 => Ok(600 + 120 * words), // RIPEMD160
+            4 => Ok(15 + 3 * words),    // IDENTITY
+            _ => Err(RvmError::InvalidPrecompile(address)),
+        }
+    }
+
     /// Execute a precompiled contract
     pub fn execute(address: u8, input: &[u8]) -> Result<Vec<u8>, RvmError> {
         match address {
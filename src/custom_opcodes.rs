@@ -0,0 +1,217 @@
+//! Custom Opcode Handlers
+//!
+//! The GhostChain opcodes (0xc0-0xd2) have no built-in semantics. Embedders bind an
+//! `OpcodeHandler` to an opcode in an `OpcodeRegistry`; the interpreter pops the handler's
+//! inputs, charges its gas on top of the opcode's base cost, runs it with host access and
+//! pushes its outputs. Executing an opcode with no handler fails like an undefined opcode.
+
+use crate::{
+    core::{expand_memory, ExecutionEnvironment},
+    error::RvmError,
+    gas::GasMetering,
+    host::{Host, Log},
+    opcodes::Opcode,
+};
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+
+/// State available to an opcode handler
+pub struct OpcodeContext<'a> {
+    /// Opcode being executed
+    pub opcode: Opcode,
+    /// Operands popped from the stack, top of stack first
+    pub inputs: Vec<u64>,
+    /// Execution environment of the current frame
+    pub env: &'a ExecutionEnvironment,
+    /// World state
+    pub host: &'a mut dyn Host,
+    /// Whether state modifications are forbidden (STATICCALL)
+    pub is_static: bool,
+    /// Call depth of the current frame
+    pub depth: usize,
+    pub(crate) memory: &'a mut Vec<u8>,
+    pub(crate) gas: &'a mut dyn GasMetering,
+}
+
+impl<'a> OpcodeContext<'a> {
+    /// Read `size` bytes of memory at `offset`, charging expansion gas
+    pub fn read_memory(&mut self, offset: u64, size: u64) -> Result<Vec<u8>, RvmError> {
+        if size == 0 {
+            return Ok(vec![]);
+        }
+        expand_memory(self.memory, self.gas, offset, size)?;
+        Ok(self.memory[offset as usize..(offset + size) as usize].to_vec())
+    }
+
+    /// Write bytes to memory at `offset`, charging expansion gas
+    pub fn write_memory(&mut self, offset: u64, data: &[u8]) -> Result<(), RvmError> {
+        expand_memory(self.memory, self.gas, offset, data.len() as u64)?;
+        if !data.is_empty() {
+            self.memory[offset as usize..offset as usize + data.len()].copy_from_slice(data);
+        }
+        Ok(())
+    }
+
+    /// Read a UTF-8 string from memory; `None` if it is not valid UTF-8
+    pub fn read_string(&mut self, offset: u64, size: u64) -> Result<Option<String>, RvmError> {
+        Ok(String::from_utf8(self.read_memory(offset, size)?).ok())
+    }
+
+    /// Charge additional gas for the opcode
+    pub fn consume_gas(&mut self, amount: u64) -> Result<(), RvmError> {
+        self.gas.consume_for(self.opcode, amount)
+    }
+
+    /// Gas left in the current frame
+    pub fn gas_remaining(&self) -> u64 {
+        self.gas.remaining()
+    }
+
+    /// Fail with `StaticCallViolation` inside a static call
+    pub fn ensure_mutable(&self) -> Result<(), RvmError> {
+        if self.is_static {
+            return Err(RvmError::StaticCallViolation);
+        }
+        Ok(())
+    }
+
+    /// Emit a log from the executing contract
+    pub fn log(&mut self, topics: Vec<[u8; 32]>, data: Vec<u8>) -> Result<(), RvmError> {
+        self.ensure_mutable()?;
+        self.host.log(Log { address: self.env.contract_address, topics, data });
+        Ok(())
+    }
+}
+
+/// Semantics of a custom opcode
+pub trait OpcodeHandler: Send + Sync {
+    /// Stack items consumed and produced
+    fn stack_io(&self) -> (usize, usize);
+
+    /// Gas charged on top of the opcode's base cost, given the popped operands
+    fn gas(&self, _inputs: &[u64]) -> u64 {
+        0
+    }
+
+    /// Execute the opcode, returning exactly `stack_io().1` values in push order
+    fn execute(&self, context: &mut OpcodeContext<'_>) -> Result<Vec<u64>, RvmError>;
+}
+
+/// Handlers bound to GhostChain opcodes
+#[derive(Clone, Default)]
+pub struct OpcodeRegistry {
+    handlers: HashMap<Opcode, Arc<dyn OpcodeHandler>>,
+}
+
+impl OpcodeRegistry {
+    /// Create an empty registry
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Bind a handler to a GhostChain opcode, replacing any previous one
+    pub fn register<H: OpcodeHandler + 'static>(&mut self, opcode: Opcode, handler: H) -> Result<(), RvmError> {
+        if !opcode.is_ghostchain() {
+            return Err(RvmError::ConfigurationError(format!(
+                "{:?} is not in the custom opcode range 0xc0-0xd2",
+                opcode
+            )));
+        }
+        self.handlers.insert(opcode, Arc::new(handler));
+        Ok(())
+    }
+
+    /// Remove the handler of an opcode
+    pub fn unregister(&mut self, opcode: Opcode) -> bool {
+        self.handlers.remove(&opcode).is_some()
+    }
+
+    /// Handler bound to an opcode
+    pub fn get(&self, opcode: Opcode) -> Option<&Arc<dyn OpcodeHandler>> {
+        self.handlers.get(&opcode)
+    }
+
+    /// Opcodes with a handler, in byte order
+    pub fn opcodes(&self) -> Vec<Opcode> {
+        let mut opcodes: Vec<Opcode> = self.handlers.keys().copied().collect();
+        opcodes.sort();
+        opcodes
+    }
+}
+
+impl fmt::Debug for OpcodeRegistry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("OpcodeRegistry").field("opcodes", &self.opcodes()).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{asm::assemble, core::RvmCore, storage::Storage};
+
+    /// Adds its operands and stores the sum at a memory offset
+    struct AddToMemory;
+
+    impl OpcodeHandler for AddToMemory {
+        fn stack_io(&self) -> (usize, usize) {
+            (3, 1)
+        }
+
+        fn gas(&self, inputs: &[u64]) -> u64 {
+            inputs[1]
+        }
+
+        fn execute(&self, context: &mut OpcodeContext<'_>) -> Result<Vec<u64>, RvmError> {
+            let [offset, a, b] = context.inputs[..] else { unreachable!() };
+            context.write_memory(offset, &(a + b).to_be_bytes())?;
+            context.host.sstore(context.env.contract_address, 1, a + b);
+            Ok(vec![a + b])
+        }
+    }
+
+    #[tokio::test]
+    async fn test_registered_handler_runs_with_host_access() {
+        // Operands: offset 0 on top, then 100 and 5
+        let code = assemble(
+            "PUSH 5
+             PUSH 100
+             PUSH 0
+             TOKEN_BALANCE
+             PUSH 8
+             PUSH 0
+             RETURN",
+        )
+        .unwrap();
+        let mut registry = OpcodeRegistry::new();
+        registry.register(Opcode::TOKEN_BALANCE, AddToMemory).unwrap();
+        assert!(registry.register(Opcode::ADD, AddToMemory).is_err());
+
+        let mut storage = Storage::new();
+        let env = ExecutionEnvironment::default();
+        let registry = Arc::new(registry);
+        let mut core = RvmCore::new(100_000).with_opcode_registry(Arc::clone(&registry));
+        let result = core.execute(&code, env.clone(), &mut storage).await.unwrap();
+        assert!(result.success, "{:?}", result.error);
+        assert_eq!(result.return_data, 105u64.to_be_bytes());
+        assert_eq!(core.stack, vec![105]);
+        assert_eq!(storage.sload(&env.contract_address, 1), 105);
+        // 3 PUSHes, base cost, 100 handler gas, memory expansion, 2 PUSHes
+        assert_eq!(result.gas_used, 3 * 3 + Opcode::TOKEN_BALANCE.gas_cost() + 100 + 3 + 2 * 3);
+
+        // Running out of gas for the handler costs the base gas once
+        let limit = 3 * 3 + Opcode::TOKEN_BALANCE.gas_cost() + 50;
+        let result = RvmCore::new(limit)
+            .with_opcode_registry(registry)
+            .execute(&code, env.clone(), &mut storage)
+            .await
+            .unwrap();
+        assert!(!result.success);
+        assert_eq!(result.gas_used, 3 * 3 + Opcode::TOKEN_BALANCE.gas_cost());
+
+        // Without a handler the opcode is undefined
+        let result = RvmCore::new(100_000).execute(&code, env, &mut storage).await.unwrap();
+        assert_eq!(result.error, Some(RvmError::InvalidOpcode(0xc3).to_string()));
+    }
+}
@@ -0,0 +1,365 @@
+//! Step Debugger
+//!
+//! Breakpoint-driven stepping through RVM bytecode. Execution is deterministic, so rather
+//! than suspending the interpreter the debugger re-runs the code against a copy of the
+//! initial state and records the machine state at the instruction it should stop at.
+
+use crate::{
+    core::{ExecutionEnvironment, ExecutionResult, RvmCore},
+    error::RvmError,
+    host::CallInputs,
+    inspector::{Inspector, Step},
+    opcodes::{DecodedOpcode, Opcode},
+    storage::Storage,
+};
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// Condition that stops execution
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Breakpoint {
+    /// Before the instruction at this pc, in any frame
+    Pc(usize),
+    /// Before any instruction with this opcode
+    Opcode(DecodedOpcode),
+    /// Before an SSTORE, optionally only to this slot
+    StorageWrite(Option<u64>),
+    /// Before a REVERT or INVALID instruction
+    Revert,
+}
+
+impl Breakpoint {
+    /// Whether the breakpoint fires before `step`
+    fn hits(&self, step: &Step<'_>) -> bool {
+        match self {
+            Breakpoint::Pc(pc) => step.pc == *pc,
+            Breakpoint::Opcode(opcode) => step.opcode == *opcode,
+            Breakpoint::StorageWrite(slot) => {
+                step.opcode == Opcode::SSTORE
+                    && slot.is_none_or(|slot| step.stack.last() == Some(&slot))
+            }
+            Breakpoint::Revert => matches!(step.opcode, DecodedOpcode::Opcode(Opcode::REVERT | Opcode::INVALID)),
+        }
+    }
+}
+
+impl fmt::Display for Breakpoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Breakpoint::Pc(pc) => write!(f, "pc {}", pc),
+            Breakpoint::Opcode(opcode) => write!(f, "opcode {:?}", opcode),
+            Breakpoint::StorageWrite(None) => write!(f, "storage write"),
+            Breakpoint::StorageWrite(Some(slot)) => write!(f, "storage write to slot {}", slot),
+            Breakpoint::Revert => write!(f, "revert"),
+        }
+    }
+}
+
+/// Machine state before the instruction execution stopped at
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Stop {
+    /// Number of instructions executed before this one, across all frames
+    pub step: usize,
+    /// Byte offset of the instruction
+    pub pc: usize,
+    /// Instruction opcode
+    pub opcode: DecodedOpcode,
+    /// Operand stack, top last
+    pub stack: Vec<u64>,
+    /// Frame memory
+    pub memory: Vec<u8>,
+    /// Gas remaining in the frame
+    pub gas_remaining: u64,
+    /// Gas refund counter of the frame
+    pub gas_refunded: u64,
+    /// Call depth (0 for the outermost frame)
+    pub depth: usize,
+    /// Address whose code is executing
+    pub address: [u8; 20],
+    /// Storage of `address` as seen by this instruction
+    pub storage: BTreeMap<u64, u64>,
+    /// Index of the breakpoint that fired, if any
+    pub breakpoint: Option<usize>,
+}
+
+/// Outcome of a debugger command
+#[derive(Debug, Clone)]
+pub enum DebugEvent {
+    /// Execution stopped before an instruction
+    Stopped(Stop),
+    /// Execution ran to completion
+    Finished(ExecutionResult),
+}
+
+/// Where the next stop should be
+#[derive(Debug, Clone, Copy)]
+enum Target {
+    /// Next instruction in any frame
+    Step,
+    /// Next instruction at or above the given depth
+    Next(usize),
+    /// Next instruction matching a breakpoint
+    Breakpoint,
+}
+
+/// Inspector that captures the state at the first matching instruction after `after`
+struct Probe<'a> {
+    /// Instructions that have already been stepped through
+    after: Option<usize>,
+    target: Target,
+    breakpoints: &'a [Breakpoint],
+    initial: &'a Storage,
+    /// Instructions seen so far
+    steps: usize,
+    /// Storage writes per open frame, outermost first
+    writes: Vec<Vec<([u8; 20], u64, u64)>>,
+    stop: Option<Stop>,
+}
+
+impl Probe<'_> {
+    /// Storage of `address` after the writes of all frames that are still open
+    fn storage_view(&self, address: &[u8; 20]) -> BTreeMap<u64, u64> {
+        let mut view: BTreeMap<u64, u64> = self.initial.storage_slots(address).into_iter().collect();
+        for (written, key, value) in self.writes.iter().flatten() {
+            if written == address {
+                view.insert(*key, *value);
+            }
+        }
+        view
+    }
+
+    /// Close a frame, keeping its writes only if it succeeded
+    fn exit(&mut self, result: &ExecutionResult) {
+        if self.stop.is_some() || self.writes.len() < 2 {
+            return;
+        }
+        if let Some(writes) = self.writes.pop() {
+            if result.success {
+                if let Some(parent) = self.writes.last_mut() {
+                    parent.extend(writes);
+                }
+            }
+        }
+    }
+}
+
+impl Inspector for Probe<'_> {
+    fn step(&mut self, step: &Step<'_>) {
+        let index = self.steps;
+        self.steps += 1;
+        if self.stop.is_some() || self.after.is_some_and(|after| index <= after) {
+            return;
+        }
+
+        let breakpoint = self.breakpoints.iter().position(|bp| bp.hits(step));
+        let stop = match self.target {
+            Target::Step => true,
+            Target::Next(depth) => step.depth <= depth,
+            Target::Breakpoint => breakpoint.is_some(),
+        };
+        if stop {
+            self.stop = Some(Stop {
+                step: index,
+                pc: step.pc,
+                opcode: step.opcode,
+                stack: step.stack.to_vec(),
+                memory: step.memory.to_vec(),
+                gas_remaining: step.gas_remaining,
+                gas_refunded: step.gas_refunded,
+                depth: step.depth,
+                address: step.address,
+                storage: self.storage_view(&step.address),
+                breakpoint,
+            });
+        }
+    }
+
+    fn call(&mut self, _inputs: &CallInputs) {
+        self.writes.push(Vec::new());
+    }
+
+    fn call_end(&mut self, _inputs: &CallInputs, result: &ExecutionResult) {
+        self.exit(result);
+    }
+
+    fn create(&mut self, _inputs: &CallInputs) {
+        self.writes.push(Vec::new());
+    }
+
+    fn create_end(&mut self, _inputs: &CallInputs, result: &ExecutionResult) {
+        self.exit(result);
+    }
+
+    fn storage_write(&mut self, address: &[u8; 20], key: u64, value: u64) {
+        if self.stop.is_none() {
+            if let Some(frame) = self.writes.last_mut() {
+                frame.push((*address, key, value));
+            }
+        }
+    }
+}
+
+/// Interactive debugger over one piece of bytecode
+pub struct Debugger {
+    code: Vec<u8>,
+    env: ExecutionEnvironment,
+    gas_limit: u64,
+    /// State every run starts from
+    initial: Storage,
+    breakpoints: Vec<Breakpoint>,
+    /// Where execution is stopped, `None` before the first instruction
+    current: Option<Stop>,
+}
+
+impl Debugger {
+    /// Create a debugger for `code`, starting every run from `initial`
+    pub fn new(code: Vec<u8>, env: ExecutionEnvironment, gas_limit: u64, initial: Storage) -> Self {
+        Self {
+            code,
+            env,
+            gas_limit,
+            initial,
+            breakpoints: Vec::new(),
+            current: None,
+        }
+    }
+
+    /// Code being debugged
+    pub fn code(&self) -> &[u8] {
+        &self.code
+    }
+
+    /// Add a breakpoint, returning its index
+    pub fn add_breakpoint(&mut self, breakpoint: Breakpoint) -> usize {
+        self.breakpoints.push(breakpoint);
+        self.breakpoints.len() - 1
+    }
+
+    /// Remove the breakpoint at `index`
+    pub fn remove_breakpoint(&mut self, index: usize) -> Option<Breakpoint> {
+        (index < self.breakpoints.len()).then(|| self.breakpoints.remove(index))
+    }
+
+    /// Breakpoints, by index
+    pub fn breakpoints(&self) -> &[Breakpoint] {
+        &self.breakpoints
+    }
+
+    /// State at the current stop
+    pub fn current(&self) -> Option<&Stop> {
+        self.current.as_ref()
+    }
+
+    /// Go back to before the first instruction
+    pub fn restart(&mut self) {
+        self.current = None;
+    }
+
+    /// Execute one instruction, entering calls
+    pub async fn step(&mut self) -> Result<DebugEvent, RvmError> {
+        self.run_to(Target::Step).await
+    }
+
+    /// Execute one instruction, stepping over calls
+    pub async fn next(&mut self) -> Result<DebugEvent, RvmError> {
+        let depth = self.current.as_ref().map_or(0, |stop| stop.depth);
+        self.run_to(Target::Next(depth)).await
+    }
+
+    /// Run until a breakpoint fires or execution ends
+    pub async fn resume(&mut self) -> Result<DebugEvent, RvmError> {
+        self.run_to(Target::Breakpoint).await
+    }
+
+    /// Re-run from the initial state up to the next instruction matching `target`
+    async fn run_to(&mut self, target: Target) -> Result<DebugEvent, RvmError> {
+        let mut state = self.initial.clone();
+        let mut probe = Probe {
+            after: self.current.as_ref().map(|stop| stop.step),
+            target,
+            breakpoints: &self.breakpoints,
+            initial: &self.initial,
+            steps: 0,
+            writes: vec![Vec::new()],
+            stop: None,
+        };
+
+        let mut core = RvmCore::new(self.gas_limit);
+        let result = core
+            .execute_with_inspector(&self.code, self.env.clone(), &mut state, &mut probe)
+            .await?;
+
+        match probe.stop {
+            Some(stop) => {
+                self.current = Some(stop.clone());
+                Ok(DebugEvent::Stopped(stop))
+            }
+            None => {
+                self.current = None;
+                Ok(DebugEvent::Finished(result))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::word_to_address;
+    use crate::host::Host;
+
+    fn stopped(event: DebugEvent) -> Stop {
+        match event {
+            DebugEvent::Stopped(stop) => stop,
+            DebugEvent::Finished(result) => panic!("execution finished: {:?}", result),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_debugger_breakpoints_and_stepping() {
+        let contract = [9u8; 20];
+        let mut storage = Storage::new();
+        storage.set_storage(contract, 3, 30);
+        // Callee: SSTORE(1, 7), STOP
+        storage.set_code(word_to_address(0x42), vec![0x60, 0x07, 0x60, 0x01, 0x55, 0x00]);
+
+        // SSTORE(1, 5), CALL(gas=50000, to=0x42, value=0, in=0/0, out=0/0), REVERT(0, 0)
+        let code = vec![
+            0x60, 0x05, 0x60, 0x01, 0x55,
+            0x60, 0x00, 0x60, 0x00, 0x60, 0x00, 0x60, 0x00, 0x60, 0x00,
+            0x60, 0x42, 0x61, 0xc3, 0x50, 0xf1,
+            0x60, 0x00, 0x60, 0x00, 0xfd,
+        ];
+        let env = ExecutionEnvironment::new(contract, [1u8; 20], 0);
+        let mut debugger = Debugger::new(code, env, 100_000, storage);
+        debugger.add_breakpoint(Breakpoint::StorageWrite(None));
+        debugger.add_breakpoint(Breakpoint::Revert);
+
+        let stop = stopped(debugger.resume().await.unwrap());
+        assert_eq!((stop.pc, stop.opcode, stop.breakpoint), (4, Opcode::SSTORE.into(), Some(0)));
+        assert_eq!(stop.stack, vec![5, 1]);
+        assert_eq!(stop.storage.get(&3), Some(&30));
+        assert_eq!(stop.storage.get(&1), None);
+
+        // `next` steps over the call, `step` would enter it
+        let stop = stopped(debugger.next().await.unwrap());
+        assert_eq!((stop.pc, stop.storage.get(&1)), (5, Some(&5)));
+
+        // The callee's SSTORE is the next write
+        let stop = stopped(debugger.resume().await.unwrap());
+        assert_eq!((stop.depth, stop.address), (1, word_to_address(0x42)));
+
+        let stop = stopped(debugger.resume().await.unwrap());
+        assert_eq!((stop.pc, stop.opcode, stop.breakpoint), (25, Opcode::REVERT.into(), Some(1)));
+        assert_eq!(stop.depth, 0);
+
+        match debugger.resume().await.unwrap() {
+            DebugEvent::Finished(result) => assert!(!result.success),
+            DebugEvent::Stopped(stop) => panic!("unexpected stop at pc {}", stop.pc),
+        }
+
+        debugger.restart();
+        let stop = stopped(debugger.step().await.unwrap());
+        assert_eq!((stop.step, stop.pc, stop.gas_remaining), (0, 0, 100_000));
+    }
+}
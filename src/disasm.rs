@@ -0,0 +1,336 @@
+//! Disassembler
+//!
+//! Turns bytecode back into an annotated listing and a basic-block control-flow graph.
+//! Jump targets are resolved when the jump is directly preceded by a PUSH; a jump whose
+//! target is computed at runtime may reach any JUMPDEST, which the reachability analysis
+//! takes into account.
+
+use crate::opcodes::Opcode;
+use std::collections::BTreeSet;
+use std::fmt::{self, Write};
+
+/// One disassembled instruction
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DisassembledInstruction {
+    /// Byte offset
+    pub offset: usize,
+    /// Raw opcode byte
+    pub byte: u8,
+    /// Decoded opcode, `None` for undefined bytes
+    pub opcode: Option<Opcode>,
+    /// PUSH immediate as present in the code
+    pub immediate: Vec<u8>,
+    /// Whether the PUSH immediate runs past the end of the code
+    pub truncated: bool,
+}
+
+impl DisassembledInstruction {
+    /// Whether execution cannot fall through to the next instruction
+    fn halts(&self) -> bool {
+        match self.opcode {
+            Some(opcode) => matches!(
+                opcode,
+                Opcode::STOP | Opcode::JUMP | Opcode::RETURN | Opcode::REVERT | Opcode::INVALID | Opcode::SELFDESTRUCT
+            ),
+            None => true,
+        }
+    }
+
+    /// Whether the instruction ends a basic block
+    fn ends_block(&self) -> bool {
+        self.halts() || self.opcode == Some(Opcode::JUMPI)
+    }
+
+    /// PUSH immediate as a stack word (low-order 8 bytes, zero-padded like the interpreter)
+    fn push_value(&self) -> Option<u64> {
+        let size = self.opcode.filter(|opcode| opcode.is_push())?.push_bytes();
+        let mut bytes = self.immediate.clone();
+        bytes.resize(size, 0);
+        Some(bytes.iter().fold(0u64, |value, byte| (value << 8) | *byte as u64))
+    }
+}
+
+impl fmt::Display for DisassembledInstruction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.opcode {
+            Some(opcode) if opcode.is_push() => write!(f, "{:?} 0x{}", opcode, hex::encode(&self.immediate)),
+            Some(opcode) => write!(f, "{:?}", opcode),
+            None => write!(f, "0x{:02x}", self.byte),
+        }
+    }
+}
+
+/// Control-flow edge out of a basic block
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Edge {
+    /// Execution continues into the block starting at this offset
+    FallThrough(usize),
+    /// Jump to the block starting at this offset
+    Jump(usize),
+    /// Jump to a constant offset that is not a JUMPDEST
+    InvalidJump(u64),
+    /// Jump to a target computed at runtime
+    Dynamic,
+}
+
+/// Straight-line run of instructions
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BasicBlock {
+    /// Offset of the first instruction
+    pub start: usize,
+    /// Index range into `Disassembly::instructions`
+    pub instructions: std::ops::Range<usize>,
+    /// Outgoing edges
+    pub edges: Vec<Edge>,
+    /// Whether the block can be reached from offset 0
+    pub reachable: bool,
+}
+
+/// Disassembled bytecode
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Disassembly {
+    /// Instructions in code order
+    pub instructions: Vec<DisassembledInstruction>,
+    /// Basic blocks in code order
+    pub blocks: Vec<BasicBlock>,
+}
+
+impl Disassembly {
+    /// Disassemble bytecode
+    pub fn new(code: &[u8]) -> Self {
+        let mut instructions = Vec::new();
+        let mut pc = 0;
+        while pc < code.len() {
+            let byte = code[pc];
+            let opcode = Opcode::from_byte(byte).ok();
+            let size = opcode.map_or(0, |opcode| opcode.push_bytes());
+            let end = (pc + 1 + size).min(code.len());
+            instructions.push(DisassembledInstruction {
+                offset: pc,
+                byte,
+                opcode,
+                immediate: code[pc + 1..end].to_vec(),
+                truncated: pc + 1 + size > code.len(),
+            });
+            pc += 1 + size;
+        }
+
+        let blocks = Self::build_blocks(&instructions);
+        Self { instructions, blocks }
+    }
+
+    /// Instructions of a block
+    pub fn block_instructions(&self, block: &BasicBlock) -> &[DisassembledInstruction] {
+        &self.instructions[block.instructions.clone()]
+    }
+
+    /// Offsets of undefined opcode bytes
+    pub fn invalid_bytes(&self) -> Vec<usize> {
+        self.instructions.iter().filter(|i| i.opcode.is_none()).map(|i| i.offset).collect()
+    }
+
+    /// Byte ranges no execution path reaches
+    pub fn unreachable_ranges(&self) -> Vec<std::ops::Range<usize>> {
+        let mut ranges: Vec<std::ops::Range<usize>> = Vec::new();
+        for (index, block) in self.blocks.iter().enumerate() {
+            if block.reachable {
+                continue;
+            }
+            let end = self.blocks.get(index + 1).map_or_else(|| self.code_size(), |next| next.start);
+            match ranges.last_mut() {
+                Some(range) if range.end == block.start => range.end = end,
+                _ => ranges.push(block.start..end),
+            }
+        }
+        ranges
+    }
+
+    /// Annotated listing, one instruction per line
+    pub fn listing(&self) -> String {
+        let mut text = String::new();
+        for block in &self.blocks {
+            if !block.reachable {
+                let _ = writeln!(text, "; unreachable");
+            }
+            for instruction in self.block_instructions(block) {
+                let _ = write!(text, "{:#06x}  {}", instruction.offset, instruction);
+                if instruction.opcode.is_none() {
+                    text.push_str("  ; invalid opcode");
+                }
+                if instruction.truncated {
+                    text.push_str("  ; truncated push data");
+                }
+                text.push('\n');
+            }
+            for edge in &block.edges {
+                if let Edge::InvalidJump(target) = edge {
+                    let _ = writeln!(text, "; jump to {:#x} is not a JUMPDEST", target);
+                }
+            }
+        }
+        text
+    }
+
+    /// Control-flow graph in Graphviz DOT format
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph cfg {\n    node [shape=box, fontname=monospace];\n");
+        let mut dynamic = false;
+        for block in &self.blocks {
+            let mut label = String::new();
+            for instruction in self.block_instructions(block) {
+                let _ = write!(label, "{:#06x}  {}\\l", instruction.offset, instruction);
+            }
+            let style = if block.reachable { "" } else { ", style=dashed, color=gray" };
+            let _ = writeln!(dot, "    b{} [label=\"{}\"{}];", block.start, label, style);
+
+            for edge in &block.edges {
+                let _ = match edge {
+                    Edge::FallThrough(target) => writeln!(dot, "    b{} -> b{} [style=dashed];", block.start, target),
+                    Edge::Jump(target) => writeln!(dot, "    b{} -> b{};", block.start, target),
+                    Edge::InvalidJump(target) => {
+                        writeln!(dot, "    b{} -> invalid [label=\"{:#x}\", color=red];", block.start, target)
+                    }
+                    Edge::Dynamic => {
+                        dynamic = true;
+                        writeln!(dot, "    b{} -> dynamic [color=blue];", block.start)
+                    }
+                };
+            }
+        }
+        if dynamic {
+            dot.push_str("    dynamic [shape=diamond, label=\"dynamic jump\"];\n");
+        }
+        if self.blocks.iter().flat_map(|b| &b.edges).any(|e| matches!(e, Edge::InvalidJump(_))) {
+            dot.push_str("    invalid [shape=octagon, color=red, label=\"invalid jump\"];\n");
+        }
+        dot.push_str("}\n");
+        dot
+    }
+
+    /// Length of the disassembled code
+    fn code_size(&self) -> usize {
+        self.instructions.last().map_or(0, |i| i.offset + 1 + i.immediate.len())
+    }
+
+    /// Split instructions into basic blocks, resolve edges and mark reachable blocks
+    fn build_blocks(instructions: &[DisassembledInstruction]) -> Vec<BasicBlock> {
+        let mut blocks: Vec<BasicBlock> = Vec::new();
+        for (index, instruction) in instructions.iter().enumerate() {
+            let leader = index == 0
+                || instruction.opcode == Some(Opcode::JUMPDEST)
+                || instructions[index - 1].ends_block();
+            if leader {
+                blocks.push(BasicBlock {
+                    start: instruction.offset,
+                    instructions: index..index + 1,
+                    edges: Vec::new(),
+                    reachable: false,
+                });
+            } else if let Some(block) = blocks.last_mut() {
+                block.instructions.end = index + 1;
+            }
+        }
+
+        let jumpdests: BTreeSet<usize> = instructions
+            .iter()
+            .filter(|i| i.opcode == Some(Opcode::JUMPDEST))
+            .map(|i| i.offset)
+            .collect();
+
+        for index in 0..blocks.len() {
+            let range = blocks[index].instructions.clone();
+            let last = &instructions[range.end - 1];
+            let mut edges = Vec::new();
+            if matches!(last.opcode, Some(Opcode::JUMP) | Some(Opcode::JUMPI)) {
+                let target = range.len().checked_sub(2).and_then(|i| instructions[range.start + i].push_value());
+                edges.push(match target {
+                    Some(target) if usize::try_from(target).is_ok_and(|t| jumpdests.contains(&t)) => {
+                        Edge::Jump(target as usize)
+                    }
+                    Some(target) => Edge::InvalidJump(target),
+                    None => Edge::Dynamic,
+                });
+            }
+            if !last.halts() {
+                if let Some(next) = blocks.get(index + 1) {
+                    edges.push(Edge::FallThrough(next.start));
+                }
+            }
+            blocks[index].edges = edges;
+        }
+
+        // Reachability from offset 0; a reachable dynamic jump may land on any JUMPDEST
+        let starts: Vec<usize> = blocks.iter().map(|b| b.start).collect();
+        let index_of = |offset: usize| starts.binary_search(&offset).ok();
+        let mut pending = if blocks.is_empty() { Vec::new() } else { vec![0] };
+        let mut dynamic_seen = false;
+        while let Some(index) = pending.pop() {
+            if blocks[index].reachable {
+                continue;
+            }
+            blocks[index].reachable = true;
+            for edge in blocks[index].edges.clone() {
+                match edge {
+                    Edge::FallThrough(target) | Edge::Jump(target) => pending.extend(index_of(target)),
+                    Edge::Dynamic if !dynamic_seen => {
+                        dynamic_seen = true;
+                        pending.extend(jumpdests.iter().filter_map(|offset| index_of(*offset)));
+                    }
+                    _ => {}
+                }
+            }
+        }
+        blocks
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_disassemble_blocks_and_reachability() {
+        // PUSH1 5, JUMP, PUSH1 1, 0x0c (undefined), JUMPDEST, PUSH2 0x00 (truncated)
+        let code = vec![0x60, 0x05, 0x56, 0x60, 0x01, 0x0c, 0x5b, 0x61, 0x00];
+        let disassembly = Disassembly::new(&code);
+
+        assert_eq!(disassembly.invalid_bytes(), vec![5]);
+        assert!(disassembly.instructions.last().unwrap().truncated);
+
+        let starts: Vec<(usize, bool)> = disassembly.blocks.iter().map(|b| (b.start, b.reachable)).collect();
+        assert_eq!(starts, vec![(0, true), (3, false), (6, false)]);
+        assert_eq!(disassembly.blocks[0].edges, vec![Edge::InvalidJump(5)]);
+        assert_eq!(disassembly.unreachable_ranges(), vec![3..9]);
+
+        let listing = disassembly.listing();
+        assert!(listing.starts_with("0x0000  PUSH1 0x05\n0x0002  JUMP\n; jump to 0x5 is not a JUMPDEST\n; unreachable\n"));
+        assert!(listing.contains("0x0005  0x0c  ; invalid opcode\n"));
+        assert!(listing.contains("0x0007  PUSH2 0x00  ; truncated push data\n"));
+    }
+
+    #[test]
+    fn test_cfg_edges_and_dot() {
+        // PUSH1 0, PUSH1 8, JUMPI, STOP, PUSH1 0 (unreachable), JUMPDEST, STOP
+        let code = vec![0x60, 0x00, 0x60, 0x08, 0x57, 0x00, 0x60, 0x00, 0x5b, 0x00];
+        let disassembly = Disassembly::new(&code);
+
+        let blocks = &disassembly.blocks;
+        assert_eq!(blocks[0].edges, vec![Edge::Jump(8), Edge::FallThrough(5)]);
+        assert_eq!(blocks.iter().map(|b| b.reachable).collect::<Vec<_>>(), vec![true, true, false, true]);
+
+        let dot = disassembly.to_dot();
+        assert!(dot.starts_with("digraph cfg {"));
+        assert!(dot.contains("    b0 -> b8;\n"));
+        assert!(dot.contains("    b0 -> b5 [style=dashed];\n"));
+        assert!(dot.contains("b6 [label=\"0x0006  PUSH1 0x00\\l\", style=dashed, color=gray];"));
+    }
+
+    #[test]
+    fn test_dynamic_jump_reaches_jumpdests() {
+        // CALLDATASIZE, JUMP, JUMPDEST, STOP
+        let disassembly = Disassembly::new(&[0x36, 0x56, 0x5b, 0x00]);
+        assert_eq!(disassembly.blocks[0].edges, vec![Edge::Dynamic]);
+        assert!(disassembly.blocks.iter().all(|b| b.reachable));
+        assert!(disassembly.to_dot().contains("dynamic [shape=diamond"));
+    }
+}
@@ -0,0 +1,472 @@
+//! EOF Containers
+//!
+//! EVM Object Format v1 (EIP-3540): a header describing the type, code and data sections,
+//! followed by the sections themselves. Containers are validated once at deploy time
+//! (EIP-3670, EIP-4200, EIP-4750, EIP-5450), so the interpreter can trust their code:
+//! every instruction is defined, relative jumps land on instruction boundaries and every
+//! instruction runs at a single, statically known stack height.
+
+use crate::{
+    error::RvmError,
+    opcodes::{DataOpcode, DecodedOpcode, Opcode},
+    MAX_STACK_SIZE,
+};
+
+/// Leading bytes of every EOF container
+pub const EOF_MAGIC: [u8; 2] = [0xef, 0x00];
+
+/// Supported container version
+pub const EOF_VERSION: u8 = 0x01;
+
+/// `outputs` value of a code section that never returns
+pub const NON_RETURNING: u8 = 0x80;
+
+/// Maximum CALLF nesting
+pub const MAX_RETURN_STACK: usize = 1024;
+
+/// Maximum number of code sections
+const MAX_CODE_SECTIONS: usize = 1024;
+
+const KIND_TYPES: u8 = 0x01;
+const KIND_CODE: u8 = 0x02;
+const KIND_DATA: u8 = 0x04;
+const TERMINATOR: u8 = 0x00;
+
+/// Signature of a code section
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TypeSection {
+    /// Stack items the section takes
+    pub inputs: u8,
+    /// Stack items the section returns, or `NON_RETURNING`
+    pub outputs: u8,
+    /// Highest stack height the section reaches
+    pub max_stack_height: u16,
+}
+
+impl TypeSection {
+    /// Whether the section can return with RETF
+    pub fn returns(&self) -> bool {
+        self.outputs != NON_RETURNING
+    }
+}
+
+/// Parsed EOF v1 container
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EofContainer {
+    /// One signature per code section
+    pub types: Vec<TypeSection>,
+    /// Code sections; execution starts in the first one
+    pub code_sections: Vec<Vec<u8>>,
+    /// Data section
+    pub data: Vec<u8>,
+}
+
+/// Cursor over container bytes
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize, what: &str) -> Result<&'a [u8], RvmError> {
+        let bytes = self.bytes
+            .get(self.pos..self.pos + len)
+            .ok_or_else(|| invalid(format!("truncated {}", what)))?;
+        self.pos += len;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self, what: &str) -> Result<u8, RvmError> {
+        Ok(self.take(1, what)?[0])
+    }
+
+    fn u16(&mut self, what: &str) -> Result<u16, RvmError> {
+        let bytes = self.take(2, what)?;
+        Ok(u16::from_be_bytes([bytes[0], bytes[1]]))
+    }
+
+    fn expect(&mut self, kind: u8, what: &str) -> Result<(), RvmError> {
+        match self.u8(what)? {
+            byte if byte == kind => Ok(()),
+            byte => Err(invalid(format!("expected {} (0x{:02x}), found 0x{:02x}", what, kind, byte))),
+        }
+    }
+}
+
+impl EofContainer {
+    /// Whether code is meant to be an EOF container
+    pub fn is_eof(code: &[u8]) -> bool {
+        code.starts_with(&EOF_MAGIC)
+    }
+
+    /// Parse a container, checking its header and section sizes
+    pub fn parse(bytes: &[u8]) -> Result<Self, RvmError> {
+        let mut reader = Reader { bytes, pos: 0 };
+        if reader.take(2, "magic")? != EOF_MAGIC {
+            return Err(invalid("missing EOF magic".to_string()));
+        }
+        let version = reader.u8("version")?;
+        if version != EOF_VERSION {
+            return Err(invalid(format!("unsupported version {}", version)));
+        }
+
+        reader.expect(KIND_TYPES, "type section header")?;
+        let types_size = reader.u16("type section size")? as usize;
+        reader.expect(KIND_CODE, "code section header")?;
+        let num_code_sections = reader.u16("code section count")? as usize;
+        if num_code_sections == 0 || num_code_sections > MAX_CODE_SECTIONS {
+            return Err(invalid(format!("invalid code section count {}", num_code_sections)));
+        }
+        let code_sizes = (0..num_code_sections)
+            .map(|_| reader.u16("code section size").map(usize::from))
+            .collect::<Result<Vec<_>, _>>()?;
+        if code_sizes.contains(&0) {
+            return Err(invalid("empty code section".to_string()));
+        }
+        if types_size != num_code_sections * 4 {
+            return Err(invalid(format!("type section size {} does not match {} code sections", types_size, num_code_sections)));
+        }
+        reader.expect(KIND_DATA, "data section header")?;
+        let data_size = reader.u16("data section size")? as usize;
+        reader.expect(TERMINATOR, "header terminator")?;
+
+        let mut types = Vec::with_capacity(num_code_sections);
+        for _ in 0..num_code_sections {
+            types.push(TypeSection {
+                inputs: reader.u8("type section")?,
+                outputs: reader.u8("type section")?,
+                max_stack_height: reader.u16("type section")?,
+            });
+        }
+        let code_sections = code_sizes
+            .iter()
+            .map(|size| reader.take(*size, "code section").map(<[u8]>::to_vec))
+            .collect::<Result<Vec<_>, _>>()?;
+        let data = reader.take(data_size, "data section")?.to_vec();
+        if reader.pos != bytes.len() {
+            return Err(invalid(format!("{} trailing bytes", bytes.len() - reader.pos)));
+        }
+
+        Ok(Self { types, code_sections, data })
+    }
+
+    /// Serialize the container
+    pub fn encode(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(self.code_section_offset(self.code_sections.len()) + self.data.len());
+        bytes.extend_from_slice(&EOF_MAGIC);
+        bytes.push(EOF_VERSION);
+        bytes.push(KIND_TYPES);
+        bytes.extend_from_slice(&(self.types.len() as u16 * 4).to_be_bytes());
+        bytes.push(KIND_CODE);
+        bytes.extend_from_slice(&(self.code_sections.len() as u16).to_be_bytes());
+        for code in &self.code_sections {
+            bytes.extend_from_slice(&(code.len() as u16).to_be_bytes());
+        }
+        bytes.push(KIND_DATA);
+        bytes.extend_from_slice(&(self.data.len() as u16).to_be_bytes());
+        bytes.push(TERMINATOR);
+        for section in &self.types {
+            bytes.push(section.inputs);
+            bytes.push(section.outputs);
+            bytes.extend_from_slice(&section.max_stack_height.to_be_bytes());
+        }
+        for code in &self.code_sections {
+            bytes.extend_from_slice(code);
+        }
+        bytes.extend_from_slice(&self.data);
+        bytes
+    }
+
+    /// Byte offset of a code section in the encoded container
+    pub fn code_section_offset(&self, index: usize) -> usize {
+        let header = 13 + 2 * self.code_sections.len();
+        let types = 4 * self.types.len();
+        header + types + self.code_sections[..index].iter().map(Vec::len).sum::<usize>()
+    }
+
+    /// Deploy-time validation of types and code
+    pub fn validate(&self) -> Result<(), RvmError> {
+        if self.types.len() != self.code_sections.len() {
+            return Err(invalid("type and code section counts differ".to_string()));
+        }
+        let Some(first) = self.types.first() else {
+            return Err(invalid("no code sections".to_string()));
+        };
+        if first.inputs != 0 || first.returns() {
+            return Err(invalid("first code section must take no inputs and not return".to_string()));
+        }
+        for (index, section) in self.types.iter().enumerate() {
+            if section.inputs > 127
+                || (section.returns() && section.outputs > 127)
+                || section.max_stack_height as usize >= MAX_STACK_SIZE
+            {
+                return Err(invalid(format!("code section {}: invalid type {:?}", index, section)));
+            }
+        }
+        for index in 0..self.code_sections.len() {
+            self.validate_code(index)
+                .map_err(|message| invalid(format!("code section {}: {}", index, message)))?;
+        }
+        Ok(())
+    }
+
+    /// Validate instructions, jumps and stack heights of one code section
+    fn validate_code(&self, index: usize) -> Result<(), String> {
+        let code = &self.code_sections[index];
+        let section = self.types[index];
+
+        // Decode instructions as (offset, opcode, immediate)
+        let mut instructions: Vec<(usize, DecodedOpcode, u16)> = Vec::new();
+        let mut pc = 0;
+        while pc < code.len() {
+            let opcode = Opcode::from_eof_byte(code[pc])
+                .map_err(|_| format!("invalid opcode 0x{:02x} at offset {}", code[pc], pc))?;
+            if is_legacy_only(opcode) {
+                return Err(format!("{:?} at offset {} is not allowed in EOF code", opcode, pc));
+            }
+            let size = opcode.immediate_size(code, pc);
+            if pc + 1 + size > code.len() {
+                return Err(format!("truncated immediate at offset {}", pc));
+            }
+            let immediate = match size {
+                2 => u16::from_be_bytes([code[pc + 1], code[pc + 2]]),
+                _ => 0,
+            };
+            instructions.push((pc, opcode, immediate));
+            pc += 1 + size;
+        }
+        let index_of = |offset: usize| instructions.binary_search_by_key(&offset, |(pc, _, _)| *pc).ok();
+
+        match instructions.last() {
+            Some((_, opcode, _)) if is_terminating(*opcode) || *opcode == Opcode::RJUMP => {}
+            _ => return Err("code must end with a terminating instruction".to_string()),
+        }
+
+        // Resolve relative jumps and check immediates
+        let mut jump_targets = vec![Vec::new(); instructions.len()];
+        for (position, (pc, opcode, immediate)) in instructions.iter().enumerate() {
+            match opcode {
+                DecodedOpcode::Opcode(Opcode::RJUMP | Opcode::RJUMPI | Opcode::RJUMPV) => {
+                    let end = pc + 1 + opcode.immediate_size(code, *pc);
+                    let offsets: Vec<i16> = match opcode {
+                        DecodedOpcode::Opcode(Opcode::RJUMPV) => code[pc + 2..end]
+                            .chunks(2)
+                            .map(|offset| i16::from_be_bytes([offset[0], offset[1]]))
+                            .collect(),
+                        _ => vec![*immediate as i16],
+                    };
+                    for offset in offsets {
+                        let target = (end as isize + offset as isize) as usize;
+                        let target = index_of(target)
+                            .ok_or_else(|| format!("relative jump at offset {} to a non-instruction", pc))?;
+                        jump_targets[position].push(target);
+                    }
+                }
+                DecodedOpcode::Opcode(Opcode::CALLF) => {
+                    let callee = self.types.get(*immediate as usize)
+                        .ok_or_else(|| format!("CALLF at offset {} to missing section {}", pc, immediate))?;
+                    if !callee.returns() {
+                        return Err(format!("CALLF at offset {} to non-returning section {}", pc, immediate));
+                    }
+                }
+                DecodedOpcode::Opcode(Opcode::RETF) if !section.returns() => {
+                    return Err(format!("RETF at offset {} in a non-returning section", pc));
+                }
+                DecodedOpcode::Data(DataOpcode::DATALOADN) if *immediate as usize + 32 > self.data.len() => {
+                    return Err(format!("DATALOADN at offset {} reads past the data section", pc));
+                }
+                _ => {}
+            }
+        }
+
+        // Every instruction must be reached at exactly one stack height
+        let mut heights: Vec<Option<usize>> = vec![None; instructions.len()];
+        let mut max_height = section.inputs as usize;
+        let mut pending = vec![(0, section.inputs as usize)];
+        while let Some((position, height)) = pending.pop() {
+            let (pc, opcode, immediate) = instructions[position];
+            match heights[position] {
+                Some(existing) if existing != height => {
+                    return Err(format!("stack height {} at offset {} differs from {}", height, pc, existing));
+                }
+                Some(_) => continue,
+                None => heights[position] = Some(height),
+            }
+
+            let (inputs, outputs) = match opcode {
+                DecodedOpcode::Opcode(Opcode::CALLF) => {
+                    let callee = self.types[immediate as usize];
+                    // EIP-5450: the callee's own stack use must fit on top of this height
+                    let needed = (callee.max_stack_height as usize).saturating_sub(callee.inputs as usize);
+                    if height + needed > MAX_STACK_SIZE {
+                        return Err(format!("CALLF at offset {} may overflow the stack", pc));
+                    }
+                    (callee.inputs as usize, callee.outputs as usize)
+                }
+                DecodedOpcode::Opcode(Opcode::RETF) => {
+                    if height != section.outputs as usize {
+                        return Err(format!("RETF at offset {} with {} items, {} declared", pc, height, section.outputs));
+                    }
+                    (height, 0)
+                }
+                _ => opcode.stack_io(),
+            };
+            if height < inputs {
+                return Err(format!("stack underflow at offset {}", pc));
+            }
+            let next_height = height - inputs + outputs;
+            if next_height > MAX_STACK_SIZE {
+                return Err(format!("stack overflow at offset {}", pc));
+            }
+            max_height = max_height.max(next_height);
+
+            pending.extend(jump_targets[position].iter().map(|target| (*target, next_height)));
+            if !is_terminating(opcode) && opcode != Opcode::RJUMP {
+                pending.push((position + 1, next_height));
+            }
+        }
+
+        if let Some(position) = heights.iter().position(Option::is_none) {
+            return Err(format!("unreachable instruction at offset {}", instructions[position].0));
+        }
+        if max_height != section.max_stack_height as usize {
+            return Err(format!(
+                "max_stack_height {} does not match computed {}",
+                section.max_stack_height, max_height
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Whether execution never continues past the opcode
+fn is_terminating(opcode: DecodedOpcode) -> bool {
+    matches!(
+        opcode,
+        DecodedOpcode::Opcode(Opcode::STOP | Opcode::RETURN | Opcode::REVERT | Opcode::INVALID | Opcode::RETF)
+    )
+}
+
+/// Legacy opcodes that EOF code may not use
+fn is_legacy_only(opcode: DecodedOpcode) -> bool {
+    matches!(
+        opcode,
+        DecodedOpcode::Opcode(
+            Opcode::JUMP | Opcode::JUMPI | Opcode::PC | Opcode::GAS | Opcode::CODESIZE | Opcode::CODECOPY |
+            Opcode::EXTCODESIZE | Opcode::EXTCODECOPY | Opcode::CALLCODE | Opcode::SELFDESTRUCT |
+            Opcode::CREATE | Opcode::CREATE2
+        )
+    )
+}
+
+fn invalid(message: String) -> RvmError {
+    RvmError::InvalidEofContainer(message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn container(sections: Vec<(TypeSection, Vec<u8>)>, data: Vec<u8>) -> EofContainer {
+        let (types, code_sections) = sections.into_iter().unzip();
+        EofContainer { types, code_sections, data }
+    }
+
+    fn main_type(max_stack_height: u16) -> TypeSection {
+        TypeSection { inputs: 0, outputs: NON_RETURNING, max_stack_height }
+    }
+
+    fn error(container: &EofContainer) -> String {
+        container.validate().unwrap_err().to_string()
+    }
+
+    #[test]
+    fn test_parse_encode_round_trip() {
+        // PUSH1 1, CALLF 1, POP, POP, STOP | DUP1, RETF
+        let eof = container(
+            vec![
+                (main_type(2), vec![0x60, 0x01, 0xe3, 0x00, 0x01, 0x50, 0x50, 0x00]),
+                (TypeSection { inputs: 1, outputs: 2, max_stack_height: 2 }, vec![0x80, 0xe4]),
+            ],
+            vec![0xaa, 0xbb],
+        );
+        let bytes = eof.encode();
+        assert_eq!(&bytes[..3], &[0xef, 0x00, 0x01]);
+        assert_eq!(eof.code_section_offset(1), bytes.len() - 2 - 2);
+        assert_eq!(EofContainer::parse(&bytes).unwrap(), eof);
+        eof.validate().unwrap();
+
+        assert!(EofContainer::parse(&bytes[..bytes.len() - 1]).is_err());
+        assert!(EofContainer::parse(&[bytes.clone(), vec![0]].concat()).is_err());
+    }
+
+    #[test]
+    fn test_validation_rejects_bad_code() {
+        // RJUMP into its own immediate
+        let eof = container(vec![(main_type(0), vec![0xe0, 0xff, 0xfe])], vec![]);
+        assert!(error(&eof).contains("non-instruction"));
+
+        // Legacy JUMP
+        let eof = container(vec![(main_type(1), vec![0x60, 0x00, 0x56])], vec![]);
+        assert!(error(&eof).contains("JUMP at offset 2 is not allowed"));
+
+        // Falls off the end
+        let eof = container(vec![(main_type(1), vec![0x60, 0x00])], vec![]);
+        assert!(error(&eof).contains("terminating"));
+
+        // DATALOADN past the data section
+        let eof = container(vec![(main_type(1), vec![0xd1, 0x00, 0x01, 0x00])], vec![0; 32]);
+        assert!(error(&eof).contains("past the data section"));
+
+        // Declared max_stack_height is wrong
+        let eof = container(vec![(main_type(2), vec![0x60, 0x00, 0x00])], vec![]);
+        assert!(error(&eof).contains("does not match computed 1"));
+
+        // Loop that grows the stack reaches its head at two heights
+        let eof = container(vec![(main_type(1), vec![0x60, 0x00, 0xe0, 0xff, 0xfb])], vec![]);
+        assert!(error(&eof).contains("differs"));
+
+        // Code after an unconditional jump is unreachable
+        let eof = container(vec![(main_type(0), vec![0xe0, 0x00, 0x01, 0x00, 0x00])], vec![]);
+        assert!(error(&eof).contains("unreachable instruction at offset 3"));
+
+        // RJUMPV table entry into its own immediate
+        let eof = container(vec![(main_type(1), vec![0x60, 0x00, 0xe2, 0x00, 0xff, 0xff, 0x00])], vec![]);
+        assert!(error(&eof).contains("non-instruction"));
+
+        // RJUMPV table running past the end of the code
+        let eof = container(vec![(main_type(1), vec![0x60, 0x00, 0xe2, 0x01, 0x00, 0x00, 0x00])], vec![]);
+        assert!(error(&eof).contains("truncated immediate"));
+    }
+
+    #[test]
+    fn test_callf_checks_callee_stack_use() {
+        // Callee pushes 1023 items and pops them again
+        let callee_code = [[0x60, 0x00].repeat(1023), vec![0x50; 1023], vec![0xe4]].concat();
+        let callee = (TypeSection { inputs: 0, outputs: 0, max_stack_height: 1023 }, callee_code);
+
+        // PUSH1 0 * n, CALLF 1, STOP
+        let caller = |n: usize| [[0x60, 0x00].repeat(n), vec![0xe3, 0x00, 0x01, 0x00]].concat();
+
+        // 1 + 1023 items fit the stack
+        let eof = container(vec![(main_type(1), caller(1)), callee.clone()], vec![]);
+        eof.validate().unwrap();
+
+        // 2 + 1023 do not
+        let eof = container(vec![(main_type(2), caller(2)), callee], vec![]);
+        assert!(error(&eof).contains("CALLF at offset 4 may overflow the stack"));
+    }
+
+    #[test]
+    fn test_rjumpv_reaches_every_target() {
+        // PUSH1 0, RJUMPV [+1, +2], STOP, STOP, STOP
+        let code = vec![0x60, 0x00, 0xe2, 0x01, 0x00, 0x01, 0x00, 0x02, 0x00, 0x00, 0x00];
+        let eof = container(vec![(main_type(1), code)], vec![]);
+        eof.validate().unwrap();
+
+        // Without the second entry the last STOP is unreachable
+        let code = vec![0x60, 0x00, 0xe2, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00];
+        let eof = container(vec![(main_type(1), code)], vec![]);
+        assert!(error(&eof).contains("unreachable instruction at offset 8"));
+    }
+}
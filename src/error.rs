@@ -30,6 +30,12 @@ pub enum RvmError {
     #[error("Execution failed: {0}")]
     ExecutionFailed(String),
     
+    #[error("Execution reverted")]
+    Reverted,
+    
+    #[error("State modification in static call")]
+    StaticCallViolation,
+    
     // Storage Errors
     #[error("Insufficient balance: available {available}, required {required}")]
     InsufficientBalance { available: u64, required: u64 },
@@ -59,12 +65,27 @@ pub enum RvmError {
     
     #[error("Invalid EVM transaction: {0}")]
     InvalidEvmTransaction(String),
+
+    #[error("Intrinsic gas too low: limit {gas_limit}, required {intrinsic}")]
+    IntrinsicGasTooLow { gas_limit: u64, intrinsic: u64 },
+
+    #[error("Invalid nonce: expected {expected}, got {got}")]
+    InvalidNonce { expected: u64, got: u64 },
+
+    #[error("Max fee per gas {max_fee} below base fee {base_fee}")]
+    FeeCapTooLow { max_fee: u64, base_fee: u64 },
+
+    #[error("Gas estimation failed: {0}")]
+    GasEstimationFailed(String),
     
     #[error("EVM state error: {0}")]
     EvmStateError(String),
     
     #[error("Block not found: {0}")]
     BlockNotFound(u64),
+
+    #[error("Invalid EOF container: {0}")]
+    InvalidEofContainer(String),
     
     // WASM-lite Errors
     #[error("Invalid WASM-lite instruction: 0x{0:02x}")]
@@ -154,6 +175,9 @@ pub enum RvmError {
     
     #[error("Debug assertion failed: {0}")]
     DebugAssertionFailed(String),
+
+    #[error("Assembly error on line {line}: {message}")]
+    AssemblyError { line: usize, message: String },
     
     // External Integration Errors
     #[error("External call failed: {0}")]
@@ -1,10 +1,97 @@
 //! Gas Metering System
 //!
 //! Tracks execution costs and prevents infinite loops or excessive resource usage.
+//! The VMs meter through the `GasMetering` trait, so a meter that prices gas differently,
+//! such as `GhostChainGasMeter`, can be used in place of the plain `GasMeter`.
 
-use crate::error::RvmError;
+use crate::{error::RvmError, ghostchain_gas::FeeReceipt, host::Host, opcodes::Opcode};
 use serde::{Deserialize, Serialize};
 
+/// Gas accounting used by the VMs
+pub trait GasMetering: Send + Sync {
+    /// Consume gas for an operation
+    fn consume(&mut self, amount: u64) -> Result<(), RvmError>;
+
+    /// Consume gas for a GhostChain opcode, so meters can price the operation
+    fn consume_for(&mut self, opcode: Opcode, amount: u64) -> Result<(), RvmError> {
+        let _ = opcode;
+        self.consume(amount)
+    }
+
+    /// Price gas that was already consumed as gas of a GhostChain opcode, such as its base
+    /// cost charged with the basic block
+    fn price_for(&mut self, opcode: Opcode, amount: u64) -> Result<(), RvmError> {
+        let _ = (opcode, amount);
+        Ok(())
+    }
+
+    /// Give back gas charged ahead of instructions that never ran
+    fn release(&mut self, amount: u64);
+
+    /// Refund gas (for storage operations)
+    fn refund(&mut self, amount: u64);
+
+    /// Take back gas refunded earlier, when a storage write undoes the change it was for
+    fn remove_refund(&mut self, amount: u64);
+
+    /// Get remaining gas
+    fn remaining(&self) -> u64;
+
+    /// Get gas used
+    fn used(&self) -> u64;
+
+    /// Get gas refunded
+    fn refunded(&self) -> u64;
+
+    /// Get gas limit
+    fn limit(&self) -> u64;
+
+    /// Reset the meter for a new execution
+    fn reset(&mut self, limit: u64);
+
+    /// Reset the meter for a new execution by `executor` against `host`
+    fn begin(&mut self, limit: u64, executor: [u8; 20], host: &dyn Host) {
+        let _ = (executor, host);
+        self.reset(limit);
+    }
+
+    /// Meter for a child frame with the given limit, priced like this one. The refund
+    /// counter is shared by the whole transaction, so the child starts from this one's.
+    fn child(&self, limit: u64) -> Self
+    where
+        Self: Sized;
+
+    /// Fold the accounting of a child frame that succeeded into this meter; a failed
+    /// child's accounting is discarded. The child's gas itself is charged separately,
+    /// through `consume`.
+    fn merge_child(&mut self, child: &Self)
+    where
+        Self: Sized,
+    {
+        let _ = child;
+    }
+
+    /// Settle the token fees of a finished execution, paid by `payer` to `validator`.
+    /// Meters without token pricing settle nothing.
+    fn settle(&self, payer: [u8; 20], validator: [u8; 20], host: &mut dyn Host) -> Result<Option<FeeReceipt>, RvmError> {
+        let _ = (payer, validator, host);
+        Ok(None)
+    }
+
+    /// Check if we have enough gas for an operation
+    fn can_consume(&self, amount: u64) -> bool {
+        amount <= self.remaining()
+    }
+
+    /// Calculate final gas cost including refunds
+    fn final_cost(&self) -> u64 {
+        self.used() - self.refunded().min(self.used() / MAX_REFUND_QUOTIENT)
+    }
+}
+
+/// EIP-3529: refunds are capped at a fifth of the gas used
+pub const MAX_REFUND_QUOTIENT: u64 = 5;
+
 /// Gas meter for tracking execution costs
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct GasMeter {
@@ -27,6 +114,7 @@ impl GasMeter {
     }
 
     /// Consume gas for an operation
+    #[inline]
     pub fn consume(&mut self, amount: u64) -> Result<(), RvmError> {
         if self.used + amount > self.limit {
             return Err(RvmError::OutOfGas {
@@ -38,12 +126,23 @@ impl GasMeter {
         Ok(())
     }
 
+    /// Give back gas charged ahead of instructions that never ran
+    pub fn release(&mut self, amount: u64) {
+        self.used = self.used.saturating_sub(amount);
+    }
+
     /// Refund gas (for storage operations)
     pub fn refund(&mut self, amount: u64) {
         self.refunded += amount;
     }
 
+    /// Take back gas refunded earlier
+    pub fn remove_refund(&mut self, amount: u64) {
+        self.refunded = self.refunded.saturating_sub(amount);
+    }
+
     /// Get remaining gas
+    #[inline]
     pub fn remaining(&self) -> u64 {
         self.limit.saturating_sub(self.used)
     }
@@ -70,7 +169,7 @@ impl GasMeter {
 
     /// Calculate final gas cost including refunds
     pub fn final_cost(&self) -> u64 {
-        self.used.saturating_sub(self.refunded / 2) // EIP-3529: limit refunds to half of gas used
+        self.used - self.refunded.min(self.used / MAX_REFUND_QUOTIENT)
     }
 
     /// Reset the gas meter for a new execution
@@ -80,6 +179,18 @@ impl GasMeter {
         self.refunded = 0;
     }
 
+    /// Get intrinsic gas of a transaction, charged before execution: the base cost, calldata
+    /// (EIP-2028), creation with its initcode words (EIP-3860) and access list (EIP-2930)
+    pub fn intrinsic_gas(data: &[u8], is_create: bool, access_list_addresses: usize, access_list_keys: usize) -> u64 {
+        let zero_bytes = data.iter().filter(|&&byte| byte == 0).count() as u64;
+        let non_zero_bytes = data.len() as u64 - zero_bytes;
+        let mut gas = 21000 + zero_bytes * 4 + non_zero_bytes * 16;
+        if is_create {
+            gas += 32000 + data.len().div_ceil(32) as u64 * 2;
+        }
+        gas + access_list_addresses as u64 * 2400 + access_list_keys as u64 * 1900
+    }
+
     /// Get gas cost for memory expansion
     pub fn memory_gas_cost(current_size: usize, new_size: usize) -> u64 {
         if new_size <= current_size {
@@ -112,46 +223,89 @@ impl GasMeter {
         375 + (topics as u64 * 375) + (data_size as u64 * 8)
     }
 
-    /// Get gas cost for SSTORE operation based on current and new values
-    pub fn sstore_gas_cost(current_value: u64, new_value: u64, original_value: u64) -> (u64, u64) {
-        // EIP-2200 gas costs
+    /// Get gas cost and refund change of an SSTORE from the slot's current, new and original
+    /// (transaction start) values, per EIP-2200 with the EIP-3529 refund schedule. Slots are
+    /// always treated as warm.
+    pub fn sstore_gas_cost(current_value: u64, new_value: u64, original_value: u64) -> (u64, i64) {
+        const SLOAD_GAS: u64 = 100;
+        const SSTORE_SET_GAS: u64 = 20000;
+        const SSTORE_RESET_GAS: u64 = 2900;
+        const SSTORE_CLEARS_SCHEDULE: i64 = 4800;
+
         if new_value == current_value {
-            return (100, 0); // SLOAD_GAS
+            return (SLOAD_GAS, 0);
         }
-
         if current_value == original_value {
-            if original_value == 0 {
-                return (20000, 0); // SSTORE_SET_GAS
-            } else {
-                return (5000, 0); // SSTORE_RESET_GAS
-            }
-        } else {
-            let gas_cost = 100; // SLOAD_GAS
-            let mut gas_refund = 0;
+            // First write to the slot in this transaction
+            return match (original_value, new_value) {
+                (0, _) => (SSTORE_SET_GAS, 0),
+                (_, 0) => (SSTORE_RESET_GAS, SSTORE_CLEARS_SCHEDULE),
+                _ => (SSTORE_RESET_GAS, 0),
+            };
+        }
 
-            if current_value != 0 && new_value == 0 {
-                gas_refund += 15000; // SSTORE_CLEARS_SCHEDULE
+        // Dirty slot: the first write already paid, adjust the refunds it earned
+        let mut refund = 0;
+        if original_value != 0 {
+            if current_value == 0 {
+                refund -= SSTORE_CLEARS_SCHEDULE;
+            } else if new_value == 0 {
+                refund += SSTORE_CLEARS_SCHEDULE;
             }
+        }
+        if new_value == original_value {
+            let first_write = if original_value == 0 { SSTORE_SET_GAS } else { SSTORE_RESET_GAS };
+            refund += (first_write - SLOAD_GAS) as i64;
+        }
+        (SLOAD_GAS, refund)
+    }
+}
 
-            if original_value != 0 {
-                if current_value == 0 {
-                    gas_refund -= 15000; // Remove refund if we're setting a cleared slot
-                }
-                if new_value == 0 {
-                    gas_refund += 15000; // Add refund for clearing
-                }
-            }
+impl GasMetering for GasMeter {
+    #[inline]
+    fn consume(&mut self, amount: u64) -> Result<(), RvmError> {
+        GasMeter::consume(self, amount)
+    }
 
-            if original_value == new_value {
-                if original_value == 0 {
-                    gas_refund += 19900; // SSTORE_SET_GAS - SLOAD_GAS
-                } else {
-                    gas_refund += 4900; // SSTORE_RESET_GAS - SLOAD_GAS
-                }
-            }
+    fn release(&mut self, amount: u64) {
+        GasMeter::release(self, amount)
+    }
 
-            (gas_cost, gas_refund)
-        }
+    fn refund(&mut self, amount: u64) {
+        GasMeter::refund(self, amount)
+    }
+
+    fn remove_refund(&mut self, amount: u64) {
+        GasMeter::remove_refund(self, amount)
+    }
+
+    #[inline]
+    fn remaining(&self) -> u64 {
+        GasMeter::remaining(self)
+    }
+
+    fn used(&self) -> u64 {
+        self.used
+    }
+
+    fn refunded(&self) -> u64 {
+        self.refunded
+    }
+
+    fn limit(&self) -> u64 {
+        self.limit
+    }
+
+    fn reset(&mut self, limit: u64) {
+        GasMeter::reset(self, limit)
+    }
+
+    fn child(&self, limit: u64) -> Self {
+        Self { refunded: self.refunded, ..GasMeter::new(limit) }
+    }
+
+    fn merge_child(&mut self, child: &Self) {
+        self.refunded = child.refunded;
     }
 }
 
@@ -187,7 +341,13 @@ mod tests {
         
         assert_eq!(meter.used(), 500);
         assert_eq!(meter.refunded(), 100);
-        assert_eq!(meter.final_cost(), 450); // 500 - 100/2
+        assert_eq!(meter.final_cost(), 400);
+
+        // Refunds are capped at a fifth of the gas used
+        meter.refund(1000);
+        assert_eq!(meter.final_cost(), 400);
+        meter.remove_refund(1050);
+        assert_eq!(meter.final_cost(), 450);
     }
 
     #[test]
@@ -197,21 +357,30 @@ mod tests {
         assert_eq!(GasMeter::memory_gas_cost(0, 64), 6);
     }
 
+    #[test]
+    fn test_intrinsic_gas() {
+        assert_eq!(GasMeter::intrinsic_gas(&[], false, 0, 0), 21000);
+        assert_eq!(GasMeter::intrinsic_gas(&[0, 0, 1], false, 0, 0), 21000 + 4 + 4 + 16);
+        // 33 bytes of initcode are two words
+        assert_eq!(GasMeter::intrinsic_gas(&[1; 33], true, 0, 0), 21000 + 33 * 16 + 32000 + 2 * 2);
+        assert_eq!(GasMeter::intrinsic_gas(&[], false, 2, 3), 21000 + 2 * 2400 + 3 * 1900);
+    }
+
     #[test]
     fn test_sstore_gas_cost() {
         // New storage slot
-        let (gas, refund) = GasMeter::sstore_gas_cost(0, 100, 0);
-        assert_eq!(gas, 20000);
-        assert_eq!(refund, 0);
-
+        assert_eq!(GasMeter::sstore_gas_cost(0, 100, 0), (20000, 0));
         // Modify existing slot
-        let (gas, refund) = GasMeter::sstore_gas_cost(100, 200, 100);
-        assert_eq!(gas, 5000);
-        assert_eq!(refund, 0);
-
+        assert_eq!(GasMeter::sstore_gas_cost(100, 200, 100), (2900, 0));
         // Clear storage slot
-        let (gas, refund) = GasMeter::sstore_gas_cost(100, 0, 100);
-        assert_eq!(gas, 5000);
-        assert_eq!(refund, 15000);
+        assert_eq!(GasMeter::sstore_gas_cost(100, 0, 100), (2900, 4800));
+        // Rewriting a value is a read
+        assert_eq!(GasMeter::sstore_gas_cost(100, 100, 100), (100, 0));
+
+        // Dirty slots: un-clearing takes the clearing refund back, restoring refunds the first write
+        assert_eq!(GasMeter::sstore_gas_cost(0, 200, 100), (100, -4800));
+        assert_eq!(GasMeter::sstore_gas_cost(200, 0, 100), (100, 4800));
+        assert_eq!(GasMeter::sstore_gas_cost(0, 100, 100), (100, -4800 + 2800));
+        assert_eq!(GasMeter::sstore_gas_cost(100, 0, 0), (100, 19900));
     }
 }
@@ -2,11 +2,25 @@
 //!
 //! Implements the advanced gas metering system supporting GCC, SPIRIT, MANA, and GHOST tokens
 //! as outlined in the GCC_GAMEPLAN_FALL2025.md
+//!
+//! Rates are integers in basis points (1/10000) so every node prices gas identically.
+//! Fractional token amounts are rounded toward zero: a SPIRIT discount never exceeds the exact
+//! discount, so the GCC cost never falls below the exact cost, while GHOST premiums and MANA
+//! rewards never exceed their exact values.
 
-use crate::error::RvmError;
+use crate::{error::RvmError, gas::{GasMetering, MAX_REFUND_QUOTIENT}, host::Host, opcodes::Opcode};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
+/// Basis points in a whole (100%)
+pub const BASIS_POINTS: u64 = 10_000;
+
+/// `bps` basis points of `amount`, rounded toward zero and saturating at `u64::MAX`
+pub fn apply_basis_points(amount: u64, bps: u64) -> u64 {
+    let scaled = amount as u128 * bps as u128 / BASIS_POINTS as u128;
+    u64::try_from(scaled).unwrap_or(u64::MAX)
+}
+
 /// Token types in the GhostChain ecosystem
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum TokenType {
@@ -20,23 +34,65 @@ pub enum TokenType {
     GHOST,
 }
 
+impl TokenType {
+    /// Token for a numeric id as used on the VM stack (0 = GCC, 1 = SPIRIT, 2 = MANA, 3 = GHOST)
+    pub fn from_id(id: u64) -> Option<Self> {
+        match id {
+            0 => Some(TokenType::GCC),
+            1 => Some(TokenType::SPIRIT),
+            2 => Some(TokenType::MANA),
+            3 => Some(TokenType::GHOST),
+            _ => None,
+        }
+    }
+
+    /// Numeric id of the token
+    pub fn id(&self) -> u64 {
+        match self {
+            TokenType::GCC => 0,
+            TokenType::SPIRIT => 1,
+            TokenType::MANA => 2,
+            TokenType::GHOST => 3,
+        }
+    }
+}
+
 /// Gas configuration for the 4-token economy
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct GhostChainGasConfig {
     /// Base gas price in GCC
     pub gcc_gas_price: u64,
-    /// SPIRIT holders get gas discounts (percentage)
-    pub spirit_discount_percentage: f64,
+    /// GCC discount for SPIRIT holders, in basis points
+    pub spirit_discount_bps: u64,
     /// Minimum SPIRIT balance required for discount
     pub spirit_discount_threshold: u64,
-    /// MANA earned per gas unit for contract execution
-    pub mana_reward_rate: f64,
-    /// Premium gas price multiplier for .ghost domain operations
-    pub ghost_premium_multiplier: f64,
+    /// MANA earned per gas unit of AI operations, in basis points
+    pub mana_reward_bps: u64,
+    /// GHOST premium on the GCC cost of domain operations, in basis points
+    pub ghost_premium_bps: u64,
     /// Gas costs for specific token operations
     pub token_operation_costs: HashMap<TokenType, u64>,
 }
 
+/// Token fees settled after an execution
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FeeReceipt {
+    /// Account charged the fees
+    pub payer: [u8; 20],
+    /// Validator paid the fees
+    pub validator: [u8; 20],
+    /// Gas units charged, before storage refunds
+    pub gas_used: u64,
+    /// GCC paid to the validator
+    pub gcc_paid: u64,
+    /// GCC discount granted for holding SPIRIT
+    pub spirit_discount: u64,
+    /// GHOST premium paid to the validator for domain operations
+    pub ghost_paid: u64,
+    /// MANA minted to the payer for AI operations
+    pub mana_credited: u64,
+}
+
 /// Token balances for an address
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct TokenBalances {
@@ -46,7 +102,12 @@ pub struct TokenBalances {
     pub ghost: u64,
 }
 
-/// Enhanced gas meter supporting 4-token economy
+/// Enhanced gas meter supporting 4-token economy.
+///
+/// Gas is counted in units like `GasMeter`, and token amounts are derived from the units
+/// used, so gas given back by `release` is never paid for. Consuming gas fails once the GCC
+/// cost of all gas used, or the GHOST premium of domain operations, exceeds the executor's
+/// balance.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct GhostChainGasMeter {
     /// Base gas meter
@@ -59,11 +120,12 @@ pub struct GhostChainGasMeter {
     executor_address: [u8; 20],
     executor_balances: TokenBalances,
 
-    /// Token rewards/costs during execution
-    mana_rewards_earned: u64,
-    gcc_gas_cost: u64,
-    spirit_discount_applied: u64,
-    ghost_premium_paid: u64,
+    /// Gas used by domain operations, which pay the GHOST premium
+    domain_gas: u64,
+    /// Gas used by AI operations, which earn MANA
+    ai_gas: u64,
+    /// MANA minted through `mint_mana_rewards`
+    mana_minted: u64,
 }
 
 /// Gas payment information
@@ -109,10 +171,30 @@ impl GhostChainGasMeter {
             config,
             executor_address,
             executor_balances,
-            mana_rewards_earned: 0,
-            gcc_gas_cost: 0,
-            spirit_discount_applied: 0,
-            ghost_premium_paid: 0,
+            domain_gas: 0,
+            ai_gas: 0,
+            mana_minted: 0,
+        }
+    }
+
+    /// Pricing context of a GhostChain opcode: GhostID and CNS opcodes are domain
+    /// operations, AGENT_* opcodes are AI operations
+    pub fn operation_context(&self, opcode: Opcode) -> GasExecutionContext {
+        GasExecutionContext {
+            executor: self.executor_address,
+            contract_address: None,
+            is_domain_operation: matches!(
+                opcode,
+                Opcode::GHOST_ID_VERIFY
+                    | Opcode::GHOST_ID_RESOLVE
+                    | Opcode::GHOST_ID_CREATE
+                    | Opcode::CNS_RESOLVE
+                    | Opcode::CNS_REGISTER
+                    | Opcode::CNS_UPDATE
+                    | Opcode::CNS_OWNER
+            ),
+            domain_name: None,
+            has_ai_operations: matches!(opcode, Opcode::AGENT_CALL | Opcode::AGENT_DEPLOY | Opcode::AGENT_QUERY),
         }
     }
 
@@ -122,7 +204,7 @@ impl GhostChainGasMeter {
         base_gas: u64,
         context: &GasExecutionContext,
     ) -> Result<GasPayment, RvmError> {
-        let mut base_cost = base_gas * self.config.gcc_gas_price;
+        let mut base_cost = base_gas.saturating_mul(self.config.gcc_gas_price);
         let mut payment = GasPayment {
             primary_token: TokenType::GCC,
             primary_amount: base_cost,
@@ -131,21 +213,18 @@ impl GhostChainGasMeter {
         };
 
         // Apply SPIRIT discount if applicable
-        if self.executor_balances.spirit >= self.config.spirit_discount_threshold {
-            let discount = (base_cost as f64 * self.config.spirit_discount_percentage / 100.0) as u64;
-            base_cost = base_cost.saturating_sub(discount);
-            payment.primary_amount = base_cost;
-        }
+        base_cost = self.apply_token_discounts(base_cost);
+        payment.primary_amount = base_cost;
 
         // Apply GHOST premium for domain operations
         if context.is_domain_operation {
-            let premium = (base_cost as f64 * (self.config.ghost_premium_multiplier - 1.0)) as u64;
+            let premium = apply_basis_points(base_cost, self.config.ghost_premium_bps);
             payment.additional_payments.insert(TokenType::GHOST, premium);
         }
 
         // Calculate MANA rewards for AI operations
         if context.has_ai_operations {
-            let mana_reward = (base_gas as f64 * self.config.mana_reward_rate) as u64;
+            let mana_reward = apply_basis_points(base_gas, self.config.mana_reward_bps);
             // MANA rewards are negative payments (earnings)
             payment.additional_payments.insert(TokenType::MANA, mana_reward);
         }
@@ -170,36 +249,40 @@ impl GhostChainGasMeter {
         // Calculate token costs
         let payment = self.calculate_gas_cost(gas_amount, context)?;
 
-        // Verify token balances (simplified - in production, this would be checked by the runtime)
-        if payment.primary_amount > self.executor_balances.gcc {
+        // The executor must afford all gas used so far
+        let gcc_cost = self.gcc_cost_of(self.used + gas_amount);
+        if gcc_cost > self.executor_balances.gcc {
             return Err(RvmError::InsufficientTokenBalance {
                 token: "GCC".to_string(),
-                required: payment.primary_amount,
+                required: gcc_cost,
                 available: self.executor_balances.gcc,
             });
         }
+        self.attribute(gas_amount, context)?;
 
         // Update gas usage
         self.used += gas_amount;
-        self.gcc_gas_cost += payment.primary_amount;
 
-        // Track SPIRIT discounts
-        if self.executor_balances.spirit >= self.config.spirit_discount_threshold {
-            let discount = (payment.primary_amount as f64 * self.config.spirit_discount_percentage / 100.0) as u64;
-            self.spirit_discount_applied += discount;
-        }
+        Ok(payment)
+    }
 
-        // Track GHOST premiums
-        if let Some(ghost_premium) = payment.additional_payments.get(&TokenType::GHOST) {
-            self.ghost_premium_paid += ghost_premium;
+    /// Count gas units toward the domain premium and AI rewards of their operation
+    fn attribute(&mut self, gas_amount: u64, context: &GasExecutionContext) -> Result<(), RvmError> {
+        if context.is_domain_operation {
+            let premium = self.premium_of(self.domain_gas + gas_amount);
+            if premium > self.executor_balances.ghost {
+                return Err(RvmError::InsufficientTokenBalance {
+                    token: "GHOST".to_string(),
+                    required: premium,
+                    available: self.executor_balances.ghost,
+                });
+            }
+            self.domain_gas += gas_amount;
         }
-
-        // Track MANA rewards
-        if let Some(mana_reward) = payment.additional_payments.get(&TokenType::MANA) {
-            self.mana_rewards_earned += mana_reward;
+        if context.has_ai_operations {
+            self.ai_gas += gas_amount;
         }
-
-        Ok(payment)
+        Ok(())
     }
 
     /// Standard gas consumption (for backwards compatibility)
@@ -218,16 +301,15 @@ impl GhostChainGasMeter {
 
     /// Mint MANA rewards for contract execution
     pub fn mint_mana_rewards(&mut self, gas_used: u64) -> u64 {
-        let mana_reward = (gas_used as f64 * self.config.mana_reward_rate) as u64;
-        self.mana_rewards_earned += mana_reward;
+        let mana_reward = apply_basis_points(gas_used, self.config.mana_reward_bps);
+        self.mana_minted += mana_reward;
         mana_reward
     }
 
     /// Apply token-specific discounts
     pub fn apply_token_discounts(&self, base_cost: u64) -> u64 {
         if self.executor_balances.spirit >= self.config.spirit_discount_threshold {
-            let discount = (base_cost as f64 * self.config.spirit_discount_percentage / 100.0) as u64;
-            base_cost.saturating_sub(discount)
+            base_cost.saturating_sub(apply_basis_points(base_cost, self.config.spirit_discount_bps))
         } else {
             base_cost
         }
@@ -241,22 +323,55 @@ impl GhostChainGasMeter {
             .unwrap_or(1000) // Default cost
     }
 
+    /// Charge the GCC cost and GHOST premium of the gas used from `payer` to `validator`, and
+    /// issue the MANA rewards to `payer`; the validator needs no minting rights. Nothing is
+    /// settled unless every step succeeds.
+    ///
+    /// Fees are charged on all gas used, before storage refunds: refunds are granted by the
+    /// transaction in its native fee, while token fees pay for the work done, so the
+    /// receipt's `gas_used` matches the execution's.
+    pub fn settle(&self, payer: [u8; 20], validator: [u8; 20], host: &mut dyn Host) -> Result<FeeReceipt, RvmError> {
+        let receipt = FeeReceipt {
+            payer,
+            validator,
+            gas_used: self.used,
+            gcc_paid: self.gcc_cost(),
+            spirit_discount: self.spirit_discount(),
+            ghost_paid: self.ghost_premium(),
+            mana_credited: self.mana_rewards(),
+        };
+        let checkpoint = host.checkpoint();
+        let settled = [(TokenType::GCC, receipt.gcc_paid), (TokenType::GHOST, receipt.ghost_paid)]
+            .into_iter()
+            .filter(|&(_, amount)| amount > 0)
+            .try_for_each(|(token, amount)| host.transfer_tokens(payer, validator, token, amount))
+            .and_then(|()| match receipt.mana_credited {
+                0 => Ok(()),
+                mana => host.issue_tokens(payer, TokenType::MANA, mana),
+            });
+        if let Err(e) = settled {
+            host.revert_to(checkpoint);
+            return Err(e);
+        }
+        Ok(receipt)
+    }
+
     /// Get final token costs breakdown
     pub fn get_token_costs_breakdown(&self) -> HashMap<TokenType, u64> {
         let mut breakdown = HashMap::new();
 
-        breakdown.insert(TokenType::GCC, self.gcc_gas_cost);
+        breakdown.insert(TokenType::GCC, self.gcc_cost());
 
-        if self.spirit_discount_applied > 0 {
-            breakdown.insert(TokenType::SPIRIT, self.spirit_discount_applied);
+        if self.spirit_discount() > 0 {
+            breakdown.insert(TokenType::SPIRIT, self.spirit_discount());
         }
 
-        if self.ghost_premium_paid > 0 {
-            breakdown.insert(TokenType::GHOST, self.ghost_premium_paid);
+        if self.ghost_premium() > 0 {
+            breakdown.insert(TokenType::GHOST, self.ghost_premium());
         }
 
-        if self.mana_rewards_earned > 0 {
-            breakdown.insert(TokenType::MANA, self.mana_rewards_earned);
+        if self.mana_rewards() > 0 {
+            breakdown.insert(TokenType::MANA, self.mana_rewards());
         }
 
         breakdown
@@ -288,22 +403,119 @@ impl GhostChainGasMeter {
     }
 
     pub fn final_cost(&self) -> u64 {
-        self.used.saturating_sub(self.refunded / 2)
+        self.used - self.refunded.min(self.used / MAX_REFUND_QUOTIENT)
+    }
+
+    /// Get the GCC cost of the gas used
+    pub fn gcc_cost(&self) -> u64 {
+        self.gcc_cost_of(self.used)
     }
 
     /// Get MANA rewards earned during execution
     pub fn mana_rewards(&self) -> u64 {
-        self.mana_rewards_earned
+        self.mana_minted + apply_basis_points(self.ai_gas, self.config.mana_reward_bps)
     }
 
     /// Get SPIRIT discount applied
     pub fn spirit_discount(&self) -> u64 {
-        self.spirit_discount_applied
+        self.used.saturating_mul(self.config.gcc_gas_price) - self.gcc_cost()
     }
 
     /// Get GHOST premium paid
     pub fn ghost_premium(&self) -> u64 {
-        self.ghost_premium_paid
+        self.premium_of(self.domain_gas)
+    }
+
+    /// GCC cost of gas units, after any SPIRIT discount
+    fn gcc_cost_of(&self, gas: u64) -> u64 {
+        self.apply_token_discounts(gas.saturating_mul(self.config.gcc_gas_price))
+    }
+
+    /// GHOST premium on the GCC cost of domain operation gas units
+    fn premium_of(&self, gas: u64) -> u64 {
+        apply_basis_points(self.gcc_cost_of(gas), self.config.ghost_premium_bps)
+    }
+}
+
+impl GasMetering for GhostChainGasMeter {
+    fn consume(&mut self, amount: u64) -> Result<(), RvmError> {
+        GhostChainGasMeter::consume(self, amount)
+    }
+
+    fn settle(&self, payer: [u8; 20], validator: [u8; 20], host: &mut dyn Host) -> Result<Option<FeeReceipt>, RvmError> {
+        GhostChainGasMeter::settle(self, payer, validator, host).map(Some)
+    }
+
+    fn consume_for(&mut self, opcode: Opcode, amount: u64) -> Result<(), RvmError> {
+        let context = self.operation_context(opcode);
+        self.consume_with_tokens(amount, &context)?;
+        Ok(())
+    }
+
+    fn price_for(&mut self, opcode: Opcode, amount: u64) -> Result<(), RvmError> {
+        let context = self.operation_context(opcode);
+        self.attribute(amount, &context)
+    }
+
+    fn release(&mut self, amount: u64) {
+        self.used = self.used.saturating_sub(amount);
+    }
+
+    fn refund(&mut self, amount: u64) {
+        self.refunded += amount;
+    }
+
+    fn remove_refund(&mut self, amount: u64) {
+        self.refunded = self.refunded.saturating_sub(amount);
+    }
+
+    fn remaining(&self) -> u64 {
+        self.limit.saturating_sub(self.used)
+    }
+
+    fn used(&self) -> u64 {
+        self.used
+    }
+
+    fn refunded(&self) -> u64 {
+        self.refunded
+    }
+
+    fn limit(&self) -> u64 {
+        self.limit
+    }
+
+    fn reset(&mut self, limit: u64) {
+        self.limit = limit;
+        self.used = 0;
+        self.refunded = 0;
+        self.domain_gas = 0;
+        self.ai_gas = 0;
+        self.mana_minted = 0;
+    }
+
+    /// Meter the executor's current on-state token balances
+    fn begin(&mut self, limit: u64, executor: [u8; 20], host: &dyn Host) {
+        self.reset(limit);
+        self.executor_address = executor;
+        for token in [TokenType::GCC, TokenType::SPIRIT, TokenType::MANA, TokenType::GHOST] {
+            self.executor_balances.set_balance(token, host.token_balance(&executor, token));
+        }
+    }
+
+    /// The child may spend what this meter has left of the executor's balances
+    fn child(&self, limit: u64) -> Self {
+        let mut balances = self.executor_balances.clone();
+        balances.gcc = balances.gcc.saturating_sub(self.gcc_cost());
+        balances.ghost = balances.ghost.saturating_sub(self.ghost_premium());
+        Self { refunded: self.refunded, ..Self::new(limit, self.config.clone(), self.executor_address, balances) }
+    }
+
+    fn merge_child(&mut self, child: &Self) {
+        self.refunded = child.refunded;
+        self.domain_gas += child.domain_gas;
+        self.ai_gas += child.ai_gas;
+        self.mana_minted += child.mana_minted;
     }
 }
 
@@ -317,10 +529,10 @@ impl Default for GhostChainGasConfig {
 
         Self {
             gcc_gas_price: 1_000_000_000, // 1 Gwei equivalent
-            spirit_discount_percentage: 10.0, // 10% discount
+            spirit_discount_bps: 1_000, // 10% discount
             spirit_discount_threshold: 1000, // Minimum 1000 SPIRIT
-            mana_reward_rate: 0.1, // 0.1 MANA per gas unit
-            ghost_premium_multiplier: 1.5, // 50% premium for .ghost operations
+            mana_reward_bps: 1_000, // 0.1 MANA per gas unit
+            ghost_premium_bps: 5_000, // 50% premium for .ghost operations
             token_operation_costs: token_costs,
         }
     }
@@ -453,4 +665,202 @@ mod tests {
         let payment = meter.calculate_gas_cost(100, &context).unwrap();
         assert!(payment.additional_payments.contains_key(&TokenType::MANA));
     }
+
+    #[test]
+    fn test_basis_point_pricing_is_exact() {
+        for (amount, bps, expected) in [
+            (0, 1_000, 0),
+            (9, 1_000, 0),
+            (19, 5_000, 9),
+            (12_345, 1, 1),
+            (1, 9_999, 0),
+            (u64::MAX, 5_000, u64::MAX / 2),
+            (u64::MAX, BASIS_POINTS, u64::MAX),
+            (u64::MAX, 2 * BASIS_POINTS, u64::MAX),
+        ] {
+            assert_eq!(apply_basis_points(amount, bps), expected, "{} bps of {}", bps, amount);
+        }
+
+        let config = GhostChainGasConfig {
+            gcc_gas_price: 3,
+            spirit_discount_bps: 3_333,
+            ..GhostChainGasConfig::default()
+        };
+        let meter = GhostChainGasMeter::new(u64::MAX, config, [1u8; 20], TokenBalances::new(0, 1000, 0, 0));
+        let context = GasExecutionContext {
+            executor: [1u8; 20],
+            contract_address: None,
+            is_domain_operation: true,
+            domain_name: None,
+            has_ai_operations: true,
+        };
+
+        // 21 GCC less a 6.9993 discount rounded down, 7.5 GHOST and 0.7 MANA rounded down
+        let payment = meter.calculate_gas_cost(7, &context).unwrap();
+        assert_eq!(payment.primary_amount, 15);
+        assert_eq!(payment.additional_payments[&TokenType::GHOST], 7);
+        assert_eq!(payment.additional_payments[&TokenType::MANA], 0);
+
+        // Costs saturate rather than overflow
+        let payment = meter.calculate_gas_cost(u64::MAX, &context).unwrap();
+        assert_eq!(payment.primary_amount, 12_298_444_273_942_158_062);
+        assert_eq!(payment.additional_payments[&TokenType::GHOST], 6_149_222_136_971_079_031);
+        assert_eq!(payment.additional_payments[&TokenType::MANA], 1_844_674_407_370_955_161);
+    }
+
+    #[tokio::test]
+    async fn test_token_pricing_applied_to_execution() {
+        use crate::{
+            asm::assemble,
+            bytecode::CodeCache,
+            core::{ExecutionEnvironment, RvmCore},
+            custom_opcodes::OpcodeRegistry,
+            ghostchain_services::{GhostChainHost, GhostChainServices},
+            runtime::{RuntimeConfig, RvmRuntime},
+            storage::Storage,
+            wasm_lite::{WasmLiteValue, WasmLiteVM},
+        };
+        use std::sync::Arc;
+
+        let config = GhostChainGasConfig { gcc_gas_price: 10, ..GhostChainGasConfig::default() };
+        let executor = [1u8; 20];
+
+        // Domain opcodes pay the 50% GHOST premium on their gas, base cost included
+        let mut registry = OpcodeRegistry::new();
+        GhostChainServices::register_opcodes(&mut registry).unwrap();
+        let balances = TokenBalances::new(1_000_000, 0, 0, 1_000_000);
+        let meter = GhostChainGasMeter::new(100_000, config.clone(), executor, balances);
+        let registry = Arc::new(registry);
+        let mut core = RvmCore::with_gas_meter(meter.clone(), Arc::new(CodeCache::new()))
+            .with_opcode_registry(Arc::clone(&registry));
+        let code = assemble("PUSH 0\nPUSH 0\nCNS_RESOLVE\nSTOP").unwrap();
+        let (mut storage, mut services) = (Storage::new(), GhostChainServices::new().unwrap());
+        let mut host = GhostChainHost::new(&mut storage, &mut services);
+        let result = core.execute(&code, ExecutionEnvironment::default(), &mut host).await.unwrap();
+        assert!(result.success, "{:?}", result.error);
+        assert_eq!(result.gas_used, 306);
+        assert_eq!(core.gas.gcc_cost(), 3060);
+        assert_eq!(core.gas.ghost_premium(), 1500);
+
+        // A callee's domain gas only counts toward the premium if the callee succeeds
+        let caller = assemble("PUSH 0\nPUSH 0\nPUSH 0\nPUSH 0\nPUSH 0\nPUSH 0x42\nPUSH 50000\nCALL\nSTOP").unwrap();
+        for (end, premium) in [("PUSH 0\nPUSH 0\nREVERT", 0), ("STOP", 1500)] {
+            let callee = assemble(&format!("PUSH 0\nPUSH 0\nCNS_RESOLVE\n{}", end)).unwrap();
+            host.set_code(crate::core::word_to_address(0x42), callee);
+            let mut core = RvmCore::with_gas_meter(meter.clone(), Arc::new(CodeCache::new()))
+                .with_opcode_registry(Arc::clone(&registry));
+            let result = core.execute(&caller, ExecutionEnvironment::default(), &mut host).await.unwrap();
+            assert!(result.success, "{:?}", result.error);
+            assert_eq!(core.gas.ghost_premium(), premium);
+        }
+
+        // The runtime meters each execution against the caller's on-state balances:
+        // 9 gas costs 90 GCC, or 81 with the SPIRIT discount
+        let meter = GhostChainGasMeter::new(0, config.clone(), [0u8; 20], TokenBalances::default());
+        let mut runtime = RvmRuntime::with_gas_meter(RuntimeConfig::default(), meter);
+        let env = ExecutionEnvironment { caller: executor, ..ExecutionEnvironment::default() };
+        let code = assemble("PUSH 1\nPUSH 2\nADD\nSTOP").unwrap();
+        for (gcc, spirit, success) in [(89, 0, false), (90, 0, true), (81, 1000, true), (80, 1000, false)] {
+            {
+                let storage = runtime.storage();
+                let mut storage = storage.write().await;
+                storage.set_token_balance(executor, TokenType::GCC, gcc);
+                storage.set_token_balance(executor, TokenType::SPIRIT, spirit);
+            }
+            let result = runtime.execute(&code, env.clone()).await.unwrap();
+            assert_eq!(result.success, success, "{} GCC, {} SPIRIT: {:?}", gcc, spirit, result.error);
+            if !success {
+                assert!(result.error.unwrap().contains("GCC"));
+            }
+        }
+
+        // WASM-lite executions are metered the same way
+        let meter = GhostChainGasMeter::new(0, config, executor, TokenBalances::new(5, 0, 0, 0));
+        let mut vm = WasmLiteVM::with_gas_meter(meter);
+        vm.load_module("demo".to_string(), WasmLiteVM::create_demo_module()).unwrap();
+        let args = vec![WasmLiteValue::I32(1), WasmLiteValue::I32(2)];
+        let error = vm.execute_function("demo", "add", args, 1000, env).await.unwrap_err();
+        assert!(matches!(error, RvmError::InsufficientTokenBalance { .. }));
+    }
+
+    #[tokio::test]
+    async fn test_fee_settlement() {
+        use crate::{
+            asm::assemble,
+            core::ExecutionEnvironment,
+            runtime::{RuntimeConfig, RvmRuntime},
+            storage::Storage,
+        };
+
+        let config = GhostChainGasConfig { gcc_gas_price: 10, ..GhostChainGasConfig::default() };
+        let (payer, validator) = ([1u8; 20], [2u8; 20]);
+        let balances = TokenBalances::new(20_000, 1000, 0, 2000);
+        let mut storage = Storage::new();
+        for (token, amount) in [(TokenType::GCC, 20_000), (TokenType::SPIRIT, 1000), (TokenType::GHOST, 2000)] {
+            storage.set_token_balance(payer, token, amount);
+        }
+
+        // 1300 gas at 10 GCC less 10%, a 50% GHOST premium on the 300 domain gas
+        // and 0.1 MANA per AI gas unit
+        let mut meter = GhostChainGasMeter::new(100_000, config.clone(), payer, balances);
+        meter.consume_for(Opcode::CNS_RESOLVE, 300).unwrap();
+        meter.consume_for(Opcode::AGENT_QUERY, 1000).unwrap();
+
+        // Settlement is all or nothing: a GHOST shortfall leaves the GCC untouched
+        storage.set_token_balance(payer, TokenType::GHOST, 1000);
+        assert!(meter.settle(payer, validator, &mut storage).is_err());
+        assert_eq!(storage.get_token_balance(&payer, TokenType::GCC), 20_000);
+        assert_eq!(storage.get_token_balance(&validator, TokenType::GCC), 0);
+
+        // MANA is issued without the validator holding minting rights
+        storage.set_token_balance(payer, TokenType::GHOST, 2000);
+        let receipt = meter.settle(payer, validator, &mut storage).unwrap();
+        assert_eq!(receipt, FeeReceipt {
+            payer,
+            validator,
+            gas_used: 1300,
+            gcc_paid: 11_700,
+            spirit_discount: 1300,
+            ghost_paid: 1350,
+            mana_credited: 100,
+        });
+        for (address, token, expected) in [
+            (payer, TokenType::GCC, 8300),
+            (payer, TokenType::GHOST, 650),
+            (payer, TokenType::MANA, 100),
+            (validator, TokenType::GCC, 11_700),
+            (validator, TokenType::GHOST, 1350),
+        ] {
+            assert_eq!(storage.get_token_balance(&address, token), expected, "{:?}", token);
+        }
+
+        // The runtime settles every execution and attaches the receipt
+        let meter = GhostChainGasMeter::new(0, config, [0u8; 20], TokenBalances::default());
+        let mut runtime = RvmRuntime::with_gas_meter(RuntimeConfig::default(), meter);
+        runtime.storage().write().await.set_token_balance(payer, TokenType::GCC, 100);
+        let env = ExecutionEnvironment { caller: payer, coinbase: validator, ..ExecutionEnvironment::default() };
+        let result = runtime.execute(&assemble("PUSH 1\nPUSH 2\nADD\nSTOP").unwrap(), env).await.unwrap();
+        let receipt = result.fee_receipt.unwrap();
+        assert_eq!((receipt.gas_used, receipt.gcc_paid, receipt.ghost_paid), (9, 90, 0));
+        let storage = runtime.storage();
+        let storage = storage.read().await;
+        assert_eq!(storage.get_token_balance(&payer, TokenType::GCC), 10);
+        assert_eq!(storage.get_token_balance(&validator, TokenType::GCC), 90);
+        drop(storage);
+
+        // An execution whose fees cannot be settled leaves no trace
+        {
+            let storage = runtime.storage();
+            let mut storage = storage.write().await;
+            storage.set_token_balance(payer, TokenType::GCC, 1_000_000);
+            storage.set_token_balance(validator, TokenType::GCC, u64::MAX);
+        }
+        let env = ExecutionEnvironment { caller: payer, coinbase: validator, ..ExecutionEnvironment::default() };
+        let result = runtime.execute(&assemble("PUSH 7\nPUSH 0\nSSTORE\nSTOP").unwrap(), env).await;
+        assert!(result.is_err());
+        let storage = runtime.storage();
+        let storage = storage.read().await;
+        assert_eq!(storage.get_storage(&[0u8; 20], 0), 0);
+        assert_eq!(storage.get_token_balance(&payer, TokenType::GCC), 1_000_000);
+    }
 }
\ No newline at end of file
@@ -2,10 +2,49 @@
 //!
 //! Implementation of GhostID identity verification and CNS domain resolution
 //! for RVM opcodes. Integrates with GhostChain services running on ports 8552-8555.
+//!
+//! The opcode handlers reach the services through `Host::ghostchain_services`. Byte
+//! strings (IDs, messages, signatures, keys) are passed as `offset, size` pairs into
+//! memory, first operand on top of the stack:
+//!
+//! | Opcode             | Operands                                               | Result            |
+//! |--------------------|--------------------------------------------------------|-------------------|
+//! | `GHOST_ID_VERIFY`  | id, message, signature (`offset, size` each)           | 1 if verified     |
+//! | `GHOST_ID_RESOLVE` | id (`offset, size`)                                    | address, or 0     |
+//! | `GHOST_ID_CREATE`  | public key (`offset, size`), output offset             | length of the ID  |
+//! | `CNS_RESOLVE`      | domain (`offset, size`)                                | address, or 0     |
+//! | `CNS_REGISTER`     | domain (`offset, size`), target address                | 1 if registered   |
+//! | `CNS_UPDATE`       | domain (`offset, size`), new target or 0, records      | 1 if updated      |
+//! | `CNS_OWNER`        | domain (`offset, size`)                                | owner, or 0       |
+//! | `TOKEN_BALANCE`    | token, account                                         | balance           |
+//! | `TOKEN_TRANSFER`   | token, recipient, amount                               | 1 if transferred  |
+//! | `TOKEN_MINT`       | token, recipient, amount                               | 1 if minted       |
+//! | `TOKEN_BURN`       | token, holder, amount                                  | 1 if burned       |
+//!
+//! Signatures use the `GhostIdService` layout: algorithm byte, 64-byte signature and
+//! recovery id. `GHOST_ID_CREATE` writes the new ID as ASCII hex at the output offset.
+//! Domains are owned by the contract that registers them, and CNS_UPDATE records are a
+//! JSON object of strings (`offset, size`, empty for none). Created GhostIDs and CNS
+//! changes are journaled with the host's checkpoints, so they are undone when the frame
+//! making them reverts.
+//!
+//! The TOKEN opcodes work on the host's token ledger, with tokens numbered as in
+//! `TokenType::from_id`. Transfers move tokens out of the executing contract; minting and
+//! burning require the executing contract to be an authorized minter, and burning another
+//! holder's tokens also spends the allowance that holder gave the contract. Every balance change
+//! emits a `TOKEN_TRANSFER_TOPIC` log with the token id, sender and recipient as topics
+//! (the zero address when minting or burning) and the amount as data.
 
 use crate::{
+    agents::AgentHost,
+    core::{address_to_word, word_to_address, ExecutionResult},
+    custom_opcodes::{OpcodeContext, OpcodeHandler, OpcodeRegistry},
     error::RvmError,
+    crypto::RvmCrypto,
     ghostchain_crypto::{GhostChainCrypto, GhostId, GhostSignature, GhostChainCryptoUtils},
+    ghostchain_gas::TokenType,
+    host::{Host, Log, CallInputs},
+    opcodes::Opcode,
 };
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
@@ -15,6 +54,8 @@ use std::collections::HashMap;
 pub struct GhostIdService {
     /// Local cache of verified GhostIDs
     cache: HashMap<String, GhostId>,
+    /// Undo journal of created GhostIDs as (ID, previous cache entry)
+    journal: Vec<(String, Option<GhostId>)>,
     /// Crypto backend
     crypto: GhostChainCrypto,
 }
@@ -26,6 +67,17 @@ pub struct CnsService {
     domains: HashMap<String, DomainRecord>,
     /// Reverse lookup cache (address -> domain)
     reverse_lookup: HashMap<[u8; 20], String>,
+    /// Undo journal, reverted together with the host's checkpoints
+    journal: Vec<CnsJournalEntry>,
+}
+
+/// Undo record for a CNS change
+#[derive(Debug, Clone)]
+enum CnsJournalEntry {
+    /// Domain record inserted or replaced
+    Domain { name: String, previous: Option<DomainRecord> },
+    /// Reverse lookup entry inserted, replaced or removed
+    Reverse { address: [u8; 20], previous: Option<String> },
 }
 
 /// Domain record structure for CNS
@@ -79,16 +131,18 @@ impl GhostIdService {
         let crypto = GhostChainCrypto::new()?;
         Ok(Self {
             cache: HashMap::new(),
+            journal: Vec::new(),
             crypto,
         })
     }
 
-    /// Verify a GhostID signature (GHOST_ID_VERIFY opcode implementation)
+    /// Verify a GhostID signature at block time `now` (GHOST_ID_VERIFY opcode implementation)
     pub fn verify_ghost_id_signature(
         &mut self,
         ghost_id: &str,
         message: &[u8],
         signature_data: &[u8],
+        now: u64,
     ) -> Result<GhostIdVerificationResult, RvmError> {
         // Validate GhostID format
         if !GhostChainCryptoUtils::validate_ghost_id_format(ghost_id) {
@@ -96,13 +150,13 @@ impl GhostIdService {
                 verified: false,
                 ghost_id: None,
                 error: Some("Invalid GhostID format".to_string()),
-                timestamp: self.current_timestamp(),
+                timestamp: now,
             });
         }
 
         // Try to get GhostID from cache first
         if let Some(cached_ghost_id) = self.cache.get(ghost_id) {
-            return self.verify_with_ghost_id(cached_ghost_id, message, signature_data);
+            return self.verify_with_ghost_id(cached_ghost_id, message, signature_data, now);
         }
 
         // If not in cache, try to resolve from service (simulated for now)
@@ -110,13 +164,13 @@ impl GhostIdService {
             Ok(ghost_id_obj) => {
                 // Cache the result
                 self.cache.insert(ghost_id.to_string(), ghost_id_obj.clone());
-                self.verify_with_ghost_id(&ghost_id_obj, message, signature_data)
+                self.verify_with_ghost_id(&ghost_id_obj, message, signature_data, now)
             }
             Err(e) => Ok(GhostIdVerificationResult {
                 verified: false,
                 ghost_id: None,
                 error: Some(format!("Failed to fetch GhostID: {}", e)),
-                timestamp: self.current_timestamp(),
+                timestamp: now,
             }),
         }
     }
@@ -161,18 +215,41 @@ impl GhostIdService {
         // Create GhostID
         let ghost_id = self.crypto.create_ghost_id(&public_key, domains, metadata)?;
 
-        // Cache the new GhostID
-        self.cache.insert(ghost_id.id.clone(), ghost_id.clone());
+        // Cache the new GhostID, recording the previous entry
+        let previous = self.cache.insert(ghost_id.id.clone(), ghost_id.clone());
+        self.journal.push((ghost_id.id.clone(), previous));
 
         Ok(ghost_id.id)
     }
 
+    /// Open a journal checkpoint
+    pub fn checkpoint(&self) -> usize {
+        self.journal.len()
+    }
+
+    /// Forget every GhostID created since the checkpoint
+    pub fn revert_to(&mut self, checkpoint: usize) {
+        while self.journal.len() > checkpoint {
+            let Some((id, previous)) = self.journal.pop() else { break };
+            match previous {
+                Some(ghost_id) => self.cache.insert(id, ghost_id),
+                None => self.cache.remove(&id),
+            };
+        }
+    }
+
+    /// Make all created GhostIDs permanent
+    pub fn commit(&mut self) {
+        self.journal.clear();
+    }
+
     /// Verify signature with known GhostID
     fn verify_with_ghost_id(
         &self,
         ghost_id: &GhostId,
         message: &[u8],
         signature_data: &[u8],
+        now: u64,
     ) -> Result<GhostIdVerificationResult, RvmError> {
         // Parse signature data (format: algorithm[1] + signature[64] + recovery_id[1])
         if signature_data.len() < 66 {
@@ -180,7 +257,7 @@ impl GhostIdService {
                 verified: false,
                 ghost_id: None,
                 error: Some("Invalid signature data length".to_string()),
-                timestamp: self.current_timestamp(),
+                timestamp: now,
             });
         }
 
@@ -192,7 +269,7 @@ impl GhostIdService {
                     verified: false,
                     ghost_id: None,
                     error: Some("Unsupported signature algorithm".to_string()),
-                    timestamp: self.current_timestamp(),
+                    timestamp: now,
                 });
             }
         };
@@ -210,13 +287,13 @@ impl GhostIdService {
                 verified,
                 ghost_id: if verified { Some(ghost_id.clone()) } else { None },
                 error: None,
-                timestamp: self.current_timestamp(),
+                timestamp: now,
             }),
             Err(e) => Ok(GhostIdVerificationResult {
                 verified: false,
                 ghost_id: None,
                 error: Some(format!("Verification failed: {}", e)),
-                timestamp: self.current_timestamp(),
+                timestamp: now,
             }),
         }
     }
@@ -244,14 +321,6 @@ impl GhostIdService {
             Err(RvmError::GhostIdNotFound(ghost_id.to_string()))
         }
     }
-
-    /// Get current timestamp
-    fn current_timestamp(&self) -> u64 {
-        std::time::SystemTime::now()
-            .duration_since(std::time::UNIX_EPOCH)
-            .unwrap_or_default()
-            .as_secs()
-    }
 }
 
 impl CnsService {
@@ -260,6 +329,7 @@ impl CnsService {
         let mut service = Self {
             domains: HashMap::new(),
             reverse_lookup: HashMap::new(),
+            journal: Vec::new(),
         };
 
         // Initialize with some test domains
@@ -267,8 +337,8 @@ impl CnsService {
         service
     }
 
-    /// Resolve domain to address (CNS_RESOLVE opcode implementation)
-    pub fn resolve_domain(&self, domain: &str) -> Result<CnsResolutionResult, RvmError> {
+    /// Resolve domain to address at block time `now` (CNS_RESOLVE opcode implementation)
+    pub fn resolve_domain(&self, domain: &str, now: u64) -> Result<CnsResolutionResult, RvmError> {
         // Validate domain format
         if domain.is_empty() || !domain.contains('.') {
             return Ok(CnsResolutionResult {
@@ -292,12 +362,7 @@ impl CnsService {
         // Look up domain
         if let Some(record) = self.domains.get(domain) {
             // Check if domain has expired
-            let current_time = std::time::SystemTime::now()
-                .duration_since(std::time::UNIX_EPOCH)
-                .unwrap_or_default()
-                .as_secs();
-
-            if record.expires_at > current_time {
+            if record.expires_at > now {
                 Ok(CnsResolutionResult {
                     resolved: true,
                     address: Some(record.address),
@@ -322,13 +387,14 @@ impl CnsService {
         }
     }
 
-    /// Register a new domain (CNS_REGISTER opcode implementation)
+    /// Register a new domain at block time `now` (CNS_REGISTER opcode implementation)
     pub fn register_domain(
         &mut self,
         domain: &str,
         owner: [u8; 20],
         target_address: [u8; 20],
         ghost_id: Option<String>,
+        now: u64,
     ) -> Result<bool, RvmError> {
         // Validate domain
         if !GhostChainCryptoUtils::is_ghostchain_domain(domain) {
@@ -342,25 +408,20 @@ impl CnsService {
             ));
         }
 
-        let current_time = std::time::SystemTime::now()
-            .duration_since(std::time::UNIX_EPOCH)
-            .unwrap_or_default()
-            .as_secs();
-
         // Create domain record
         let record = DomainRecord {
             name: domain.to_string(),
             address: target_address,
             owner,
-            registered_at: current_time,
-            expires_at: current_time + 365 * 24 * 60 * 60, // 1 year
+            registered_at: now,
+            expires_at: now.saturating_add(365 * 24 * 60 * 60), // 1 year
             ghost_id,
             records: HashMap::new(),
         };
 
         // Register domain
-        self.domains.insert(domain.to_string(), record);
-        self.reverse_lookup.insert(target_address, domain.to_string());
+        self.set_domain(record);
+        self.set_reverse(target_address, Some(domain.to_string()));
 
         Ok(true)
     }
@@ -374,7 +435,8 @@ impl CnsService {
         new_records: Option<HashMap<String, String>>,
     ) -> Result<bool, RvmError> {
         // Get existing record
-        let record = self.domains.get_mut(domain)
+        let mut record = self.domains.get(domain)
+            .cloned()
             .ok_or_else(|| RvmError::DomainNotFound(domain.to_string()))?;
 
         // Check ownership
@@ -387,11 +449,11 @@ impl CnsService {
         // Update address if provided
         if let Some(new_addr) = new_address {
             // Remove old reverse lookup
-            self.reverse_lookup.remove(&record.address);
+            self.set_reverse(record.address, None);
             // Update record
             record.address = new_addr;
             // Add new reverse lookup
-            self.reverse_lookup.insert(new_addr, domain.to_string());
+            self.set_reverse(new_addr, Some(domain.to_string()));
         }
 
         // Update additional records if provided
@@ -399,6 +461,7 @@ impl CnsService {
             record.records.extend(records);
         }
 
+        self.set_domain(record);
         Ok(true)
     }
 
@@ -416,13 +479,54 @@ impl CnsService {
         self.reverse_lookup.get(address).cloned()
     }
 
-    /// Initialize test domains for development
-    fn initialize_test_domains(&mut self) {
-        let current_time = std::time::SystemTime::now()
-            .duration_since(std::time::UNIX_EPOCH)
-            .unwrap_or_default()
-            .as_secs();
+    /// Open a journal checkpoint
+    pub fn checkpoint(&self) -> usize {
+        self.journal.len()
+    }
 
+    /// Undo every change made since the checkpoint
+    pub fn revert_to(&mut self, checkpoint: usize) {
+        while self.journal.len() > checkpoint {
+            let Some(entry) = self.journal.pop() else { break };
+            match entry {
+                CnsJournalEntry::Domain { name, previous: Some(record) } => {
+                    self.domains.insert(name, record);
+                }
+                CnsJournalEntry::Domain { name, previous: None } => {
+                    self.domains.remove(&name);
+                }
+                CnsJournalEntry::Reverse { address, previous: Some(name) } => {
+                    self.reverse_lookup.insert(address, name);
+                }
+                CnsJournalEntry::Reverse { address, previous: None } => {
+                    self.reverse_lookup.remove(&address);
+                }
+            }
+        }
+    }
+
+    /// Make all changes permanent
+    pub fn commit(&mut self) {
+        self.journal.clear();
+    }
+
+    /// Insert or replace a domain record, recording the previous one
+    fn set_domain(&mut self, record: DomainRecord) {
+        let previous = self.domains.insert(record.name.clone(), record.clone());
+        self.journal.push(CnsJournalEntry::Domain { name: record.name, previous });
+    }
+
+    /// Set or clear a reverse lookup entry, recording the previous one
+    fn set_reverse(&mut self, address: [u8; 20], name: Option<String>) {
+        let previous = match name {
+            Some(name) => self.reverse_lookup.insert(address, name),
+            None => self.reverse_lookup.remove(&address),
+        };
+        self.journal.push(CnsJournalEntry::Reverse { address, previous });
+    }
+
+    /// Initialize test domains for development; they never expire
+    fn initialize_test_domains(&mut self) {
         // Test domains
         let test_domains = vec![
             ("test.ghost", [0x11; 20], [0x22; 20]),
@@ -436,8 +540,8 @@ impl CnsService {
                 name: domain.to_string(),
                 address,
                 owner,
-                registered_at: current_time,
-                expires_at: current_time + 365 * 24 * 60 * 60, // 1 year
+                registered_at: 0,
+                expires_at: u64::MAX,
                 ghost_id: None,
                 records: HashMap::new(),
             };
@@ -472,8 +576,9 @@ impl GhostChainServices {
         ghost_id: &str,
         message: &[u8],
         signature: &[u8],
+        now: u64,
     ) -> Result<bool, RvmError> {
-        let result = self.ghost_id.verify_ghost_id_signature(ghost_id, message, signature)?;
+        let result = self.ghost_id.verify_ghost_id_signature(ghost_id, message, signature, now)?;
         Ok(result.verified)
     }
 
@@ -493,8 +598,8 @@ impl GhostChainServices {
     }
 
     /// Execute CNS resolution (for CNS_RESOLVE opcode)
-    pub fn execute_cns_resolve(&self, domain: &str) -> Result<Option<[u8; 20]>, RvmError> {
-        let result = self.cns.resolve_domain(domain)?;
+    pub fn execute_cns_resolve(&self, domain: &str, now: u64) -> Result<Option<[u8; 20]>, RvmError> {
+        let result = self.cns.resolve_domain(domain, now)?;
         Ok(result.address)
     }
 
@@ -505,8 +610,9 @@ impl GhostChainServices {
         owner: [u8; 20],
         target_address: [u8; 20],
         ghost_id: Option<String>,
+        now: u64,
     ) -> Result<bool, RvmError> {
-        self.cns.register_domain(domain, owner, target_address, ghost_id)
+        self.cns.register_domain(domain, owner, target_address, ghost_id, now)
     }
 
     /// Execute CNS update (for CNS_UPDATE opcode)
@@ -524,6 +630,441 @@ impl GhostChainServices {
     pub fn execute_cns_owner(&self, domain: &str) -> Result<Option<[u8; 20]>, RvmError> {
         self.cns.get_domain_owner(domain)
     }
+
+    /// Make GhostID and CNS changes permanent, once the wrapped host's state is committed
+    pub fn commit(&mut self) {
+        self.ghost_id.commit();
+        self.cns.commit();
+    }
+}
+
+/// Host wrapper that exposes GhostChain services to the interpreter
+pub struct GhostChainHost<'a> {
+    /// Wrapped world state
+    inner: &'a mut dyn Host,
+    /// GhostChain services
+    services: &'a mut GhostChainServices,
+    /// Open checkpoints as (inner checkpoint, GhostID checkpoint, CNS checkpoint)
+    checkpoints: Vec<(usize, usize, usize)>,
+}
+
+impl<'a> GhostChainHost<'a> {
+    /// Wrap a host with GhostChain services
+    pub fn new(inner: &'a mut dyn Host, services: &'a mut GhostChainServices) -> Self {
+        Self { inner, services, checkpoints: Vec::new() }
+    }
+}
+
+impl Host for GhostChainHost<'_> {
+    fn balance(&self, address: &[u8; 20]) -> u64 {
+        self.inner.balance(address)
+    }
+
+    fn transfer(&mut self, from: [u8; 20], to: [u8; 20], amount: u64) -> Result<(), RvmError> {
+        self.inner.transfer(from, to, amount)
+    }
+
+    fn nonce(&self, address: &[u8; 20]) -> u64 {
+        self.inner.nonce(address)
+    }
+
+    fn increment_nonce(&mut self, address: [u8; 20]) {
+        self.inner.increment_nonce(address)
+    }
+
+    fn code(&self, address: &[u8; 20]) -> Option<Vec<u8>> {
+        self.inner.code(address)
+    }
+
+    fn set_code(&mut self, address: [u8; 20], code: Vec<u8>) {
+        self.inner.set_code(address, code)
+    }
+
+    fn sload(&self, address: &[u8; 20], key: u64) -> u64 {
+        self.inner.sload(address, key)
+    }
+
+    fn sstore(&mut self, address: [u8; 20], key: u64, value: u64) {
+        self.inner.sstore(address, key, value)
+    }
+
+    fn block_hash(&self, number: u64) -> [u8; 32] {
+        self.inner.block_hash(number)
+    }
+
+    fn log(&mut self, log: Log) {
+        self.inner.log(log)
+    }
+
+    fn wrapped(&self) -> Option<&dyn Host> {
+        Some(&*self.inner)
+    }
+
+    fn wrapped_mut(&mut self) -> Option<&mut dyn Host> {
+        Some(&mut *self.inner)
+    }
+
+    fn call(&mut self, inputs: &CallInputs) -> Option<ExecutionResult> {
+        self.inner.call(inputs)
+    }
+
+    fn checkpoint(&mut self) -> usize {
+        self.checkpoints.push((
+            self.inner.checkpoint(),
+            self.services.ghost_id.checkpoint(),
+            self.services.cns.checkpoint(),
+        ));
+        self.checkpoints.len() - 1
+    }
+
+    fn revert_to(&mut self, checkpoint: usize) {
+        if let Some(&(inner, ghost_id, cns)) = self.checkpoints.get(checkpoint) {
+            self.inner.revert_to(inner);
+            self.services.ghost_id.revert_to(ghost_id);
+            self.services.cns.revert_to(cns);
+            self.checkpoints.truncate(checkpoint);
+        }
+    }
+
+    fn ghostchain_services(&mut self) -> Option<&mut GhostChainServices> {
+        Some(self.services)
+    }
+
+    fn agent_host(&mut self) -> Option<&mut dyn AgentHost> {
+        self.inner.agent_host()
+    }
+}
+
+/// GHOST_ID_VERIFY handler
+pub struct GhostIdVerify;
+
+/// GHOST_ID_RESOLVE handler
+pub struct GhostIdResolve;
+
+/// GHOST_ID_CREATE handler
+pub struct GhostIdCreate;
+
+/// CNS_RESOLVE handler
+pub struct CnsResolve;
+
+/// CNS_REGISTER handler
+pub struct CnsRegister;
+
+/// CNS_UPDATE handler
+pub struct CnsUpdate;
+
+/// CNS_OWNER handler
+pub struct CnsOwner;
+
+/// TOKEN_BALANCE handler
+pub struct TokenBalance;
+
+/// TOKEN_TRANSFER handler
+pub struct TokenTransfer;
+
+/// TOKEN_MINT handler
+pub struct TokenMint;
+
+/// TOKEN_BURN handler
+pub struct TokenBurn;
+
+/// Event signature topic of token balance changes
+pub fn token_transfer_topic() -> [u8; 32] {
+    RvmCrypto::keccak256(b"TokenTransfer(uint8,address,address,uint256)")
+}
+
+impl GhostChainServices {
+    /// Bind the standard GhostChain opcode handlers
+    pub fn register_opcodes(registry: &mut OpcodeRegistry) -> Result<(), RvmError> {
+        registry.register(Opcode::GHOST_ID_VERIFY, GhostIdVerify)?;
+        registry.register(Opcode::GHOST_ID_RESOLVE, GhostIdResolve)?;
+        registry.register(Opcode::GHOST_ID_CREATE, GhostIdCreate)?;
+        registry.register(Opcode::CNS_RESOLVE, CnsResolve)?;
+        registry.register(Opcode::CNS_REGISTER, CnsRegister)?;
+        registry.register(Opcode::CNS_UPDATE, CnsUpdate)?;
+        registry.register(Opcode::CNS_OWNER, CnsOwner)?;
+        registry.register(Opcode::TOKEN_BALANCE, TokenBalance)?;
+        registry.register(Opcode::TOKEN_TRANSFER, TokenTransfer)?;
+        registry.register(Opcode::TOKEN_MINT, TokenMint)?;
+        registry.register(Opcode::TOKEN_BURN, TokenBurn)?;
+        Ok(())
+    }
+}
+
+/// Services of the host executing a GhostChain opcode
+fn services<'c>(context: &'c mut OpcodeContext<'_>) -> Result<&'c mut GhostChainServices, RvmError> {
+    context.host
+        .ghostchain_services()
+        .ok_or_else(|| RvmError::GhostChainError("host provides no GhostChain services".to_string()))
+}
+
+impl OpcodeHandler for GhostIdVerify {
+    fn stack_io(&self) -> (usize, usize) {
+        (6, 1)
+    }
+
+    /// Message hashing, per word
+    fn gas(&self, inputs: &[u64]) -> u64 {
+        inputs[3].div_ceil(32).saturating_mul(6)
+    }
+
+    fn execute(&self, context: &mut OpcodeContext<'_>) -> Result<Vec<u64>, RvmError> {
+        let [id_offset, id_size, message_offset, message_size, signature_offset, signature_size] =
+            context.inputs[..]
+        else {
+            return Err(RvmError::StackUnderflow);
+        };
+        let ghost_id = context.read_string(id_offset, id_size)?;
+        let message = context.read_memory(message_offset, message_size)?;
+        let signature = context.read_memory(signature_offset, signature_size)?;
+        let Some(ghost_id) = ghost_id else {
+            return Ok(vec![0]);
+        };
+        let now = context.env.timestamp;
+        let verified = services(context)?.execute_ghost_id_verify(&ghost_id, &message, &signature, now)?;
+        Ok(vec![verified as u64])
+    }
+}
+
+impl OpcodeHandler for GhostIdResolve {
+    fn stack_io(&self) -> (usize, usize) {
+        (2, 1)
+    }
+
+    fn execute(&self, context: &mut OpcodeContext<'_>) -> Result<Vec<u64>, RvmError> {
+        let [id_offset, id_size] = context.inputs[..] else {
+            return Err(RvmError::StackUnderflow);
+        };
+        let Some(ghost_id) = context.read_string(id_offset, id_size)? else {
+            return Ok(vec![0]);
+        };
+        match services(context)?.execute_ghost_id_resolve(&ghost_id) {
+            Ok(address) => Ok(vec![address.map(|address| address_to_word(&address)).unwrap_or(0)]),
+            Err(RvmError::InvalidGhostIdFormat(_)) => Ok(vec![0]),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+impl OpcodeHandler for GhostIdCreate {
+    fn stack_io(&self) -> (usize, usize) {
+        (3, 1)
+    }
+
+    fn execute(&self, context: &mut OpcodeContext<'_>) -> Result<Vec<u64>, RvmError> {
+        let [key_offset, key_size, output_offset] = context.inputs[..] else {
+            return Err(RvmError::StackUnderflow);
+        };
+        context.ensure_mutable()?;
+        let public_key = context.read_memory(key_offset, key_size)?;
+        let ghost_id = services(context)?.execute_ghost_id_create(&public_key, Vec::new(), HashMap::new())?;
+        context.write_memory(output_offset, ghost_id.as_bytes())?;
+        Ok(vec![ghost_id.len() as u64])
+    }
+}
+
+impl OpcodeHandler for CnsResolve {
+    fn stack_io(&self) -> (usize, usize) {
+        (2, 1)
+    }
+
+    fn execute(&self, context: &mut OpcodeContext<'_>) -> Result<Vec<u64>, RvmError> {
+        let [name_offset, name_size] = context.inputs[..] else {
+            return Err(RvmError::StackUnderflow);
+        };
+        let Some(domain) = context.read_string(name_offset, name_size)? else {
+            return Ok(vec![0]);
+        };
+        let now = context.env.timestamp;
+        let address = services(context)?.execute_cns_resolve(&domain, now)?;
+        Ok(vec![address.map(|address| address_to_word(&address)).unwrap_or(0)])
+    }
+}
+
+impl OpcodeHandler for CnsRegister {
+    fn stack_io(&self) -> (usize, usize) {
+        (3, 1)
+    }
+
+    fn execute(&self, context: &mut OpcodeContext<'_>) -> Result<Vec<u64>, RvmError> {
+        let [name_offset, name_size, target] = context.inputs[..] else {
+            return Err(RvmError::StackUnderflow);
+        };
+        context.ensure_mutable()?;
+        let Some(domain) = context.read_string(name_offset, name_size)? else {
+            return Ok(vec![0]);
+        };
+        let (owner, now) = (context.env.contract_address, context.env.timestamp);
+        let registered = services(context)?.execute_cns_register(&domain, owner, word_to_address(target), None, now);
+        domain_outcome(registered)
+    }
+}
+
+impl OpcodeHandler for CnsUpdate {
+    fn stack_io(&self) -> (usize, usize) {
+        (5, 1)
+    }
+
+    fn execute(&self, context: &mut OpcodeContext<'_>) -> Result<Vec<u64>, RvmError> {
+        let [name_offset, name_size, target, records_offset, records_size] = context.inputs[..] else {
+            return Err(RvmError::StackUnderflow);
+        };
+        context.ensure_mutable()?;
+        let domain = context.read_string(name_offset, name_size)?;
+        let records = context.read_memory(records_offset, records_size)?;
+        let records = if records.is_empty() {
+            None
+        } else {
+            match serde_json::from_slice::<HashMap<String, String>>(&records) {
+                Ok(records) => Some(records),
+                Err(_) => return Ok(vec![0]),
+            }
+        };
+        let Some(domain) = domain else {
+            return Ok(vec![0]);
+        };
+        let new_address = (target != 0).then(|| word_to_address(target));
+        let owner = context.env.contract_address;
+        let updated = services(context)?.execute_cns_update(&domain, owner, new_address, records);
+        domain_outcome(updated)
+    }
+}
+
+impl OpcodeHandler for CnsOwner {
+    fn stack_io(&self) -> (usize, usize) {
+        (2, 1)
+    }
+
+    fn execute(&self, context: &mut OpcodeContext<'_>) -> Result<Vec<u64>, RvmError> {
+        let [name_offset, name_size] = context.inputs[..] else {
+            return Err(RvmError::StackUnderflow);
+        };
+        let Some(domain) = context.read_string(name_offset, name_size)? else {
+            return Ok(vec![0]);
+        };
+        let owner = services(context)?.execute_cns_owner(&domain)?;
+        Ok(vec![owner.map(|owner| address_to_word(&owner)).unwrap_or(0)])
+    }
+}
+
+/// Result flag of a CNS write; rejected names and ownership failures push 0
+fn domain_outcome(result: Result<bool, RvmError>) -> Result<Vec<u64>, RvmError> {
+    match result {
+        Ok(success) => Ok(vec![success as u64]),
+        Err(
+            RvmError::InvalidDomainName(_)
+            | RvmError::DomainNotFound(_)
+            | RvmError::DomainRegistrationFailed(_)
+            | RvmError::UnauthorizedDomainOperation(_),
+        ) => Ok(vec![0]),
+        Err(e) => Err(e),
+    }
+}
+
+/// Token for a stack operand
+fn token(id: u64) -> Result<TokenType, RvmError> {
+    TokenType::from_id(id).ok_or_else(|| RvmError::InvalidTokenType(id.to_string()))
+}
+
+/// Address as a right-aligned log topic
+fn address_topic(address: &[u8; 20]) -> [u8; 32] {
+    let mut topic = [0u8; 32];
+    topic[12..].copy_from_slice(address);
+    topic
+}
+
+/// Log a successful balance change and push 1; ledger rejections push 0
+fn token_outcome(
+    context: &mut OpcodeContext<'_>,
+    result: Result<(), RvmError>,
+    token: TokenType,
+    from: [u8; 20],
+    to: [u8; 20],
+    amount: u64,
+) -> Result<Vec<u64>, RvmError> {
+    match result {
+        Ok(()) => {
+            let mut token_topic = [0u8; 32];
+            token_topic[24..].copy_from_slice(&token.id().to_be_bytes());
+            let mut data = vec![0u8; 24];
+            data.extend_from_slice(&amount.to_be_bytes());
+            let topics = vec![token_transfer_topic(), token_topic, address_topic(&from), address_topic(&to)];
+            context.log(topics, data)?;
+            Ok(vec![1])
+        }
+        Err(
+            RvmError::InsufficientTokenBalance { .. }
+            | RvmError::TokenTransferFailed(_)
+            | RvmError::TokenMintUnauthorized(_)
+            | RvmError::TokenBurnFailed(_),
+        ) => Ok(vec![0]),
+        Err(e) => Err(e),
+    }
+}
+
+impl OpcodeHandler for TokenBalance {
+    fn stack_io(&self) -> (usize, usize) {
+        (2, 1)
+    }
+
+    fn execute(&self, context: &mut OpcodeContext<'_>) -> Result<Vec<u64>, RvmError> {
+        let [token_id, account] = context.inputs[..] else {
+            return Err(RvmError::StackUnderflow);
+        };
+        let token = token(token_id)?;
+        Ok(vec![context.host.token_balance(&word_to_address(account), token)])
+    }
+}
+
+impl OpcodeHandler for TokenTransfer {
+    fn stack_io(&self) -> (usize, usize) {
+        (3, 1)
+    }
+
+    fn execute(&self, context: &mut OpcodeContext<'_>) -> Result<Vec<u64>, RvmError> {
+        let [token_id, recipient, amount] = context.inputs[..] else {
+            return Err(RvmError::StackUnderflow);
+        };
+        context.ensure_mutable()?;
+        let token = token(token_id)?;
+        let (from, to) = (context.env.contract_address, word_to_address(recipient));
+        let result = context.host.transfer_tokens(from, to, token, amount);
+        token_outcome(context, result, token, from, to, amount)
+    }
+}
+
+impl OpcodeHandler for TokenMint {
+    fn stack_io(&self) -> (usize, usize) {
+        (3, 1)
+    }
+
+    fn execute(&self, context: &mut OpcodeContext<'_>) -> Result<Vec<u64>, RvmError> {
+        let [token_id, recipient, amount] = context.inputs[..] else {
+            return Err(RvmError::StackUnderflow);
+        };
+        context.ensure_mutable()?;
+        let token = token(token_id)?;
+        let to = word_to_address(recipient);
+        let result = context.host.mint_tokens(context.env.contract_address, to, token, amount);
+        token_outcome(context, result, token, [0u8; 20], to, amount)
+    }
+}
+
+impl OpcodeHandler for TokenBurn {
+    fn stack_io(&self) -> (usize, usize) {
+        (3, 1)
+    }
+
+    fn execute(&self, context: &mut OpcodeContext<'_>) -> Result<Vec<u64>, RvmError> {
+        let [token_id, holder, amount] = context.inputs[..] else {
+            return Err(RvmError::StackUnderflow);
+        };
+        context.ensure_mutable()?;
+        let token = token(token_id)?;
+        let from = word_to_address(holder);
+        let result = context.host.burn_tokens(context.env.contract_address, from, token, amount);
+        token_outcome(context, result, token, from, [0u8; 20], amount)
+    }
 }
 
 impl Default for GhostChainServices {
@@ -531,6 +1072,7 @@ impl Default for GhostChainServices {
         Self::new().unwrap_or_else(|_| Self {
             ghost_id: GhostIdService {
                 cache: HashMap::new(),
+                journal: Vec::new(),
                 crypto: GhostChainCrypto::default(),
             },
             cns: CnsService::new(),
@@ -541,6 +1083,7 @@ impl Default for GhostChainServices {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::test_utils::{store_bytes, words};
 
     #[test]
     fn test_ghost_id_service() {
@@ -563,13 +1106,15 @@ mod tests {
     fn test_cns_service() {
         let mut service = CnsService::new();
 
+        let now = 1_700_000_000;
+
         // Test domain resolution (should find test domain)
-        let result = service.resolve_domain("test.ghost").unwrap();
+        let result = service.resolve_domain("test.ghost", now).unwrap();
         assert!(result.resolved);
         assert!(result.address.is_some());
 
         // Test non-existent domain
-        let result = service.resolve_domain("nonexistent.ghost").unwrap();
+        let result = service.resolve_domain("nonexistent.ghost", now).unwrap();
         assert!(!result.resolved);
 
         // Test domain registration
@@ -577,13 +1122,19 @@ mod tests {
         let owner = [0x99; 20];
         let target = [0xaa; 20];
 
-        let success = service.register_domain(new_domain, owner, target, None).unwrap();
+        let success = service.register_domain(new_domain, owner, target, None, now).unwrap();
         assert!(success);
 
         // Verify registration
-        let result = service.resolve_domain(new_domain).unwrap();
+        let result = service.resolve_domain(new_domain, now).unwrap();
         assert!(result.resolved);
         assert_eq!(result.address.unwrap(), target);
+        assert_eq!(result.record.unwrap().registered_at, now);
+
+        // Registrations expire a year after the block they were made in
+        let result = service.resolve_domain(new_domain, now + 365 * 24 * 60 * 60).unwrap();
+        assert!(!result.resolved);
+        assert_eq!(result.error.as_deref(), Some("Domain has expired"));
     }
 
     #[test]
@@ -591,7 +1142,7 @@ mod tests {
         let mut services = GhostChainServices::new().unwrap();
 
         // Test CNS operations
-        let resolved = services.execute_cns_resolve("test.ghost").unwrap();
+        let resolved = services.execute_cns_resolve("test.ghost", 0).unwrap();
         assert!(resolved.is_some());
 
         let owner = services.execute_cns_owner("test.ghost").unwrap();
@@ -620,4 +1171,353 @@ mod tests {
         assert!(GhostChainCryptoUtils::validate_ghost_id_format("1234567890abcdef1234567890abcdef"));
         assert!(!GhostChainCryptoUtils::validate_ghost_id_format("invalid"));
     }
-}
\ No newline at end of file
+
+    /// Public key and `GhostIdService` signature layout of a secp256k1 signature over "unlock"
+    fn unlock_signature() -> (Vec<u8>, Vec<u8>) {
+        use k256::ecdsa::SigningKey;
+
+        let signing_key = SigningKey::from_slice(&[7u8; 32]).unwrap();
+        let public_key = signing_key.verifying_key().to_encoded_point(false).as_bytes()[1..].to_vec();
+        let (signature, recovery_id) = signing_key
+            .sign_prehash_recoverable(&RvmCrypto::keccak256(b"unlock"))
+            .unwrap();
+        let mut signature_data = vec![1]; // secp256k1
+        signature_data.extend_from_slice(&signature.to_bytes());
+        signature_data.push(recovery_id.to_byte());
+        (public_key, signature_data)
+    }
+
+    #[tokio::test]
+    async fn test_ghost_id_opcodes() {
+        use crate::{
+            asm::assemble,
+            core::{bytes32_to_word, ExecutionEnvironment, RvmCore},
+            storage::Storage,
+        };
+        use std::sync::Arc;
+
+        let (public_key, signature_data) = unlock_signature();
+
+        // Key at 0, ID written to 64, message at 96, signature at 128; returns the
+        // verification flag and the resolved address
+        let program = |message: &[u8]| {
+            let source = format!(
+                "{}{}{}
+                 PUSH 64
+                 PUSH 64
+                 PUSH 0
+                 GHOST_ID_CREATE
+                 POP
+                 PUSH {}
+                 PUSH 128
+                 PUSH {}
+                 PUSH 96
+                 PUSH 32
+                 PUSH 64
+                 GHOST_ID_VERIFY
+                 PUSH 0
+                 MSTORE
+                 PUSH 32
+                 PUSH 64
+                 GHOST_ID_RESOLVE
+                 PUSH 32
+                 MSTORE
+                 PUSH 64
+                 PUSH 0
+                 RETURN",
+                store_bytes(0, &public_key),
+                store_bytes(96, message),
+                store_bytes(128, &signature_data),
+                signature_data.len(),
+                message.len(),
+            );
+            assemble(&source).unwrap()
+        };
+
+        let mut registry = OpcodeRegistry::new();
+        GhostChainServices::register_opcodes(&mut registry).unwrap();
+        let registry = Arc::new(registry);
+        let mut storage = Storage::new();
+        let mut services = GhostChainServices::new().unwrap();
+        let expected_address = address_to_word(&RvmCrypto::public_key_to_address(&public_key.clone().try_into().unwrap()));
+
+        for (message, verified) in [(&b"unlock"[..], 1), (&b"unlocK"[..], 0)] {
+            let mut host = GhostChainHost::new(&mut storage, &mut services);
+            let mut core = RvmCore::new(1_000_000).with_opcode_registry(Arc::clone(&registry));
+            let result = core.execute(&program(message), ExecutionEnvironment::default(), &mut host).await.unwrap();
+            assert!(result.success, "{:?}", result.error);
+            assert_eq!(bytes32_to_word(&result.return_data[..32].try_into().unwrap()), verified);
+            assert_eq!(bytes32_to_word(&result.return_data[32..].try_into().unwrap()), expected_address);
+        }
+
+        // The opcodes need a host with GhostChain services
+        let mut core = RvmCore::new(1_000_000).with_opcode_registry(registry);
+        let result = core.execute(&program(b"unlock"), ExecutionEnvironment::default(), &mut storage).await.unwrap();
+        assert!(result.error.unwrap().contains("no GhostChain services"));
+    }
+
+    #[tokio::test]
+    async fn test_ghost_id_creation_is_journaled() {
+        use crate::{
+            asm::assemble,
+            core::{bytes32_to_word, ExecutionEnvironment, RvmCore},
+            storage::Storage,
+        };
+        use std::sync::Arc;
+
+        let (public_key, signature_data) = unlock_signature();
+        let id = GhostChainCrypto::new().unwrap()
+            .create_ghost_id(
+                &crate::ghostchain_crypto::GhostPublicKey {
+                    key: public_key.clone(),
+                    algorithm: crate::ghostchain_crypto::CryptoAlgorithm::Ed25519,
+                },
+                Vec::new(),
+                HashMap::new(),
+            )
+            .unwrap()
+            .id;
+
+        // Create the GhostID of the key at 0 and revert
+        let create = assemble(&format!(
+            "{}
+             PUSH 64
+             PUSH {}
+             PUSH 0
+             GHOST_ID_CREATE
+             PUSH 0
+             PUSH 0
+             REVERT",
+            store_bytes(0, &public_key),
+            public_key.len(),
+        ))
+        .unwrap();
+        // Verify the signature against the ID and return the flag
+        let verify = assemble(&format!(
+            "{}{}{}
+             PUSH {}
+             PUSH 128
+             PUSH 6
+             PUSH 96
+             PUSH {}
+             PUSH 0
+             GHOST_ID_VERIFY
+             PUSH 0
+             MSTORE
+             PUSH 32
+             PUSH 0
+             RETURN",
+            store_bytes(0, id.as_bytes()),
+            store_bytes(96, b"unlock"),
+            store_bytes(128, &signature_data),
+            signature_data.len(),
+            id.len(),
+        ))
+        .unwrap();
+
+        let mut registry = OpcodeRegistry::new();
+        GhostChainServices::register_opcodes(&mut registry).unwrap();
+        let registry = Arc::new(registry);
+        let mut storage = Storage::new();
+        let mut services = GhostChainServices::new().unwrap();
+
+        for (code, succeeds) in [(&create, false), (&verify, true)] {
+            let mut host = GhostChainHost::new(&mut storage, &mut services);
+            let mut core = RvmCore::new(1_000_000).with_opcode_registry(Arc::clone(&registry));
+            let result = core.execute(code, ExecutionEnvironment::default(), &mut host).await.unwrap();
+            assert_eq!(result.success, succeeds, "{:?}", result.error);
+            if succeeds {
+                // The reverted GhostID is unknown again
+                assert_eq!(bytes32_to_word(&result.return_data.try_into().unwrap()), 0);
+            }
+        }
+        assert!(!services.ghost_id.cache.contains_key(&id));
+    }
+
+    #[tokio::test]
+    async fn test_cns_opcodes_are_journaled() {
+        use crate::{
+            asm::assemble,
+            core::{ExecutionEnvironment, RvmCore},
+            storage::Storage,
+        };
+        use std::sync::Arc;
+
+        let records = br#"{"url":"https://vault.ghost"}"#;
+        // Register vault.ghost -> 0x99.., update its records, try to update test.ghost,
+        // then return the flags, resolved address and owner; revert instead if asked
+        let program = |domain: &str, revert: bool| {
+            let source = format!(
+                "{}{}{}
+                 PUSH 0x9999999999999999
+                 PUSH {len}
+                 PUSH 256
+                 CNS_REGISTER
+                 PUSH 0
+                 MSTORE
+                 PUSH {len}
+                 PUSH 256
+                 CNS_RESOLVE
+                 PUSH 32
+                 MSTORE
+                 PUSH {records_len}
+                 PUSH 320
+                 PUSH 0
+                 PUSH {len}
+                 PUSH 256
+                 CNS_UPDATE
+                 PUSH 64
+                 MSTORE
+                 PUSH 0
+                 PUSH 0
+                 PUSH 0x1234
+                 PUSH 10
+                 PUSH 384
+                 CNS_UPDATE
+                 PUSH 96
+                 MSTORE
+                 PUSH {len}
+                 PUSH 256
+                 CNS_OWNER
+                 PUSH 128
+                 MSTORE
+                 PUSH 160
+                 PUSH 0
+                 {}",
+                store_bytes(256, domain.as_bytes()),
+                store_bytes(320, records),
+                store_bytes(384, b"test.ghost"),
+                if revert { "REVERT" } else { "RETURN" },
+                len = domain.len(),
+                records_len = records.len(),
+            );
+            assemble(&source).unwrap()
+        };
+
+        let mut registry = OpcodeRegistry::new();
+        GhostChainServices::register_opcodes(&mut registry).unwrap();
+        let registry = Arc::new(registry);
+        let mut storage = Storage::new();
+        let mut services = GhostChainServices::new().unwrap();
+        let env = ExecutionEnvironment { contract_address: [0xab; 20], ..Default::default() };
+
+        // A reverted registration leaves no trace
+        let result = {
+            let mut host = GhostChainHost::new(&mut storage, &mut services);
+            let mut core = RvmCore::new(1_000_000).with_opcode_registry(Arc::clone(&registry));
+            core.execute(&program("vault.ghost", true), env.clone(), &mut host).await.unwrap()
+        };
+        assert!(!result.success);
+        assert_eq!(services.execute_cns_resolve("vault.ghost", env.timestamp).unwrap(), None);
+        assert_eq!(services.execute_cns_owner("vault.ghost").unwrap(), None);
+
+        let result = {
+            let mut host = GhostChainHost::new(&mut storage, &mut services);
+            let mut core = RvmCore::new(1_000_000).with_opcode_registry(Arc::clone(&registry));
+            core.execute(&program("vault.ghost", false), env.clone(), &mut host).await.unwrap()
+        };
+        assert!(result.success, "{:?}", result.error);
+        assert_eq!(words(&result.return_data), vec![1, 0x9999999999999999, 1, 0, address_to_word(&[0xab; 20])]);
+
+        let record = services.cns.domains.get("vault.ghost").unwrap();
+        assert_eq!(record.owner, [0xab; 20]);
+        assert_eq!(record.registered_at, env.timestamp);
+        assert_eq!(record.records.get("url").map(String::as_str), Some("https://vault.ghost"));
+        assert_eq!(services.cns.reverse_lookup(&record.address).as_deref(), Some("vault.ghost"));
+    }
+
+    #[tokio::test]
+    async fn test_token_opcodes() {
+        use crate::{
+            asm::assemble,
+            core::{bytes32_to_word, word_to_address, ExecutionEnvironment, RvmCore},
+            storage::Storage,
+        };
+        use std::sync::Arc;
+
+        // Mint 500 MANA to the contract, send 200 to 0x42, burn 100 of 0x42's, try to
+        // overspend, then return the four flags and 0x42's balance
+        let code = assemble(
+            "PUSH 500
+             ADDRESS
+             PUSH 2
+             TOKEN_MINT
+             PUSH 0
+             MSTORE
+             PUSH 200
+             PUSH 0x42
+             PUSH 2
+             TOKEN_TRANSFER
+             PUSH 32
+             MSTORE
+             PUSH 100
+             PUSH 0x42
+             PUSH 2
+             TOKEN_BURN
+             PUSH 64
+             MSTORE
+             PUSH 1000
+             PUSH 0x42
+             PUSH 2
+             TOKEN_TRANSFER
+             PUSH 96
+             MSTORE
+             PUSH 0x42
+             PUSH 2
+             TOKEN_BALANCE
+             PUSH 128
+             MSTORE
+             PUSH 160
+             PUSH 0
+             RETURN",
+        )
+        .unwrap();
+        let mut registry = OpcodeRegistry::new();
+        GhostChainServices::register_opcodes(&mut registry).unwrap();
+        let registry = Arc::new(registry);
+        let contract = word_to_address(0xab);
+        let recipient = word_to_address(0x42);
+        let env = ExecutionEnvironment { contract_address: contract, ..Default::default() };
+
+        // Only authorized minters can mint and burn, and burning 0x42's tokens takes its allowance
+        let mut storage = Storage::new();
+        for (authorized, allowance, expected) in [
+            (false, 100, vec![0, 0, 0, 0, 0]),
+            (true, 0, vec![1, 1, 0, 0, 200]),
+            (true, 100, vec![1, 1, 1, 0, 100]),
+        ] {
+            storage = Storage::new();
+            storage.set_token_minter(contract, authorized);
+            storage.approve_tokens(recipient, contract, TokenType::MANA, allowance);
+            let mut core = RvmCore::new(1_000_000).with_opcode_registry(Arc::clone(&registry));
+            let result = core.execute(&code, env.clone(), &mut storage).await.unwrap();
+            assert!(result.success, "{:?}", result.error);
+            assert_eq!(words(&result.return_data), expected);
+            if !authorized {
+                assert!(storage.take_logs().is_empty());
+            }
+        }
+        assert_eq!(storage.get_token_balance(&contract, TokenType::MANA), 300);
+        assert_eq!(storage.get_token_balance(&recipient, TokenType::MANA), 100);
+        assert_eq!(storage.get_token_balance(&recipient, TokenType::GCC), 0);
+        assert_eq!(storage.token_allowance(&recipient, &contract, TokenType::MANA), 0);
+
+        let logs = storage.take_logs();
+        assert_eq!(logs.len(), 3);
+        let transfer = &logs[1];
+        assert_eq!(transfer.address, contract);
+        assert_eq!(transfer.topics[0], token_transfer_topic());
+        assert_eq!(bytes32_to_word(&transfer.topics[1]), 2);
+        assert_eq!(transfer.topics[2], address_topic(&contract));
+        assert_eq!(transfer.topics[3], address_topic(&recipient));
+        assert_eq!(transfer.data[24..], 200u64.to_be_bytes());
+        assert_eq!(logs[0].topics[2], [0u8; 32]);
+        assert_eq!(logs[2].topics[3], [0u8; 32]);
+
+        // Unknown token ids halt execution
+        let code = assemble("PUSH 0\nPUSH 4\nTOKEN_BALANCE\nSTOP").unwrap();
+        let mut core = RvmCore::new(100_000).with_opcode_registry(registry);
+        let result = core.execute(&code, ExecutionEnvironment::default(), &mut storage).await.unwrap();
+        assert_eq!(result.error, Some(RvmError::InvalidTokenType("4".to_string()).to_string()));
+    }
+}
@@ -0,0 +1,209 @@
+//! Host Interface
+//!
+//! World-state access for the interpreter. `RvmCore` never owns state; it queries a
+//! `Host` for balances, code, storage slots, block hashes, logs and calls, so a single
+//! execution works directly against the caller's state instead of a copy of it.
+//!
+//! The GhostChain token ledger, L2 inbox and bridge methods are optional: by default they
+//! go to the host returned by `wrapped`, so wrapper hosts need not forward them, and fail
+//! (or report nothing) on a host that wraps none and does not implement them.
+
+use crate::{
+    agents::AgentHost,
+    bridge::{BridgeMessage, SignedBridgeMessage},
+    core::ExecutionResult,
+    error::RvmError,
+    ghostchain_gas::TokenType,
+    ghostchain_services::GhostChainServices,
+    l2::{L2Checkpoint, L2Transaction},
+};
+use serde::{Deserialize, Serialize};
+
+/// Log entry emitted by the LOG0..LOG4 opcodes
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Log {
+    /// Contract address that emitted the log
+    pub address: [u8; 20],
+    /// Log topics
+    pub topics: Vec<[u8; 32]>,
+    /// Log data
+    pub data: Vec<u8>,
+}
+
+/// Kind of message call
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CallKind {
+    /// CALL
+    Call,
+    /// STATICCALL
+    StaticCall,
+    /// CREATE
+    Create,
+}
+
+/// Inputs of a message call or contract creation
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CallInputs {
+    /// Call kind
+    pub kind: CallKind,
+    /// Calling address
+    pub caller: [u8; 20],
+    /// Callee address (the new contract address for CREATE)
+    pub target: [u8; 20],
+    /// Value transferred with the call
+    pub value: u64,
+    /// Call data, or init code for CREATE
+    pub input: Vec<u8>,
+    /// Gas forwarded to the callee
+    pub gas_limit: u64,
+    /// Call depth of the callee
+    pub depth: usize,
+}
+
+/// Interface between the interpreter and world state
+pub trait Host {
+    /// Get account balance
+    fn balance(&self, address: &[u8; 20]) -> u64;
+
+    /// Transfer value between accounts
+    fn transfer(&mut self, from: [u8; 20], to: [u8; 20], amount: u64) -> Result<(), RvmError>;
+
+    /// Get account nonce
+    fn nonce(&self, address: &[u8; 20]) -> u64;
+
+    /// Increment account nonce
+    fn increment_nonce(&mut self, address: [u8; 20]);
+
+    /// Get contract code, if any
+    fn code(&self, address: &[u8; 20]) -> Option<Vec<u8>>;
+
+    /// Install contract code at an address
+    fn set_code(&mut self, address: [u8; 20], code: Vec<u8>);
+
+    /// Read a storage slot
+    fn sload(&self, address: &[u8; 20], key: u64) -> u64;
+
+    /// Write a storage slot
+    fn sstore(&mut self, address: [u8; 20], key: u64, value: u64);
+
+    /// Read a storage slot as it was when the transaction started, for SSTORE pricing.
+    /// Hosts that keep no original values report the current one.
+    fn sload_original(&self, address: &[u8; 20], key: u64) -> u64 {
+        self.wrapped()
+            .map_or_else(|| self.sload(address, key), |host| host.sload_original(address, key))
+    }
+
+    /// Get the hash of a past block (zero if unknown)
+    fn block_hash(&self, number: u64) -> [u8; 32];
+
+    /// Record a log entry
+    fn log(&mut self, log: Log);
+
+    /// Host this one wraps, which the token, L2 and bridge methods default to
+    fn wrapped(&self) -> Option<&dyn Host> {
+        None
+    }
+
+    /// Mutable access to the wrapped host
+    fn wrapped_mut(&mut self) -> Option<&mut dyn Host> {
+        None
+    }
+
+    /// Get a GhostChain token balance
+    fn token_balance(&self, address: &[u8; 20], token: TokenType) -> u64 {
+        self.wrapped().map_or(0, |host| host.token_balance(address, token))
+    }
+
+    /// Move tokens between accounts
+    fn transfer_tokens(&mut self, from: [u8; 20], to: [u8; 20], token: TokenType, amount: u64) -> Result<(), RvmError> {
+        unsupported(self.wrapped_mut(), "token ledger")?.transfer_tokens(from, to, token, amount)
+    }
+
+    /// Create tokens on behalf of an authorized minter
+    fn mint_tokens(&mut self, minter: [u8; 20], to: [u8; 20], token: TokenType, amount: u64) -> Result<(), RvmError> {
+        unsupported(self.wrapped_mut(), "token ledger")?.mint_tokens(minter, to, token, amount)
+    }
+
+    /// Create tokens as protocol issuance, such as execution rewards, which needs no minter
+    fn issue_tokens(&mut self, to: [u8; 20], token: TokenType, amount: u64) -> Result<(), RvmError> {
+        unsupported(self.wrapped_mut(), "token ledger")?.issue_tokens(to, token, amount)
+    }
+
+    /// Destroy tokens on behalf of an authorized minter, with the holder's consent
+    fn burn_tokens(&mut self, burner: [u8; 20], from: [u8; 20], token: TokenType, amount: u64) -> Result<(), RvmError> {
+        unsupported(self.wrapped_mut(), "token ledger")?.burn_tokens(burner, from, token, amount)
+    }
+
+    /// Append a transaction to a rollup's L2 inbox, returning its index
+    fn l2_submit(&mut self, rollup: [u8; 20], data: Vec<u8>, l1_block: u64) -> Result<u64, RvmError> {
+        unsupported(self.wrapped_mut(), "L2 inbox")?.l2_submit(rollup, data, l1_block)
+    }
+
+    /// Get a transaction of a rollup's L2 inbox
+    fn l2_transaction(&self, rollup: [u8; 20], index: u64) -> Option<L2Transaction> {
+        self.wrapped()?.l2_transaction(rollup, index)
+    }
+
+    /// Record that an inbox transaction is included in a posted batch of the rollup
+    fn l2_include(&mut self, rollup: [u8; 20], index: u64, batch_root: [u8; 32]) -> Result<(), RvmError> {
+        unsupported(self.wrapped_mut(), "L2 inbox")?.l2_include(rollup, index, batch_root)
+    }
+
+    /// Record an L2 state root checkpoint of a rollup
+    fn l2_sync_state(&mut self, rollup: [u8; 20], checkpoint: L2Checkpoint) -> Result<(), RvmError> {
+        unsupported(self.wrapped_mut(), "L2 inbox")?.l2_sync_state(rollup, checkpoint)
+    }
+
+    /// Nonce of the next message sent over the bridge
+    fn bridge_nonce(&self) -> u64 {
+        self.wrapped().map_or(0, |host| host.bridge_nonce())
+    }
+
+    /// Append a message to the bridge outbox
+    fn bridge_send(&mut self, message: BridgeMessage) -> Result<(), RvmError> {
+        unsupported(self.wrapped_mut(), "bridge")?.bridge_send(message)
+    }
+
+    /// Get a bridge inbox message
+    fn bridge_message(&self, index: u64) -> Option<SignedBridgeMessage> {
+        self.wrapped()?.bridge_message(index)
+    }
+
+    /// Whether a relayer key is trusted for messages from a chain
+    fn is_bridge_signer(&self, chain_id: u64, public_key: &[u8]) -> bool {
+        self.wrapped().is_some_and(|host| host.is_bridge_signer(chain_id, public_key))
+    }
+
+    /// Mark a message id as consumed; false if it already was or cannot be recorded
+    fn bridge_consume(&mut self, id: [u8; 32]) -> bool {
+        self.wrapped_mut().is_some_and(|host| host.bridge_consume(id))
+    }
+
+    /// Service a call natively (precompiles, system contracts).
+    ///
+    /// Returning `None` lets the interpreter execute the callee's code.
+    fn call(&mut self, _inputs: &CallInputs) -> Option<ExecutionResult> {
+        None
+    }
+
+    /// Open a journal checkpoint
+    fn checkpoint(&mut self) -> usize;
+
+    /// Undo every change made since the checkpoint
+    fn revert_to(&mut self, checkpoint: usize);
+
+    /// GhostChain services available to this host, if any
+    fn ghostchain_services(&mut self) -> Option<&mut GhostChainServices> {
+        None
+    }
+
+    /// Agents available to this host, if any
+    fn agent_host(&mut self) -> Option<&mut dyn AgentHost> {
+        None
+    }
+}
+
+/// The wrapped host, or an error naming the feature the host lacks
+fn unsupported<'h>(host: Option<&'h mut dyn Host>, feature: &str) -> Result<&'h mut dyn Host, RvmError> {
+    host.ok_or_else(|| RvmError::GhostChainError(format!("host provides no {}", feature)))
+}
@@ -0,0 +1,299 @@
+//! Execution Inspector
+//!
+//! Observation hooks for the interpreter: every step, call and create entry and exit,
+//! log and storage write. Inspectors are static type parameters, so running with the
+//! default `NoopInspector` compiles the hooks away entirely.
+
+use crate::{
+    core::ExecutionResult,
+    host::{CallInputs, Log},
+    opcodes::DecodedOpcode,
+};
+
+/// Interpreter state at an instruction boundary
+#[derive(Debug, Clone, Copy)]
+pub struct Step<'a> {
+    /// Byte offset of the instruction
+    pub pc: usize,
+    /// Instruction opcode
+    pub opcode: DecodedOpcode,
+    /// Operand stack, top last
+    pub stack: &'a [u64],
+    /// Frame memory
+    pub memory: &'a [u8],
+    /// Gas remaining in the frame
+    pub gas_remaining: u64,
+    /// Gas refund counter of the frame
+    pub gas_refunded: u64,
+    /// Call depth of the frame (0 for the outermost frame)
+    pub depth: usize,
+    /// Address whose code is executing
+    pub address: [u8; 20],
+}
+
+/// Execution observer. All hooks default to doing nothing.
+pub trait Inspector {
+    /// Whether the step hooks observe anything. When false the interpreter skips its
+    /// per-instruction step bookkeeping.
+    fn observes_steps(&self) -> bool {
+        true
+    }
+
+    /// Called before an instruction executes
+    fn step(&mut self, _step: &Step<'_>) {}
+
+    /// Called after an instruction executed (or failed), with the state it left behind
+    /// and the gas it cost
+    fn step_end(&mut self, _step: &Step<'_>, _gas_cost: u64) {}
+
+    /// Called when a CALL or STATICCALL frame is entered
+    fn call(&mut self, _inputs: &CallInputs) {}
+
+    /// Called when a CALL or STATICCALL frame exits
+    fn call_end(&mut self, _inputs: &CallInputs, _result: &ExecutionResult) {}
+
+    /// Called when a CREATE frame is entered
+    fn create(&mut self, _inputs: &CallInputs) {}
+
+    /// Called when a CREATE frame exits; `return_data` holds the deployed code on success
+    fn create_end(&mut self, _inputs: &CallInputs, _result: &ExecutionResult) {}
+
+    /// Called when a log is emitted
+    fn log(&mut self, _log: &Log) {}
+
+    /// Called when a storage slot is written
+    fn storage_write(&mut self, _address: &[u8; 20], _key: u64, _value: u64) {}
+}
+
+/// Inspector that observes nothing
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NoopInspector;
+
+impl Inspector for NoopInspector {
+    fn observes_steps(&self) -> bool {
+        false
+    }
+}
+
+impl<I: Inspector + ?Sized> Inspector for &mut I {
+    fn observes_steps(&self) -> bool {
+        (**self).observes_steps()
+    }
+
+    fn step(&mut self, step: &Step<'_>) {
+        (**self).step(step)
+    }
+
+    fn step_end(&mut self, step: &Step<'_>, gas_cost: u64) {
+        (**self).step_end(step, gas_cost)
+    }
+
+    fn call(&mut self, inputs: &CallInputs) {
+        (**self).call(inputs)
+    }
+
+    fn call_end(&mut self, inputs: &CallInputs, result: &ExecutionResult) {
+        (**self).call_end(inputs, result)
+    }
+
+    fn create(&mut self, inputs: &CallInputs) {
+        (**self).create(inputs)
+    }
+
+    fn create_end(&mut self, inputs: &CallInputs, result: &ExecutionResult) {
+        (**self).create_end(inputs, result)
+    }
+
+    fn log(&mut self, log: &Log) {
+        (**self).log(log)
+    }
+
+    fn storage_write(&mut self, address: &[u8; 20], key: u64, value: u64) {
+        (**self).storage_write(address, key, value)
+    }
+}
+
+/// Optionally enabled inspector
+impl<I: Inspector> Inspector for Option<I> {
+    fn observes_steps(&self) -> bool {
+        self.as_ref().is_some_and(I::observes_steps)
+    }
+
+    fn step(&mut self, step: &Step<'_>) {
+        if let Some(inspector) = self {
+            inspector.step(step);
+        }
+    }
+
+    fn step_end(&mut self, step: &Step<'_>, gas_cost: u64) {
+        if let Some(inspector) = self {
+            inspector.step_end(step, gas_cost);
+        }
+    }
+
+    fn call(&mut self, inputs: &CallInputs) {
+        if let Some(inspector) = self {
+            inspector.call(inputs);
+        }
+    }
+
+    fn call_end(&mut self, inputs: &CallInputs, result: &ExecutionResult) {
+        if let Some(inspector) = self {
+            inspector.call_end(inputs, result);
+        }
+    }
+
+    fn create(&mut self, inputs: &CallInputs) {
+        if let Some(inspector) = self {
+            inspector.create(inputs);
+        }
+    }
+
+    fn create_end(&mut self, inputs: &CallInputs, result: &ExecutionResult) {
+        if let Some(inspector) = self {
+            inspector.create_end(inputs, result);
+        }
+    }
+
+    fn log(&mut self, log: &Log) {
+        if let Some(inspector) = self {
+            inspector.log(log);
+        }
+    }
+
+    fn storage_write(&mut self, address: &[u8; 20], key: u64, value: u64) {
+        if let Some(inspector) = self {
+            inspector.storage_write(address, key, value);
+        }
+    }
+}
+
+/// Run two inspectors side by side
+impl<A: Inspector, B: Inspector> Inspector for (A, B) {
+    fn observes_steps(&self) -> bool {
+        self.0.observes_steps() || self.1.observes_steps()
+    }
+
+    fn step(&mut self, step: &Step<'_>) {
+        self.0.step(step);
+        self.1.step(step);
+    }
+
+    fn step_end(&mut self, step: &Step<'_>, gas_cost: u64) {
+        self.0.step_end(step, gas_cost);
+        self.1.step_end(step, gas_cost);
+    }
+
+    fn call(&mut self, inputs: &CallInputs) {
+        self.0.call(inputs);
+        self.1.call(inputs);
+    }
+
+    fn call_end(&mut self, inputs: &CallInputs, result: &ExecutionResult) {
+        self.0.call_end(inputs, result);
+        self.1.call_end(inputs, result);
+    }
+
+    fn create(&mut self, inputs: &CallInputs) {
+        self.0.create(inputs);
+        self.1.create(inputs);
+    }
+
+    fn create_end(&mut self, inputs: &CallInputs, result: &ExecutionResult) {
+        self.0.create_end(inputs, result);
+        self.1.create_end(inputs, result);
+    }
+
+    fn log(&mut self, log: &Log) {
+        self.0.log(log);
+        self.1.log(log);
+    }
+
+    fn storage_write(&mut self, address: &[u8; 20], key: u64, value: u64) {
+        self.0.storage_write(address, key, value);
+        self.1.storage_write(address, key, value);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        core::{word_to_address, ExecutionEnvironment, RvmCore},
+        host::Host,
+        storage::Storage,
+    };
+
+    #[derive(Default)]
+    struct Counter {
+        steps: usize,
+        step_ends: usize,
+        top_level_gas: u64,
+        calls: Vec<(usize, bool)>,
+        logs: usize,
+        storage_writes: Vec<(u64, u64)>,
+    }
+
+    impl Inspector for Counter {
+        fn step(&mut self, _step: &Step<'_>) {
+            self.steps += 1;
+        }
+
+        fn step_end(&mut self, step: &Step<'_>, gas_cost: u64) {
+            self.step_ends += 1;
+            if step.depth == 0 {
+                self.top_level_gas += gas_cost;
+            }
+        }
+
+        fn call_end(&mut self, inputs: &CallInputs, result: &ExecutionResult) {
+            self.calls.push((inputs.depth, result.success));
+        }
+
+        fn log(&mut self, _log: &Log) {
+            self.logs += 1;
+        }
+
+        fn storage_write(&mut self, _address: &[u8; 20], key: u64, value: u64) {
+            self.storage_writes.push((key, value));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_inspector_observes_execution() {
+        let mut storage = Storage::new();
+        // Callee: SSTORE(1, 7), STOP
+        storage.set_code(word_to_address(0x42), vec![0x60, 0x07, 0x60, 0x01, 0x55, 0x00]);
+
+        // Caller: LOG0(0, 0), CALL(gas=50000, to=0x42, value=0, in=0/0, out=0/0), STOP
+        let code = vec![
+            0x60, 0x00, 0x60, 0x00, 0xa0,
+            0x60, 0x00, 0x60, 0x00, 0x60, 0x00, 0x60, 0x00, 0x60, 0x00,
+            0x60, 0x42, 0x61, 0xc3, 0x50, 0xf1, 0x00,
+        ];
+
+        let mut counter = Counter::default();
+        let mut core = RvmCore::new(100_000);
+        let result = core
+            .execute_with_inspector(&code, ExecutionEnvironment::default(), &mut storage, &mut counter)
+            .await
+            .unwrap();
+
+        assert!(result.success);
+        // 12 caller instructions + 4 callee instructions
+        assert_eq!(counter.steps, 16);
+        assert_eq!(counter.step_ends, 16);
+        assert_eq!(counter.top_level_gas, result.gas_used);
+        assert_eq!(counter.calls, vec![(1, true)]);
+        assert_eq!(counter.logs, 1);
+        assert_eq!(counter.storage_writes, vec![(1, 7)]);
+    }
+
+    #[test]
+    fn test_disabled_inspectors_skip_steps() {
+        assert!(!(None::<Counter>, None::<Counter>).observes_steps());
+        assert!(!(&mut None::<Counter>, NoopInspector).observes_steps());
+        assert!((None::<Counter>, Some(Counter::default())).observes_steps());
+        assert!(!Some(NoopInspector).observes_steps());
+    }
+}
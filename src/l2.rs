@@ -0,0 +1,498 @@
+//! L2 Rollup Opcodes
+//!
+//! L1 side of local rollups. Each rollup belongs to an L1 contract and the opcodes work on
+//! the rollup of the executing contract: they post transactions to its inbox, prove that
+//! inbox transactions were included in a sequencer batch, and checkpoint the L2 state root
+//! a verified batch produced. Operands, first on top of the stack:
+//!
+//! | Opcode            | Operands                                                          | Result        |
+//! |-------------------|-------------------------------------------------------------------|---------------|
+//! | `L2_SUBMIT`       | data (`offset, size`)                                             | inbox index   |
+//! | `L2_BATCH_VERIFY` | batch root offset, inbox index, position, proof (`offset, size`)  | 1 if included |
+//! | `L2_STATE_SYNC`   | batch root offset, state root offset, L2 block number             | 1 if recorded |
+//!
+//! Roots are 32 bytes in memory and proofs are concatenated 32-byte sibling hashes, as
+//! produced by `L2Batch::proof`. A batch is a Merkle tree over the hashes of its
+//! transactions, padded with zero hashes to a power of two, with leaf and node hashes
+//! domain-separated so a node can never pass for a transaction. Only batch roots posted with
+//! `Storage::post_l2_batch` by the rollup's sequencer or its contract can be verified
+//! against. A state root can only be checkpointed for a batch with a verified transaction,
+//! and L2 block numbers must increase within a rollup.
+
+use crate::{
+    crypto::RvmCrypto,
+    custom_opcodes::{OpcodeContext, OpcodeHandler, OpcodeRegistry},
+    error::RvmError,
+    opcodes::Opcode,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+
+/// Gas per byte of data posted to the L2 inbox
+pub const L2_DATA_BYTE_GAS: u64 = 16;
+
+/// Gas per sibling hash of an inclusion proof
+pub const L2_PROOF_HASH_GAS: u64 = 100;
+
+/// Domain prefix of batch leaf hashes
+const LEAF_PREFIX: u8 = 0;
+
+/// Domain prefix of batch node hashes
+const NODE_PREFIX: u8 = 1;
+
+/// Transaction posted to the L2 inbox
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct L2Transaction {
+    /// Position in the inbox
+    pub index: u64,
+    /// Rollup contract that submitted the transaction
+    pub sender: [u8; 20],
+    /// Transaction payload
+    pub data: Vec<u8>,
+    /// L1 block of the submission
+    pub l1_block: u64,
+}
+
+impl L2Transaction {
+    /// Leaf committed to by batches
+    pub fn hash(&self) -> [u8; 32] {
+        let mut preimage = vec![LEAF_PREFIX];
+        preimage.extend_from_slice(&self.index.to_be_bytes());
+        preimage.extend_from_slice(&self.sender);
+        preimage.extend_from_slice(&self.data);
+        RvmCrypto::keccak256(&preimage)
+    }
+}
+
+/// L2 state root recorded on L1
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct L2Checkpoint {
+    /// L2 block the state root belongs to
+    pub l2_block: u64,
+    /// L2 state root
+    pub state_root: [u8; 32],
+    /// Batch that produced the state
+    pub batch_root: [u8; 32],
+    /// L1 block of the checkpoint
+    pub l1_block: u64,
+}
+
+/// State of one rollup kept by the L1 host
+#[derive(Debug, Clone, Default)]
+pub struct L2State {
+    /// Account allowed to post batch roots besides the rollup contract
+    pub(crate) sequencer: Option<[u8; 20]>,
+    /// Submitted transactions
+    pub(crate) inbox: Vec<L2Transaction>,
+    /// Posted batch roots
+    pub(crate) batches: HashSet<[u8; 32]>,
+    /// Batch root each verified inbox transaction was included in
+    pub(crate) included: HashMap<u64, [u8; 32]>,
+    /// Batches with at least one verified transaction
+    pub(crate) verified: HashSet<[u8; 32]>,
+    /// Recorded state roots, oldest first
+    pub(crate) checkpoints: Vec<L2Checkpoint>,
+}
+
+impl L2State {
+    /// Whether a batch has at least one verified transaction
+    pub fn is_verified_batch(&self, batch_root: &[u8; 32]) -> bool {
+        self.verified.contains(batch_root)
+    }
+
+    /// Check that an inbox transaction may be recorded as included in a batch
+    pub fn check_inclusion(&self, index: u64, batch_root: &[u8; 32]) -> Result<(), RvmError> {
+        if !self.batches.contains(batch_root) {
+            return Err(RvmError::L2BatchVerificationFailed(format!(
+                "batch {} was not posted",
+                hex::encode(batch_root)
+            )));
+        }
+        match self.included.get(&index) {
+            Some(root) if root != batch_root => Err(RvmError::L2BatchVerificationFailed(format!(
+                "transaction {} is already included in batch {}",
+                index,
+                hex::encode(root)
+            ))),
+            _ => Ok(()),
+        }
+    }
+
+    /// Check that a checkpoint may be recorded
+    pub fn check_checkpoint(&self, checkpoint: &L2Checkpoint) -> Result<(), RvmError> {
+        if !self.is_verified_batch(&checkpoint.batch_root) {
+            return Err(RvmError::L2StateSyncFailed(format!(
+                "batch {} has no verified transactions",
+                hex::encode(checkpoint.batch_root)
+            )));
+        }
+        if let Some(latest) = self.checkpoints.last() {
+            if checkpoint.l2_block <= latest.l2_block {
+                return Err(RvmError::L2StateSyncFailed(format!(
+                    "L2 block {} is not after checkpointed block {}",
+                    checkpoint.l2_block, latest.l2_block
+                )));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Sequencer batch over inbox transactions
+#[derive(Debug, Clone)]
+pub struct L2Batch {
+    /// Transaction hashes padded with zero hashes to a power of two
+    leaves: Vec<[u8; 32]>,
+}
+
+impl L2Batch {
+    /// Batch over transactions, in order
+    pub fn new(transactions: &[L2Transaction]) -> Self {
+        let mut leaves: Vec<[u8; 32]> = transactions.iter().map(L2Transaction::hash).collect();
+        if !leaves.is_empty() {
+            leaves.resize(leaves.len().next_power_of_two(), [0u8; 32]);
+        }
+        Self { leaves }
+    }
+
+    /// Merkle commitment of the batch
+    pub fn root(&self) -> [u8; 32] {
+        let mut level = self.leaves.clone();
+        while level.len() > 1 {
+            level = level.chunks(2).map(|pair| node_hash(&pair[0], &pair[1])).collect();
+        }
+        level.first().copied().unwrap_or([0u8; 32])
+    }
+
+    /// Sibling hashes proving the transaction at `position`, leaf level first
+    pub fn proof(&self, position: usize) -> Option<Vec<[u8; 32]>> {
+        if position >= self.leaves.len() {
+            return None;
+        }
+        let mut proof = Vec::new();
+        let mut level = self.leaves.clone();
+        let mut index = position;
+        while level.len() > 1 {
+            proof.push(level[index ^ 1]);
+            level = level.chunks(2).map(|pair| node_hash(&pair[0], &pair[1])).collect();
+            index /= 2;
+        }
+        Some(proof)
+    }
+}
+
+/// Hash of two sibling nodes of a batch tree
+fn node_hash(left: &[u8; 32], right: &[u8; 32]) -> [u8; 32] {
+    let mut preimage = vec![NODE_PREFIX];
+    preimage.extend_from_slice(left);
+    preimage.extend_from_slice(right);
+    RvmCrypto::keccak256(&preimage)
+}
+
+/// Check that `leaf` is at `position` of the batch with `root`, given its sibling hashes
+pub fn verify_inclusion(leaf: &[u8; 32], proof: &[[u8; 32]], root: &[u8; 32], position: u64) -> bool {
+    if proof.len() < 64 && position >> proof.len() != 0 {
+        return false;
+    }
+    let computed = proof.iter().enumerate().fold(*leaf, |hash, (level, sibling)| {
+        if (position >> level) & 1 == 0 {
+            node_hash(&hash, sibling)
+        } else {
+            node_hash(sibling, &hash)
+        }
+    });
+    computed == *root
+}
+
+/// L2_SUBMIT handler
+pub struct L2Submit;
+
+/// L2_BATCH_VERIFY handler
+pub struct L2BatchVerify;
+
+/// L2_STATE_SYNC handler
+pub struct L2StateSync;
+
+/// Bind the L2 opcode handlers
+pub fn register_l2_opcodes(registry: &mut OpcodeRegistry) -> Result<(), RvmError> {
+    registry.register(Opcode::L2_SUBMIT, L2Submit)?;
+    registry.register(Opcode::L2_BATCH_VERIFY, L2BatchVerify)?;
+    registry.register(Opcode::L2_STATE_SYNC, L2StateSync)?;
+    Ok(())
+}
+
+/// Read a 32-byte root from memory
+fn read_root(context: &mut OpcodeContext<'_>, offset: u64) -> Result<[u8; 32], RvmError> {
+    let bytes = context.read_memory(offset, 32)?;
+    let mut root = [0u8; 32];
+    root.copy_from_slice(&bytes);
+    Ok(root)
+}
+
+impl OpcodeHandler for L2Submit {
+    fn stack_io(&self) -> (usize, usize) {
+        (2, 1)
+    }
+
+    fn gas(&self, inputs: &[u64]) -> u64 {
+        inputs[1].saturating_mul(L2_DATA_BYTE_GAS)
+    }
+
+    fn execute(&self, context: &mut OpcodeContext<'_>) -> Result<Vec<u64>, RvmError> {
+        let [data_offset, data_size] = context.inputs[..] else {
+            return Err(RvmError::StackUnderflow);
+        };
+        context.ensure_mutable()?;
+        let data = context.read_memory(data_offset, data_size)?;
+        let (rollup, l1_block) = (context.env.contract_address, context.env.block_number);
+        Ok(vec![context.host.l2_submit(rollup, data, l1_block)?])
+    }
+}
+
+impl OpcodeHandler for L2BatchVerify {
+    fn stack_io(&self) -> (usize, usize) {
+        (5, 1)
+    }
+
+    fn gas(&self, inputs: &[u64]) -> u64 {
+        (inputs[4] / 32).saturating_mul(L2_PROOF_HASH_GAS)
+    }
+
+    fn execute(&self, context: &mut OpcodeContext<'_>) -> Result<Vec<u64>, RvmError> {
+        let [root_offset, index, position, proof_offset, proof_size] = context.inputs[..] else {
+            return Err(RvmError::StackUnderflow);
+        };
+        context.ensure_mutable()?;
+        let batch_root = read_root(context, root_offset)?;
+        let proof = context.read_memory(proof_offset, proof_size)?;
+        if proof.len() % 32 != 0 {
+            return Ok(vec![0]);
+        }
+        let rollup = context.env.contract_address;
+        let Some(transaction) = context.host.l2_transaction(rollup, index) else {
+            return Ok(vec![0]);
+        };
+        let proof: Vec<[u8; 32]> = proof
+            .chunks(32)
+            .map(|hash| hash.try_into().expect("32-byte chunks"))
+            .collect();
+        if !verify_inclusion(&transaction.hash(), &proof, &batch_root, position) {
+            return Ok(vec![0]);
+        }
+        match context.host.l2_include(rollup, index, batch_root) {
+            Ok(()) => Ok(vec![1]),
+            Err(RvmError::L2BatchVerificationFailed(_)) => Ok(vec![0]),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+impl OpcodeHandler for L2StateSync {
+    fn stack_io(&self) -> (usize, usize) {
+        (3, 1)
+    }
+
+    fn execute(&self, context: &mut OpcodeContext<'_>) -> Result<Vec<u64>, RvmError> {
+        let [batch_root_offset, state_root_offset, l2_block] = context.inputs[..] else {
+            return Err(RvmError::StackUnderflow);
+        };
+        context.ensure_mutable()?;
+        let batch_root = read_root(context, batch_root_offset)?;
+        let state_root = read_root(context, state_root_offset)?;
+        let checkpoint = L2Checkpoint { l2_block, state_root, batch_root, l1_block: context.env.block_number };
+        match context.host.l2_sync_state(context.env.contract_address, checkpoint) {
+            Ok(()) => Ok(vec![1]),
+            Err(RvmError::L2StateSyncFailed(_)) => Ok(vec![0]),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        asm::assemble,
+        core::ExecutionEnvironment,
+        runtime::{RuntimeConfig, RvmRuntime},
+        test_utils::{store_bytes, words},
+    };
+
+    /// Prove inbox transaction `index` against a batch, then checkpoint a state root for it.
+    /// Batch root at 0, state root at 32, proof at 64; returns the verify and sync flags
+    fn settle(batch_root: &[u8; 32], state_root: &[u8; 32], index: u64, position: u64, proof: &[[u8; 32]], l2_block: u64) -> Vec<u8> {
+        let source = format!(
+            "{}{}{}
+             PUSH {}
+             PUSH 64
+             PUSH {position}
+             PUSH {index}
+             PUSH 0
+             L2_BATCH_VERIFY
+             PUSH 512
+             MSTORE
+             PUSH {l2_block}
+             PUSH 32
+             PUSH 0
+             L2_STATE_SYNC
+             PUSH 544
+             MSTORE
+             PUSH 64
+             PUSH 512
+             RETURN",
+            store_bytes(0, batch_root),
+            store_bytes(32, state_root),
+            store_bytes(64, &proof.concat()),
+            proof.len() * 32,
+        );
+        assemble(&source).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_l1_l2_round_trip() {
+        let mut l1 = RvmRuntime::new(RuntimeConfig::default());
+        let (bridge, sequencer) = ([0xb1; 20], [0x5e; 20]);
+        let env = ExecutionEnvironment { contract_address: bridge, block_number: 7, ..Default::default() };
+
+        // L1: post four deposits, each an 8-byte amount, and return their inbox indices
+        let amounts = [5u64, 10, 20, 40];
+        let source: String = amounts
+            .iter()
+            .enumerate()
+            .map(|(i, amount)| format!(
+                "PUSH {amount}\nPUSH 256\nMSTORE\nPUSH 8\nPUSH 280\nL2_SUBMIT\nPUSH {}\nMSTORE\n",
+                i * 32
+            ))
+            .collect::<String>()
+            + "PUSH 128\nPUSH 0\nRETURN";
+        let result = l1.execute(&assemble(&source).unwrap(), env.clone()).await.unwrap();
+        assert!(result.success, "{:?}", result.error);
+        assert_eq!(words(&result.return_data), vec![0, 1, 2, 3]);
+        assert!(result.gas_used > 4 * 8 * L2_DATA_BYTE_GAS);
+
+        // L2: the sequencer batches the inbox and executes the deposits on its own VM
+        let inbox = l1.storage().read().await.l2_inbox(&bridge).to_vec();
+        assert_eq!(inbox.len(), 4);
+        assert!(inbox.iter().all(|tx| tx.sender == bridge && tx.l1_block == 7));
+        let batch = L2Batch::new(&inbox);
+        let mut l2 = RvmRuntime::new(RuntimeConfig::default());
+        for tx in &inbox {
+            let amount = u64::from_be_bytes(tx.data[..].try_into().unwrap());
+            let deposit = format!("PUSH 0\nSLOAD\nPUSH {amount}\nADD\nPUSH 0\nSSTORE\nSTOP");
+            let result = l2.execute(&assemble(&deposit).unwrap(), env.clone()).await.unwrap();
+            assert!(result.success, "{:?}", result.error);
+        }
+        let total = l2.storage().read().await.get_storage(&bridge, 0);
+        assert_eq!(total, 75);
+        let state_root = RvmCrypto::keccak256(&total.to_be_bytes());
+        let proof = batch.proof(2).unwrap();
+        assert_eq!(proof.len(), 2);
+
+        // L1: deposit 2 cannot be proven before the sequencer posts the batch
+        let result = l1.execute(&settle(&batch.root(), &state_root, 2, 2, &proof, 1), env.clone()).await.unwrap();
+        assert_eq!(words(&result.return_data), vec![0, 0]);
+        {
+            let storage = l1.storage();
+            let mut storage = storage.write().await;
+            storage.set_l2_sequencer(bridge, sequencer);
+            storage.post_l2_batch(bridge, sequencer, batch.root()).unwrap();
+        }
+
+        // A proof for the wrong position verifies nothing, so the batch cannot be synced
+        let result = l1.execute(&settle(&batch.root(), &state_root, 2, 1, &proof, 1), env.clone()).await.unwrap();
+        assert_eq!(words(&result.return_data), vec![0, 0]);
+
+        let result = l1.execute(&settle(&batch.root(), &state_root, 2, 2, &proof, 1), env.clone()).await.unwrap();
+        assert!(result.success, "{:?}", result.error);
+        assert_eq!(words(&result.return_data), vec![1, 1]);
+
+        // L2 block numbers must move forward
+        let result = l1.execute(&settle(&batch.root(), &state_root, 2, 2, &proof, 1), env.clone()).await.unwrap();
+        assert_eq!(words(&result.return_data), vec![1, 0]);
+
+        let storage = l1.storage();
+        let storage = storage.read().await;
+        assert_eq!(storage.l2_batch_of(&bridge, 2), Some(batch.root()));
+        assert_eq!(storage.l2_batch_of(&bridge, 1), None);
+        assert_eq!(
+            storage.l2_checkpoints(&bridge),
+            &[L2Checkpoint { l2_block: 1, state_root, batch_root: batch.root(), l1_block: 7 }]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_made_up_batch_roots_are_rejected() {
+        let mut l1 = RvmRuntime::new(RuntimeConfig::default());
+        let (rollup, other) = ([0xa1; 20], [0xa2; 20]);
+        let submit = assemble("PUSH 1\nPUSH 0\nMSTORE8\nPUSH 1\nPUSH 0\nL2_SUBMIT\nSTOP").unwrap();
+        for contract_address in [rollup, other] {
+            let env = ExecutionEnvironment { contract_address, ..Default::default() };
+            let result = l1.execute(&submit, env).await.unwrap();
+            assert!(result.success, "{:?}", result.error);
+        }
+        let transaction = l1.storage().read().await.l2_inbox(&rollup)[0].clone();
+        let state_root = [0x77; 32];
+        let env = ExecutionEnvironment { contract_address: rollup, ..Default::default() };
+
+        // The transaction's own hash is the root of a one-transaction batch, but nobody posted it
+        let made_up = transaction.hash();
+        let result = l1.execute(&settle(&made_up, &state_root, 0, 0, &[], u64::MAX), env.clone()).await.unwrap();
+        assert!(result.success, "{:?}", result.error);
+        assert_eq!(words(&result.return_data), vec![0, 0]);
+
+        // Only the rollup contract and its sequencer may post
+        {
+            let storage = l1.storage();
+            let mut storage = storage.write().await;
+            assert!(matches!(
+                storage.post_l2_batch(rollup, other, made_up),
+                Err(RvmError::L2BatchVerificationFailed(_))
+            ));
+            storage.post_l2_batch(rollup, rollup, made_up).unwrap();
+        }
+        let result = l1.execute(&settle(&made_up, &state_root, 0, 0, &[], u64::MAX), env).await.unwrap();
+        assert_eq!(words(&result.return_data), vec![1, 1]);
+
+        // The batch belongs to its rollup, and checkpoints of one rollup leave the others alone
+        let env = ExecutionEnvironment { contract_address: other, ..Default::default() };
+        let result = l1.execute(&settle(&made_up, &state_root, 0, 0, &[], 1), env.clone()).await.unwrap();
+        assert_eq!(words(&result.return_data), vec![0, 0]);
+        let other_root = l1.storage().read().await.l2_inbox(&other)[0].hash();
+        l1.storage().write().await.post_l2_batch(other, other, other_root).unwrap();
+        let result = l1.execute(&settle(&other_root, &state_root, 0, 0, &[], 1), env).await.unwrap();
+        assert_eq!(words(&result.return_data), vec![1, 1]);
+        assert_eq!(l1.storage().read().await.l2_checkpoints(&rollup)[0].l2_block, u64::MAX);
+    }
+
+    #[test]
+    fn test_batch_proofs() {
+        let transactions: Vec<L2Transaction> = (0..3)
+            .map(|index| L2Transaction { index, sender: [1u8; 20], data: vec![index as u8], l1_block: 1 })
+            .collect();
+        let batch = L2Batch::new(&transactions);
+        for (position, transaction) in transactions.iter().enumerate() {
+            let proof = batch.proof(position).unwrap();
+            assert!(verify_inclusion(&transaction.hash(), &proof, &batch.root(), position as u64));
+            assert!(!verify_inclusion(&transaction.hash(), &proof, &batch.root(), position as u64 + 4));
+        }
+        // Padding leaves exist but no fourth transaction does
+        assert!(batch.proof(3).is_some());
+        assert!(batch.proof(4).is_none());
+
+        // A transaction whose fields spell out two child hashes does not pass for their node
+        let pair = L2Batch::new(&transactions[..2]);
+        let children = [transactions[0].hash(), transactions[1].hash()].concat();
+        let forged = L2Transaction {
+            index: u64::from_be_bytes(children[..8].try_into().unwrap()),
+            sender: children[8..28].try_into().unwrap(),
+            data: children[28..].to_vec(),
+            l1_block: 1,
+        };
+        assert_eq!(pair.root(), node_hash(&transactions[0].hash(), &transactions[1].hash()));
+        assert!(!verify_inclusion(&forged.hash(), &[], &pair.root(), 0));
+
+        let single = L2Batch::new(&transactions[..1]);
+        assert_eq!(single.root(), transactions[0].hash());
+        assert_eq!(single.proof(0), Some(vec![]));
+    }
+}
@@ -4,6 +4,21 @@
 //! Designed for blockchain, agent, and cloud-native systems with deterministic execution.
 
 pub mod core;
+pub mod bytecode;
+pub mod host;
+pub mod inspector;
+pub mod tracer;
+pub mod call_tracer;
+pub mod profiler;
+pub mod debugger;
+pub mod asm;
+pub mod disasm;
+pub mod validator;
+pub mod eof;
+pub mod custom_opcodes;
+pub mod agents;
+pub mod l2;
+pub mod bridge;
 pub mod runtime;
 pub mod revm;
 pub mod opcodes;
@@ -15,8 +30,20 @@ pub mod ghostchain_crypto;
 pub mod ghostchain_services;
 pub mod error;
 pub mod wasm_lite;
+#[cfg(test)]
+mod test_utils;
 
 pub use core::*;
+pub use host::*;
+pub use inspector::*;
+pub use tracer::*;
+pub use call_tracer::*;
+pub use profiler::*;
+pub use custom_opcodes::*;
+pub use agents::*;
+pub use l2::*;
+pub use bridge::*;
+pub use debugger::*;
 pub use runtime::*;
 pub use revm::*;
 pub use wasm_lite::*;
@@ -5,9 +5,16 @@
 
 use rvm::{
     runtime::{RvmRuntime, RuntimeConfig, DeploymentRequest},
-    revm::REvm,
+    revm::{REvm, EvmTransaction, DEFAULT_GAS_PRICE},
     wasm_lite::{WasmLiteVM, WasmLiteValue},
     core::ExecutionEnvironment,
+    tracer::Eip3155Tracer,
+    profiler::GasProfiler,
+    debugger::{Breakpoint, DebugEvent, Debugger},
+    opcodes::Opcode,
+    asm::assemble,
+    disasm::Disassembly,
+    validator::validate,
 };
 use clap::{Parser, Subcommand};
 use std::fs;
@@ -55,6 +62,15 @@ enum Commands {
         /// Call value
         #[arg(short, long, default_value = "0")]
         value: u64,
+        /// Write an EIP-3155 JSON trace to stderr
+        #[arg(long)]
+        trace: bool,
+        /// Profile gas usage by opcode and call stack
+        #[arg(long)]
+        profile: bool,
+        /// Folded-stack output file for --profile (default: <file>.folded)
+        #[arg(long)]
+        folded: Option<String>,
     },
     /// Execute EVM bytecode
     Evm {
@@ -66,6 +82,9 @@ enum Commands {
         /// Call value
         #[arg(short, long, default_value = "0")]
         value: u64,
+        /// Write an EIP-3155 JSON trace to stderr
+        #[arg(long)]
+        trace: bool,
     },
     /// Execute WASM-lite module
     WasmLite {
@@ -91,6 +110,9 @@ enum Commands {
         /// VM type (rvm, evm)
         #[arg(short, long, default_value = "rvm")]
         vm_type: String,
+        /// Reject bytecode that fails static validation
+        #[arg(long)]
+        validate: bool,
     },
     /// Call a deployed contract
     Call {
@@ -108,6 +130,41 @@ enum Commands {
         #[arg(short, long, default_value = "rvm")]
         vm_type: String,
     },
+    /// Estimate the gas limit a call to EVM bytecode needs
+    Estimate {
+        /// Contract bytecode file path (raw or hex text)
+        file: String,
+        /// Call data (hex)
+        #[arg(long, default_value = "")]
+        data: String,
+        /// Caller address (hex)
+        #[arg(short, long)]
+        caller: Option<String>,
+        /// Call value
+        #[arg(short, long, default_value = "0")]
+        value: u64,
+    },
+    /// Assemble mnemonic source into bytecode
+    Asm {
+        /// Assembly source file path
+        file: String,
+        /// Write raw bytecode to this file instead of printing hex
+        #[arg(short, long)]
+        output: Option<String>,
+    },
+    /// Disassemble bytecode (raw or hex text)
+    Disasm {
+        /// Bytecode file path
+        file: String,
+        /// Print the control-flow graph as Graphviz DOT instead of a listing
+        #[arg(long)]
+        cfg: bool,
+    },
+    /// Statically validate bytecode (raw or hex text)
+    Validate {
+        /// Bytecode file path
+        file: String,
+    },
     /// Show runtime statistics
     Stats,
     /// Run interactive REPL
@@ -116,9 +173,10 @@ enum Commands {
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    // Initialize tracing
+    // Initialize tracing on stderr, keeping stdout clean for piped output (asm, disasm --cfg)
     tracing_subscriber::fmt()
         .with_env_filter(EnvFilter::from_default_env().add_directive("rvm=info".parse().unwrap()))
+        .with_writer(std::io::stderr)
         .init();
 
     let cli = Cli::parse();
@@ -130,6 +188,8 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         enable_agent_apis: true,
         enable_crypto_hooks: true,
         debug_mode: cli.debug,
+        validate_bytecode: false,
+        chain_id: 0,
     };
 
     info!("Starting RVM v{}", rvm::VERSION);
@@ -138,19 +198,21 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         Commands::Demo { demo_type } => {
             run_demos(&demo_type, config).await?;
         }
-        Commands::Run { file, caller, value } => {
+        Commands::Run { file, caller, value, trace, profile, folded } => {
             let caller_addr = parse_address(caller.as_deref().unwrap_or("0x0000000000000000000000000000000000000001"))?;
-            run_rvm_file(&file, caller_addr, value, config).await?;
+            let folded = profile.then(|| folded.unwrap_or_else(|| format!("{}.folded", file)));
+            run_rvm_file(&file, caller_addr, value, trace, folded.as_deref(), config).await?;
         }
-        Commands::Evm { file, caller, value } => {
+        Commands::Evm { file, caller, value, trace } => {
             let caller_addr = parse_address(caller.as_deref().unwrap_or("0x0000000000000000000000000000000000000001"))?;
-            run_evm_file(&file, caller_addr, value).await?;
+            run_evm_file(&file, caller_addr, value, trace).await?;
         }
         Commands::WasmLite { file, function, args } => {
             run_wasm_lite_file(&file, &function, args.as_deref()).await?;
         }
-        Commands::Deploy { file, deployer, balance, vm_type } => {
+        Commands::Deploy { file, deployer, balance, vm_type, validate } => {
             let deployer_addr = parse_address(deployer.as_deref().unwrap_or("0x0000000000000000000000000000000000000001"))?;
+            let config = RuntimeConfig { validate_bytecode: validate, ..config };
             deploy_contract(&file, deployer_addr, balance, &vm_type, config).await?;
         }
         Commands::Call { address, data, caller, value, vm_type } => {
@@ -159,6 +221,20 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             let call_data = hex::decode(data.trim_start_matches("0x"))?;
             call_contract(contract_addr, call_data, caller_addr, value, &vm_type, config).await?;
         }
+        Commands::Estimate { file, data, caller, value } => {
+            let caller_addr = parse_address(caller.as_deref().unwrap_or("0x0000000000000000000000000000000000000001"))?;
+            let call_data = hex::decode(data.trim_start_matches("0x"))?;
+            estimate_gas(&file, call_data, caller_addr, value, cli.gas_limit).await?;
+        }
+        Commands::Asm { file, output } => {
+            assemble_file(&file, output.as_deref())?;
+        }
+        Commands::Disasm { file, cfg } => {
+            disassemble_file(&file, cfg)?;
+        }
+        Commands::Validate { file } => {
+            validate_file(&file)?;
+        }
         Commands::Stats => {
             show_stats(config).await?;
         }
@@ -275,6 +351,8 @@ async fn run_rvm_file(
     file: &str,
     caller: [u8; 20],
     value: u64,
+    trace: bool,
+    folded: Option<&str>,
     config: RuntimeConfig,
 ) -> Result<(), Box<dyn std::error::Error>> {
     info!("Executing RVM bytecode from: {}", file);
@@ -283,7 +361,19 @@ async fn run_rvm_file(
     let env = ExecutionEnvironment::new([0u8; 20], caller, value);
     
     let mut runtime = RvmRuntime::new(config);
-    let result = runtime.execute(&bytecode, env).await?;
+    let mut tracer = trace.then(|| Eip3155Tracer::new(std::io::stderr().lock()));
+    let mut profiler = folded.map(|_| GasProfiler::new());
+    let result = runtime.execute_inspected(&bytecode, env, &mut (&mut tracer, &mut profiler)).await?;
+
+    if let Some(tracer) = tracer.as_mut() {
+        tracer.finish(&result)?;
+    }
+    if let (Some(profiler), Some(path)) = (profiler, folded) {
+        fs::write(path, profiler.folded_stacks())?;
+        println!("Gas Profile:");
+        print!("{}", profiler.opcode_summary());
+        println!("  Folded stacks written to: {}", path);
+    }
     
     println!("Execution Result:");
     println!("  Success: {}", result.success);
@@ -302,13 +392,21 @@ async fn run_evm_file(
     file: &str,
     caller: [u8; 20],
     value: u64,
+    trace: bool,
 ) -> Result<(), Box<dyn std::error::Error>> {
     info!("Executing EVM bytecode from: {}", file);
     
     let bytecode = fs::read(file)?;
     let mut revm = REvm::new(1337);
     
-    let result = revm.execute_bytecode(&bytecode, caller, value, 1000000).await?;
+    let result = if trace {
+        let mut tracer = Eip3155Tracer::new(std::io::stderr().lock());
+        let result = revm.execute_bytecode_with_inspector(&bytecode, caller, value, 1000000, &mut tracer).await?;
+        tracer.finish(&result)?;
+        result
+    } else {
+        revm.execute_bytecode(&bytecode, caller, value, 1000000).await?
+    };
     
     println!("EVM Execution Result:");
     println!("  Success: {}", result.success);
@@ -365,6 +463,8 @@ async fn deploy_contract(
         }
         "evm" => {
             let mut revm = REvm::new(1337);
+            // Fund the deployer on the fresh chain to cover gas and value
+            revm.set_account_balance(deployer, funding(config.max_gas_limit, balance)?).await;
             let address = revm.deploy_contract(bytecode, deployer, balance, config.max_gas_limit).await?;
             println!("EVM contract deployed at: 0x{}", hex::encode(address));
         }
@@ -401,6 +501,8 @@ async fn call_contract(
         }
         "evm" => {
             let mut revm = REvm::new(1337);
+            // Fund the caller on the fresh chain to cover gas and value
+            revm.set_account_balance(caller, funding(config.max_gas_limit, value)?).await;
             let result = revm.call_contract(address, data, caller, value, config.max_gas_limit).await?;
             
             println!("EVM Call Result:");
@@ -417,6 +519,100 @@ async fn call_contract(
     Ok(())
 }
 
+/// Deploy bytecode on a fresh EVM chain and estimate the gas limit a call to it needs
+async fn estimate_gas(
+    file: &str,
+    data: Vec<u8>,
+    caller: [u8; 20],
+    value: u64,
+    gas_limit: u64,
+) -> Result<(), Box<dyn std::error::Error>> {
+    info!("Estimating gas for a call to: {}", file);
+
+    let bytecode = read_bytecode(file)?;
+    let mut revm = REvm::new(1337);
+    // Fund the caller to deploy the contract and send the value
+    revm.set_account_balance(caller, funding(gas_limit, value)?).await;
+    let address = revm.deploy_contract(bytecode, caller, 0, gas_limit).await?;
+    let tx = EvmTransaction {
+        hash: [0u8; 32],
+        from: caller,
+        to: Some(address),
+        value,
+        data,
+        gas_limit,
+        gas_price: DEFAULT_GAS_PRICE,
+        max_fee_per_gas: None,
+        max_priority_fee_per_gas: None,
+        nonce: revm.get_account_nonce(&caller).await,
+        access_list: vec![],
+    };
+    let gas = revm.estimate_gas(&tx).await?;
+
+    println!("Estimated gas: {}", gas);
+    Ok(())
+}
+
+/// Assemble a source file, printing hex or writing raw bytecode
+fn assemble_file(file: &str, output: Option<&str>) -> Result<(), Box<dyn std::error::Error>> {
+    let source = fs::read_to_string(file)?;
+    let bytecode = assemble(&source)?;
+
+    match output {
+        Some(path) => {
+            fs::write(path, &bytecode)?;
+            println!("Assembled {} bytes to {}", bytecode.len(), path);
+        }
+        None => println!("0x{}", hex::encode(&bytecode)),
+    }
+    Ok(())
+}
+
+/// Print a disassembly listing or control-flow graph
+fn disassemble_file(file: &str, cfg: bool) -> Result<(), Box<dyn std::error::Error>> {
+    let disassembly = Disassembly::new(&read_bytecode(file)?);
+    if cfg {
+        print!("{}", disassembly.to_dot());
+    } else {
+        print!("{}", disassembly.listing());
+    }
+    Ok(())
+}
+
+/// Report static validation problems, failing if there are any
+fn validate_file(file: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let errors = validate(&read_bytecode(file)?);
+    if errors.is_empty() {
+        println!("✅ {} is valid", file);
+        return Ok(());
+    }
+    for error in &errors {
+        println!("  {}", error);
+    }
+    Err(format!("{} validation error(s) in {}", errors.len(), file).into())
+}
+
+/// Balance that buys `gas_limit` gas at the default price and sends `value`
+fn funding(gas_limit: u64, value: u64) -> Result<u64, Box<dyn std::error::Error>> {
+    gas_limit
+        .checked_mul(DEFAULT_GAS_PRICE)
+        .and_then(|fee| fee.checked_add(value))
+        .ok_or_else(|| format!("gas limit {} and value {} overflow the account balance", gas_limit, value).into())
+}
+
+/// Read bytecode stored either raw or as hex text
+fn read_bytecode(file: &str) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    let bytes = fs::read(file)?;
+    if let Ok(text) = std::str::from_utf8(&bytes) {
+        let text = text.trim();
+        let hex_text = text.strip_prefix("0x").unwrap_or(text);
+        if !hex_text.is_empty() && hex_text.len() % 2 == 0 && hex_text.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return Ok(hex::decode(hex_text)?);
+        }
+    }
+    Ok(bytes)
+}
+
 /// Show runtime statistics
 async fn show_stats(config: RuntimeConfig) -> Result<(), Box<dyn std::error::Error>> {
     let runtime = RvmRuntime::new(config);
@@ -437,7 +633,9 @@ async fn run_repl(config: RuntimeConfig) -> Result<(), Box<dyn std::error::Error
     println!("🔧 RVM Interactive REPL");
     println!("Type 'help' for commands, 'exit' to quit\n");
     
+    let gas_limit = config.max_gas_limit;
     let mut runtime = RvmRuntime::new(config);
+    let mut debugger: Option<Debugger> = None;
     
     loop {
         print!("rvm> ");
@@ -445,15 +643,29 @@ async fn run_repl(config: RuntimeConfig) -> Result<(), Box<dyn std::error::Error
         io::stdout().flush()?;
         
         let mut input = String::new();
-        io::stdin().read_line(&mut input)?;
+        if io::stdin().read_line(&mut input)? == 0 {
+            break;
+        }
         let input = input.trim();
+        let mut words = input.split_whitespace();
+        let command = words.next().unwrap_or("");
+        let args: Vec<&str> = words.collect();
         
-        match input {
+        match command {
             "exit" | "quit" => break,
             "help" => {
                 println!("Available commands:");
                 println!("  demo - Run demo execution");
                 println!("  stats - Show runtime statistics");
+                println!("  load <file|0xhex> - Load bytecode into the debugger");
+                println!("  break <pc|OPCODE|sstore [slot]|revert> - Add a breakpoint");
+                println!("  delete <n> - Remove breakpoint n");
+                println!("  breakpoints - List breakpoints");
+                println!("  step (s) - Execute one instruction, entering calls");
+                println!("  next (n) - Execute one instruction, stepping over calls");
+                println!("  continue (c) - Run to the next breakpoint");
+                println!("  restart - Go back to the first instruction");
+                println!("  stack | memory | storage | gas - Inspect the current stop");
                 println!("  help - Show this help");
                 println!("  exit - Exit REPL");
             }
@@ -469,9 +681,33 @@ async fn run_repl(config: RuntimeConfig) -> Result<(), Box<dyn std::error::Error
                 let stats = runtime.get_stats();
                 println!("Executions: {}, Gas used: {}", stats.total_executions, stats.total_gas_used);
             }
+            "load" => {
+                let Some(source) = args.first() else {
+                    println!("Usage: load <file|0xhex>");
+                    continue;
+                };
+                let code = match source.strip_prefix("0x") {
+                    Some(hex_code) => hex::decode(hex_code).map_err(|e| e.to_string()),
+                    None => fs::read(source).map_err(|e| e.to_string()),
+                };
+                match code {
+                    Ok(code) => {
+                        let state = runtime.storage().read().await.clone();
+                        println!("Loaded {} bytes", code.len());
+                        debugger = Some(Debugger::new(code, ExecutionEnvironment::default(), gas_limit, state));
+                    }
+                    Err(e) => println!("Failed to load {}: {}", source, e),
+                }
+            }
             "" => continue,
             _ => {
-                println!("Unknown command: {}", input);
+                let Some(debugger) = debugger.as_mut() else {
+                    println!("Unknown command: {} (load bytecode to use the debugger)", input);
+                    continue;
+                };
+                if let Err(e) = debug_command(debugger, command, &args).await {
+                    println!("{}", e);
+                }
             }
         }
     }
@@ -480,6 +716,97 @@ async fn run_repl(config: RuntimeConfig) -> Result<(), Box<dyn std::error::Error
     Ok(())
 }
 
+/// Handle a debugger command in the REPL
+async fn debug_command(debugger: &mut Debugger, command: &str, args: &[&str]) -> Result<(), Box<dyn std::error::Error>> {
+    match command {
+        "break" | "b" => {
+            let breakpoint = match args {
+                ["sstore"] => Breakpoint::StorageWrite(None),
+                ["sstore", slot] => Breakpoint::StorageWrite(Some(parse_number(slot)?)),
+                ["revert"] => Breakpoint::Revert,
+                [target] => match Opcode::from_name(target) {
+                    Some(opcode) => Breakpoint::Opcode(opcode),
+                    None => Breakpoint::Pc(parse_number(target)? as usize),
+                },
+                _ => return Err("Usage: break <pc|OPCODE|sstore [slot]|revert>".into()),
+            };
+            let index = debugger.add_breakpoint(breakpoint);
+            println!("Breakpoint {}: {}", index, breakpoint);
+        }
+        "delete" => {
+            let index = args.first().ok_or("Usage: delete <n>")?.parse::<usize>()?;
+            match debugger.remove_breakpoint(index) {
+                Some(breakpoint) => println!("Removed breakpoint {}: {}", index, breakpoint),
+                None => println!("No breakpoint {}", index),
+            }
+        }
+        "breakpoints" => {
+            for (index, breakpoint) in debugger.breakpoints().iter().enumerate() {
+                println!("  {}: {}", index, breakpoint);
+            }
+        }
+        "step" | "s" => print_debug_event(debugger.step().await?),
+        "next" | "n" => print_debug_event(debugger.next().await?),
+        "continue" | "c" => print_debug_event(debugger.resume().await?),
+        "restart" => {
+            debugger.restart();
+            println!("Restarted");
+        }
+        "stack" | "memory" | "storage" | "gas" => {
+            let stop = debugger.current().ok_or("Not stopped; use step, next or continue")?;
+            match command {
+                "stack" => {
+                    for (depth, value) in stop.stack.iter().rev().enumerate() {
+                        println!("  {:>3}: {:#x}", depth, value);
+                    }
+                }
+                "memory" => {
+                    for (offset, chunk) in stop.memory.chunks(32).enumerate() {
+                        println!("  {:#06x}: {}", offset * 32, hex::encode(chunk));
+                    }
+                }
+                "storage" => {
+                    for (key, value) in &stop.storage {
+                        println!("  {:#x}: {:#x}", key, value);
+                    }
+                }
+                _ => println!("  Remaining: {}, Refunded: {}", stop.gas_remaining, stop.gas_refunded),
+            }
+        }
+        _ => return Err(format!("Unknown command: {}", command).into()),
+    }
+    Ok(())
+}
+
+/// Print where the debugger stopped
+fn print_debug_event(event: DebugEvent) {
+    match event {
+        DebugEvent::Stopped(stop) => {
+            if let Some(index) = stop.breakpoint {
+                println!("Breakpoint {} hit", index);
+            }
+            println!(
+                "  pc={} {:?} depth={} gas={} stack={:?}",
+                stop.pc, stop.opcode, stop.depth, stop.gas_remaining, stop.stack
+            );
+        }
+        DebugEvent::Finished(result) => {
+            println!("Execution finished: success={}, gas={}", result.success, result.gas_used);
+            if let Some(error) = result.error {
+                println!("  Error: {}", error);
+            }
+        }
+    }
+}
+
+/// Parse a decimal or 0x-prefixed hex number
+fn parse_number(text: &str) -> Result<u64, Box<dyn std::error::Error>> {
+    Ok(match text.strip_prefix("0x") {
+        Some(hex_text) => u64::from_str_radix(hex_text, 16)?,
+        None => text.parse()?,
+    })
+}
+
 /// Parse hex address string to byte array
 fn parse_address(addr_str: &str) -> Result<[u8; 20], Box<dyn std::error::Error>> {
     let addr_str = addr_str.trim_start_matches("0x");
@@ -5,7 +5,7 @@
 use crate::error::RvmError;
 
 /// RVM opcodes with gas costs
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
 pub enum Opcode {
     // Arithmetic Operations
     STOP = 0x00,        // Gas: 0
@@ -194,6 +194,31 @@ pub enum Opcode {
     AGENT_CALL = 0xd0,          // Gas: 5000 - Call AI agent function
     AGENT_DEPLOY = 0xd1,        // Gas: 50000 - Deploy AI agent
     AGENT_QUERY = 0xd2,         // Gas: 1000 - Query agent state
+
+    // EOF operations (decoded only inside EOF code sections)
+    RJUMP = 0xe0,               // Gas: 2 - Relative jump
+    RJUMPI = 0xe1,              // Gas: 4 - Conditional relative jump
+    RJUMPV = 0xe2,              // Gas: 4 - Relative jump through a jump table
+    CALLF = 0xe3,               // Gas: 5 - Call code section
+    RETF = 0xe4,                // Gas: 3 - Return from code section
+}
+
+/// EOF data section access. These share 0xd0-0xd2 with the AGENT_* opcodes, so they
+/// only decode inside EOF code sections.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum DataOpcode {
+    DATALOAD = 0xd0,            // Gas: 4 - Load 32 bytes of the data section
+    DATALOADN = 0xd1,           // Gas: 3 - Load 32 bytes at an immediate offset
+    DATASIZE = 0xd2,            // Gas: 2 - Data section size
+    DATACOPY = 0xd3,            // Gas: 3 - Copy data section to memory
+}
+
+/// An opcode as decoded in the context of its code: legacy code only holds `Opcode`s,
+/// EOF code sections may also hold `DataOpcode`s
+#[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum DecodedOpcode {
+    Opcode(Opcode),
+    Data(DataOpcode),
 }
 
 impl Opcode {
@@ -360,6 +385,43 @@ impl Opcode {
         }
     }
 
+    /// Convert a byte inside an EOF code section to opcode.
+    ///
+    /// EOF code adds RJUMP, RJUMPI, RJUMPV, CALLF and RETF, and decodes 0xd0-0xd3 as data
+    /// section access instead of the AGENT_* opcodes.
+    pub fn from_eof_byte(byte: u8) -> Result<DecodedOpcode, RvmError> {
+        match byte {
+            0xd0 => Ok(DecodedOpcode::Data(DataOpcode::DATALOAD)),
+            0xd1 => Ok(DecodedOpcode::Data(DataOpcode::DATALOADN)),
+            0xd2 => Ok(DecodedOpcode::Data(DataOpcode::DATASIZE)),
+            0xd3 => Ok(DecodedOpcode::Data(DataOpcode::DATACOPY)),
+            0xe0 => Ok(Opcode::RJUMP.into()),
+            0xe1 => Ok(Opcode::RJUMPI.into()),
+            0xe2 => Ok(Opcode::RJUMPV.into()),
+            0xe3 => Ok(Opcode::CALLF.into()),
+            0xe4 => Ok(Opcode::RETF.into()),
+            _ => Opcode::from_byte(byte).map(DecodedOpcode::Opcode),
+        }
+    }
+
+    /// Look up an opcode by mnemonic (case-insensitive), including EOF-only opcodes
+    pub fn from_name(name: &str) -> Option<DecodedOpcode> {
+        (0..=u8::MAX)
+            .flat_map(|byte| [Opcode::from_byte(byte).map(DecodedOpcode::Opcode).ok(), Opcode::from_eof_byte(byte).ok()])
+            .flatten()
+            .find(|opcode| format!("{:?}", opcode).eq_ignore_ascii_case(name))
+    }
+
+    /// Whether the opcode is only valid inside EOF code sections
+    pub fn is_eof_only(&self) -> bool {
+        matches!(self, Opcode::RJUMP | Opcode::RJUMPI | Opcode::RJUMPV | Opcode::CALLF | Opcode::RETF)
+    }
+
+    /// Whether the opcode is in the GhostChain range (0xc0-0xd2), served by registered handlers
+    pub fn is_ghostchain(&self) -> bool {
+        (0xc0..=0xd2).contains(&(*self as u8))
+    }
+
     /// Get gas cost for opcode
     pub fn gas_cost(&self) -> u64 {
         match self {
@@ -445,6 +507,103 @@ impl Opcode {
             Opcode::CNS_REGISTER | Opcode::BRIDGE_SEND => 20000,
 
             Opcode::L2_BATCH_VERIFY | Opcode::AGENT_DEPLOY => 50000,
+
+            Opcode::RJUMP => 2,
+            Opcode::RETF => 3,
+            Opcode::RJUMPI | Opcode::RJUMPV => 4,
+            Opcode::CALLF => 5,
+        }
+    }
+
+    /// Stack items consumed and produced.
+    ///
+    /// GhostChain opcodes without a standard handler report no stack effect; at runtime the
+    /// signature of the registered handler applies.
+    pub fn stack_io(&self) -> (usize, usize) {
+        let byte = *self as u8;
+        match self {
+            _ if self.is_push() => (0, 1),
+            // DUPn reads n items and leaves them plus a copy
+            _ if (0x80..=0x8f).contains(&byte) => {
+                let n = (byte - 0x7f) as usize;
+                (n, n + 1)
+            }
+            // SWAPn reads n + 1 items
+            _ if (0x90..=0x9f).contains(&byte) => {
+                let n = (byte - 0x8f) as usize + 1;
+                (n, n)
+            }
+            _ if (0xa0..=0xa4).contains(&byte) => ((byte - 0xa0) as usize + 2, 0),
+
+            Opcode::STOP | Opcode::JUMPDEST | Opcode::INVALID => (0, 0),
+
+            Opcode::ADD | Opcode::MUL | Opcode::SUB | Opcode::DIV | Opcode::SDIV |
+            Opcode::MOD | Opcode::SMOD | Opcode::EXP | Opcode::SIGNEXTEND |
+            Opcode::LT | Opcode::GT | Opcode::SLT | Opcode::SGT | Opcode::EQ |
+            Opcode::AND | Opcode::OR | Opcode::XOR | Opcode::BYTE | Opcode::KECCAK256 => (2, 1),
+
+            Opcode::ADDMOD | Opcode::MULMOD => (3, 1),
+
+            Opcode::ISZERO | Opcode::NOT | Opcode::BALANCE | Opcode::CALLDATALOAD |
+            Opcode::EXTCODESIZE | Opcode::BLOCKHASH | Opcode::MLOAD | Opcode::SLOAD => (1, 1),
+
+            Opcode::ADDRESS | Opcode::ORIGIN | Opcode::CALLER | Opcode::CALLVALUE |
+            Opcode::CALLDATASIZE | Opcode::CODESIZE | Opcode::GASPRICE | Opcode::COINBASE |
+            Opcode::TIMESTAMP | Opcode::NUMBER | Opcode::DIFFICULTY | Opcode::GASLIMIT |
+            Opcode::PC | Opcode::MSIZE | Opcode::GAS => (0, 1),
+
+            Opcode::CALLDATACOPY | Opcode::CODECOPY => (3, 0),
+            Opcode::EXTCODECOPY => (4, 0),
+
+            Opcode::POP | Opcode::JUMP | Opcode::SELFDESTRUCT => (1, 0),
+            Opcode::MSTORE | Opcode::MSTORE8 | Opcode::SSTORE | Opcode::JUMPI |
+            Opcode::RETURN | Opcode::REVERT => (2, 0),
+
+            Opcode::CREATE => (3, 1),
+            Opcode::CREATE2 => (4, 1),
+            Opcode::CALL | Opcode::CALLCODE => (7, 1),
+            Opcode::DELEGATECALL | Opcode::STATICCALL => (6, 1),
+
+            // CALLF and RETF take their stack effect from the EOF type section
+            Opcode::RJUMPI | Opcode::RJUMPV => (1, 0),
+
+            // GhostChain opcodes, as implemented by the standard handlers
+            Opcode::GHOST_ID_VERIFY => (6, 1),
+            Opcode::GHOST_ID_RESOLVE => (2, 1),
+            Opcode::GHOST_ID_CREATE => (3, 1),
+            Opcode::CNS_RESOLVE | Opcode::CNS_OWNER => (2, 1),
+            Opcode::CNS_REGISTER => (3, 1),
+            Opcode::CNS_UPDATE => (5, 1),
+            Opcode::TOKEN_BALANCE => (2, 1),
+            Opcode::TOKEN_TRANSFER | Opcode::TOKEN_MINT | Opcode::TOKEN_BURN => (3, 1),
+            Opcode::L2_SUBMIT => (2, 1),
+            Opcode::L2_BATCH_VERIFY => (5, 1),
+            Opcode::L2_STATE_SYNC => (3, 1),
+            Opcode::BRIDGE_SEND => (4, 1),
+            Opcode::BRIDGE_RECEIVE => (3, 2),
+            Opcode::AGENT_CALL => (8, 2),
+            Opcode::AGENT_DEPLOY => (4, 1),
+            Opcode::AGENT_QUERY => (6, 2),
+
+            _ => (0, 0),
+        }
+    }
+
+    /// Size of the fixed immediate following the opcode in code; for RJUMPV this is the
+    /// max index byte in front of its jump table
+    pub fn immediate_bytes(&self) -> usize {
+        match self {
+            Opcode::RJUMP | Opcode::RJUMPI | Opcode::CALLF => 2,
+            Opcode::RJUMPV => 1,
+            _ => self.push_bytes(),
+        }
+    }
+
+    /// Size of the immediate of the instruction at `pc`, including RJUMPV's jump table
+    pub fn immediate_size(&self, code: &[u8], pc: usize) -> usize {
+        match self {
+            Opcode::RJUMPV => 1 + 2 * (code.get(pc + 1).copied().unwrap_or(0) as usize + 1),
+            _ => self.immediate_bytes(),
         }
     }
 
@@ -501,3 +660,115 @@ impl Opcode {
         }
     }
 }
+
+impl DataOpcode {
+    /// Get gas cost for opcode
+    pub fn gas_cost(&self) -> u64 {
+        match self {
+            DataOpcode::DATASIZE => 2,
+            DataOpcode::DATALOADN | DataOpcode::DATACOPY => 3,
+            DataOpcode::DATALOAD => 4,
+        }
+    }
+
+    /// Stack items consumed and produced
+    pub fn stack_io(&self) -> (usize, usize) {
+        match self {
+            DataOpcode::DATALOAD => (1, 1),
+            DataOpcode::DATALOADN | DataOpcode::DATASIZE => (0, 1),
+            DataOpcode::DATACOPY => (3, 0),
+        }
+    }
+
+    /// Size of the immediate following the opcode in code
+    pub fn immediate_bytes(&self) -> usize {
+        match self {
+            DataOpcode::DATALOADN => 2,
+            _ => 0,
+        }
+    }
+}
+
+impl DecodedOpcode {
+    /// Byte encoding of the opcode
+    pub fn byte(&self) -> u8 {
+        match self {
+            DecodedOpcode::Opcode(opcode) => *opcode as u8,
+            DecodedOpcode::Data(opcode) => *opcode as u8,
+        }
+    }
+
+    /// The opcode, unless it is EOF data section access
+    pub fn opcode(&self) -> Option<Opcode> {
+        match self {
+            DecodedOpcode::Opcode(opcode) => Some(*opcode),
+            DecodedOpcode::Data(_) => None,
+        }
+    }
+
+    /// Get gas cost for opcode
+    pub fn gas_cost(&self) -> u64 {
+        match self {
+            DecodedOpcode::Opcode(opcode) => opcode.gas_cost(),
+            DecodedOpcode::Data(opcode) => opcode.gas_cost(),
+        }
+    }
+
+    /// Stack items consumed and produced
+    pub fn stack_io(&self) -> (usize, usize) {
+        match self {
+            DecodedOpcode::Opcode(opcode) => opcode.stack_io(),
+            DecodedOpcode::Data(opcode) => opcode.stack_io(),
+        }
+    }
+
+    /// Size of the fixed immediate following the opcode in code
+    pub fn immediate_bytes(&self) -> usize {
+        match self {
+            DecodedOpcode::Opcode(opcode) => opcode.immediate_bytes(),
+            DecodedOpcode::Data(opcode) => opcode.immediate_bytes(),
+        }
+    }
+
+    /// Size of the immediate of the instruction at `pc`, including RJUMPV's jump table
+    pub fn immediate_size(&self, code: &[u8], pc: usize) -> usize {
+        match self {
+            DecodedOpcode::Opcode(opcode) => opcode.immediate_size(code, pc),
+            DecodedOpcode::Data(opcode) => opcode.immediate_bytes(),
+        }
+    }
+}
+
+impl From<Opcode> for DecodedOpcode {
+    fn from(opcode: Opcode) -> Self {
+        DecodedOpcode::Opcode(opcode)
+    }
+}
+
+impl From<DataOpcode> for DecodedOpcode {
+    fn from(opcode: DataOpcode) -> Self {
+        DecodedOpcode::Data(opcode)
+    }
+}
+
+impl PartialEq<Opcode> for DecodedOpcode {
+    fn eq(&self, other: &Opcode) -> bool {
+        *self == DecodedOpcode::Opcode(*other)
+    }
+}
+
+impl PartialEq<DataOpcode> for DecodedOpcode {
+    fn eq(&self, other: &DataOpcode) -> bool {
+        *self == DecodedOpcode::Data(*other)
+    }
+}
+
+/// Formats as the mnemonic, like `Opcode`
+impl std::fmt::Debug for DecodedOpcode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DecodedOpcode::Opcode(opcode) => opcode.fmt(f),
+            DecodedOpcode::Data(opcode) => opcode.fmt(f),
+        }
+    }
+}
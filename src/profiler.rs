@@ -0,0 +1,241 @@
+//! Gas Profiler
+//!
+//! Attributes gas to program counters, opcodes and call-stack paths. Gas spent by a
+//! callee is charged to the callee's frames rather than to the CALL that entered it, so
+//! every unit of gas appears exactly once. Output is folded-stack text for flamegraph
+//! tools plus a per-opcode summary table.
+
+use crate::{
+    core::ExecutionResult,
+    host::CallInputs,
+    inspector::{Inspector, Step},
+    opcodes::DecodedOpcode,
+};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write;
+
+/// Gas attributed to one instruction
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PcStats {
+    /// Opcode at this pc
+    pub opcode: DecodedOpcode,
+    /// Times executed
+    pub count: u64,
+    /// Gas spent, excluding gas spent by callees
+    pub gas: u64,
+}
+
+/// Gas attributed to one opcode
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct OpcodeStats {
+    /// Times executed
+    pub count: u64,
+    /// Gas spent, excluding gas spent by callees
+    pub gas: u64,
+}
+
+/// Open call frame
+#[derive(Debug)]
+struct Frame {
+    /// Interned call-stack path ending in this frame
+    path: usize,
+    /// Whether the frame executed any instruction (false for precompiles)
+    stepped: bool,
+    /// Gas used by callees since the current instruction started
+    callee_gas: u64,
+}
+
+/// Inspector collecting a gas profile
+#[derive(Debug, Default)]
+pub struct GasProfiler {
+    /// Open frames, outermost first
+    frames: Vec<Frame>,
+    /// Interned call-stack paths
+    paths: Vec<String>,
+    /// Path to interned index
+    path_ids: HashMap<String, usize>,
+    /// Gas per (path, opcode)
+    folded: HashMap<(usize, DecodedOpcode), u64>,
+    /// Gas per (contract, pc)
+    by_pc: BTreeMap<([u8; 20], usize), PcStats>,
+    /// Gas per opcode
+    by_opcode: BTreeMap<DecodedOpcode, OpcodeStats>,
+    /// Gas used by natively serviced calls, per path
+    native: BTreeMap<usize, u64>,
+}
+
+impl GasProfiler {
+    /// Create an empty profiler
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Gas per contract and pc
+    pub fn pc_stats(&self) -> &BTreeMap<([u8; 20], usize), PcStats> {
+        &self.by_pc
+    }
+
+    /// Gas per opcode
+    pub fn opcode_stats(&self) -> Vec<(DecodedOpcode, OpcodeStats)> {
+        self.by_opcode.iter().map(|(opcode, stats)| (*opcode, *stats)).collect()
+    }
+
+    /// Total gas attributed
+    pub fn total_gas(&self) -> u64 {
+        self.folded.values().sum::<u64>() + self.native.values().sum::<u64>()
+    }
+
+    /// Folded stacks (`frame;frame;OPCODE gas` per line), sorted, for flamegraph tools
+    pub fn folded_stacks(&self) -> String {
+        let mut lines: Vec<String> = self.folded.iter()
+            .filter(|(_, gas)| **gas > 0)
+            .map(|((path, opcode), gas)| format!("{};{:?} {}", self.paths[*path], opcode, gas))
+            .chain(self.native.iter().map(|(path, gas)| format!("{};[native] {}", self.paths[*path], gas)))
+            .collect();
+        lines.sort();
+        lines.into_iter().map(|line| line + "\n").collect()
+    }
+
+    /// Per-opcode table sorted by gas, highest first
+    pub fn opcode_summary(&self) -> String {
+        let total = self.total_gas().max(1);
+        let mut rows: Vec<(&DecodedOpcode, &OpcodeStats)> = self.by_opcode.iter().collect();
+        rows.sort_by(|a, b| b.1.gas.cmp(&a.1.gas).then(a.0.cmp(b.0)));
+
+        let mut table = format!("{:<18} {:>10} {:>12} {:>8}\n", "OPCODE", "COUNT", "GAS", "GAS %");
+        for (opcode, stats) in rows {
+            let _ = writeln!(
+                table,
+                "{:<18} {:>10} {:>12} {:>7.2}%",
+                format!("{:?}", opcode),
+                stats.count,
+                stats.gas,
+                stats.gas as f64 * 100.0 / total as f64,
+            );
+        }
+        table
+    }
+
+    /// Intern the path of a frame for `address` nested in the current frame
+    fn enter(&mut self, address: &[u8; 20]) {
+        let label = format!("0x{}", hex::encode(address));
+        let path = match self.frames.last() {
+            Some(parent) => format!("{};{}", self.paths[parent.path], label),
+            None => label,
+        };
+        let id = match self.path_ids.get(&path) {
+            Some(id) => *id,
+            None => {
+                self.paths.push(path.clone());
+                self.path_ids.insert(path, self.paths.len() - 1);
+                self.paths.len() - 1
+            }
+        };
+        self.frames.push(Frame { path: id, stepped: false, callee_gas: 0 });
+    }
+
+    /// Close the current frame, charging natively serviced calls to it
+    fn exit(&mut self, result: &ExecutionResult) {
+        if let Some(frame) = self.frames.pop() {
+            if !frame.stepped && result.gas_used > 0 {
+                *self.native.entry(frame.path).or_default() += result.gas_used;
+            }
+        }
+        if let Some(parent) = self.frames.last_mut() {
+            parent.callee_gas += result.gas_used;
+        }
+    }
+}
+
+impl Inspector for GasProfiler {
+    fn step(&mut self, step: &Step<'_>) {
+        // The outermost frame is not announced through `call` outside of REvm
+        if self.frames.is_empty() {
+            self.enter(&step.address);
+        }
+        if let Some(frame) = self.frames.last_mut() {
+            frame.stepped = true;
+            frame.callee_gas = 0;
+        }
+    }
+
+    fn step_end(&mut self, step: &Step<'_>, gas_cost: u64) {
+        let Some(frame) = self.frames.last() else { return };
+        let gas = gas_cost.saturating_sub(frame.callee_gas);
+        *self.folded.entry((frame.path, step.opcode)).or_default() += gas;
+
+        let opcode_stats = self.by_opcode.entry(step.opcode).or_default();
+        opcode_stats.count += 1;
+        opcode_stats.gas += gas;
+
+        let pc_stats = self.by_pc.entry((step.address, step.pc)).or_insert(PcStats {
+            opcode: step.opcode,
+            count: 0,
+            gas: 0,
+        });
+        pc_stats.count += 1;
+        pc_stats.gas += gas;
+    }
+
+    fn call(&mut self, inputs: &CallInputs) {
+        self.enter(&inputs.target);
+    }
+
+    fn call_end(&mut self, _inputs: &CallInputs, result: &ExecutionResult) {
+        self.exit(result);
+    }
+
+    fn create(&mut self, inputs: &CallInputs) {
+        self.enter(&inputs.target);
+    }
+
+    fn create_end(&mut self, _inputs: &CallInputs, result: &ExecutionResult) {
+        self.exit(result);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        core::{word_to_address, ExecutionEnvironment, RvmCore},
+        host::Host,
+        opcodes::Opcode,
+        storage::Storage,
+    };
+
+    #[tokio::test]
+    async fn test_profile_attributes_callee_gas_once() {
+        let mut storage = Storage::new();
+        // Callee: SSTORE(1, 7), STOP
+        storage.set_code(word_to_address(0x42), vec![0x60, 0x07, 0x60, 0x01, 0x55, 0x00]);
+
+        // Caller: CALL(gas=50000, to=0x42, value=0, in=0/0, out=0/0), STOP
+        let code = vec![
+            0x60, 0x00, 0x60, 0x00, 0x60, 0x00, 0x60, 0x00, 0x60, 0x00,
+            0x60, 0x42, 0x61, 0xc3, 0x50, 0xf1, 0x00,
+        ];
+        let contract = [9u8; 20];
+        let env = ExecutionEnvironment::new(contract, [1u8; 20], 0);
+
+        let mut profiler = GasProfiler::new();
+        let mut core = RvmCore::new(100_000);
+        let result = core.execute_with_inspector(&code, env, &mut storage, &mut profiler).await.unwrap();
+        assert!(result.success);
+        assert_eq!(profiler.total_gas(), result.gas_used);
+
+        let root = format!("0x{}", hex::encode(contract));
+        let callee = format!("0x{}", hex::encode(word_to_address(0x42)));
+        let folded = profiler.folded_stacks();
+        assert!(folded.contains(&format!("{};CALL 100\n", root)));
+        assert!(folded.contains(&format!("{};{};SSTORE 20000\n", root, callee)));
+        assert!(folded.contains(&format!("{};PUSH1 18\n", root)));
+
+        let stats = &profiler.pc_stats()[&(contract, 15)];
+        assert_eq!((stats.opcode, stats.count, stats.gas), (Opcode::CALL.into(), 1, 100));
+
+        let summary = profiler.opcode_summary();
+        assert!(summary.starts_with("OPCODE"));
+        assert!(summary.lines().nth(1).unwrap().starts_with("SSTORE"));
+    }
+}
@@ -5,18 +5,35 @@
 
 use crate::{
     core::{RvmCore, ExecutionEnvironment, ExecutionResult, Contract},
+    asm::assemble,
     crypto::{RvmCrypto, Precompiles},
+    custom_opcodes::OpcodeHandler,
+    eof::EofContainer,
     error::RvmError,
-    gas::GasMeter,
+    gas::{GasMeter, MAX_REFUND_QUOTIENT},
+    host::{Host, Log, CallInputs, CallKind},
+    inspector::{Inspector, NoopInspector},
+    bridge::{BridgeMessage, SignedBridgeMessage},
+    call_tracer::{decode_revert_reason, CallFrame, CallTracer},
+    profiler::GasProfiler,
     opcodes::Opcode,
     runtime::{RvmRuntime, RuntimeConfig},
     storage::Storage,
 };
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
 use tokio::sync::RwLock;
 use std::sync::Arc;
 
+/// Gas price of transactions built by `deploy_contract` and `call_contract` (1 Gwei),
+/// raised to the base fee when it is higher
+pub const DEFAULT_GAS_PRICE: u64 = 1_000_000_000;
+
+/// Bound on the base fee change between blocks, as a divisor (EIP-1559)
+pub const BASE_FEE_MAX_CHANGE_DENOMINATOR: u64 = 8;
+
+/// Ratio of the block gas limit to the gas target (EIP-1559)
+pub const ELASTICITY_MULTIPLIER: u64 = 2;
+
 /// EVM-compatible execution environment
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct EvmEnvironment {
@@ -51,10 +68,67 @@ pub struct EvmTransaction {
     pub data: Vec<u8>,
     /// Gas limit
     pub gas_limit: u64,
-    /// Gas price
+    /// Gas price, for legacy transactions
     pub gas_price: u64,
+    /// Maximum total fee per gas (EIP-1559); when set, `gas_price` is ignored
+    #[serde(default)]
+    pub max_fee_per_gas: Option<u64>,
+    /// Maximum tip per gas paid to the coinbase (EIP-1559)
+    #[serde(default)]
+    pub max_priority_fee_per_gas: Option<u64>,
     /// Transaction nonce
     pub nonce: u64,
+    /// Addresses and storage keys the transaction declares it will access (EIP-2930)
+    #[serde(default)]
+    pub access_list: Vec<AccessListItem>,
+}
+
+impl EvmTransaction {
+    /// Gas charged before execution starts
+    pub fn intrinsic_gas(&self) -> u64 {
+        let access_list_keys = self.access_list.iter().map(|item| item.storage_keys.len()).sum();
+        GasMeter::intrinsic_gas(&self.data, self.to.is_none(), self.access_list.len(), access_list_keys)
+    }
+
+    /// Highest price per gas the sender may pay, which their balance must cover
+    pub fn max_gas_price(&self) -> u64 {
+        self.max_fee_per_gas.unwrap_or(self.gas_price)
+    }
+
+    /// Price per gas paid under a base fee: the base fee plus the tip, capped at the max fee.
+    /// Legacy transactions pay `gas_price`, tipping whatever exceeds the base fee; they may
+    /// not set a max priority fee.
+    pub fn effective_gas_price(&self, base_fee: u64) -> Result<u64, RvmError> {
+        let max_fee = self.max_gas_price();
+        if max_fee < base_fee {
+            return Err(RvmError::FeeCapTooLow { max_fee, base_fee });
+        }
+        match self.max_fee_per_gas {
+            Some(max_fee) => {
+                let priority_fee = self.max_priority_fee_per_gas.unwrap_or(0);
+                if priority_fee > max_fee {
+                    return Err(RvmError::InvalidEvmTransaction(format!(
+                        "max priority fee per gas {} exceeds max fee per gas {}",
+                        priority_fee, max_fee
+                    )));
+                }
+                Ok(max_fee.min(base_fee.saturating_add(priority_fee)))
+            }
+            None if self.max_priority_fee_per_gas.is_some() => Err(RvmError::InvalidEvmTransaction(
+                "max priority fee per gas set without max fee per gas".to_string(),
+            )),
+            None => Ok(self.gas_price),
+        }
+    }
+}
+
+/// Access list entry
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AccessListItem {
+    /// Accessed account
+    pub address: [u8; 20],
+    /// Accessed storage keys of the account
+    pub storage_keys: Vec<u64>,
 }
 
 /// EVM account state
@@ -76,10 +150,14 @@ pub struct REvm {
     runtime: RvmRuntime,
     /// EVM environment
     env: EvmEnvironment,
-    /// EVM accounts
-    accounts: HashMap<[u8; 20], EvmAccount>,
     /// Transaction history
     transactions: Vec<EvmTransaction>,
+    /// Hashes of transactions executed since the last mined block
+    pending_transactions: Vec<[u8; 32]>,
+    /// Gas used by transactions since the last mined block
+    pending_gas_used: u64,
+    /// Relayed bridge messages awaiting the next transaction
+    pending_bridge_messages: Vec<SignedBridgeMessage>,
     /// Block history
     blocks: Vec<EvmBlock>,
 }
@@ -103,10 +181,31 @@ pub struct EvmBlock {
     pub gas_limit: u64,
     /// Gas used
     pub gas_used: u64,
+    /// Base fee per gas (EIP-1559)
+    #[serde(default)]
+    pub base_fee: u64,
     /// Transactions in block
     pub transactions: Vec<[u8; 32]>,
 }
 
+impl EvmBlock {
+    /// Base fee of the next block, moved towards the gas target by at most 1/8 (EIP-1559)
+    pub fn next_base_fee(&self) -> u64 {
+        let target = self.gas_limit / ELASTICITY_MULTIPLIER;
+        if target == 0 || self.gas_used == target {
+            return self.base_fee;
+        }
+        let change = |delta: u64| {
+            (self.base_fee as u128 * delta as u128 / target as u128 / BASE_FEE_MAX_CHANGE_DENOMINATOR as u128) as u64
+        };
+        if self.gas_used > target {
+            self.base_fee.saturating_add(change(self.gas_used - target).max(1))
+        } else {
+            self.base_fee - change(target - self.gas_used)
+        }
+    }
+}
+
 /// EVM execution result with additional EVM-specific information
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct EvmResult {
@@ -166,6 +265,9 @@ pub struct TransactionReceipt {
     pub block_number: u64,
     /// Gas used
     pub gas_used: u64,
+    /// Price paid per gas
+    #[serde(default)]
+    pub effective_gas_price: u64,
     /// Success status
     pub success: bool,
     /// Contract address (for contract creation)
@@ -174,6 +276,135 @@ pub struct TransactionReceipt {
     pub logs: Vec<EvmLog>,
 }
 
+/// Host used for EVM execution: shared storage plus block history and precompiles
+pub struct EvmHost<'a> {
+    /// Underlying world state
+    storage: &'a mut Storage,
+    /// Mined blocks, for BLOCKHASH
+    blocks: &'a [EvmBlock],
+}
+
+impl<'a> EvmHost<'a> {
+    /// Create a new EVM host over storage and block history
+    pub fn new(storage: &'a mut Storage, blocks: &'a [EvmBlock]) -> Self {
+        Self { storage, blocks }
+    }
+
+    /// Precompile address encoded in the callee, if any
+    fn precompile_address(address: &[u8; 20]) -> Option<u8> {
+        let is_precompile = address[..19].iter().all(|byte| *byte == 0) && (1..=4).contains(&address[19]);
+        is_precompile.then_some(address[19])
+    }
+}
+
+impl Host for EvmHost<'_> {
+    fn balance(&self, address: &[u8; 20]) -> u64 {
+        self.storage.balance(address)
+    }
+
+    fn transfer(&mut self, from: [u8; 20], to: [u8; 20], amount: u64) -> Result<(), RvmError> {
+        Host::transfer(self.storage, from, to, amount)
+    }
+
+    fn nonce(&self, address: &[u8; 20]) -> u64 {
+        self.storage.nonce(address)
+    }
+
+    fn increment_nonce(&mut self, address: [u8; 20]) {
+        Host::increment_nonce(self.storage, address)
+    }
+
+    fn code(&self, address: &[u8; 20]) -> Option<Vec<u8>> {
+        self.storage.code(address)
+    }
+
+    fn set_code(&mut self, address: [u8; 20], code: Vec<u8>) {
+        self.storage.set_code(address, code)
+    }
+
+    fn sload(&self, address: &[u8; 20], key: u64) -> u64 {
+        self.storage.sload(address, key)
+    }
+
+    fn sstore(&mut self, address: [u8; 20], key: u64, value: u64) {
+        self.storage.sstore(address, key, value)
+    }
+
+    fn block_hash(&self, number: u64) -> [u8; 32] {
+        self.blocks.iter()
+            .find(|block| block.number == number)
+            .map(|block| block.hash)
+            .unwrap_or([0u8; 32])
+    }
+
+    fn log(&mut self, log: Log) {
+        self.storage.log(log)
+    }
+
+    fn wrapped(&self) -> Option<&dyn Host> {
+        Some(&*self.storage)
+    }
+
+    fn wrapped_mut(&mut self) -> Option<&mut dyn Host> {
+        Some(&mut *self.storage)
+    }
+
+    fn call(&mut self, inputs: &CallInputs) -> Option<ExecutionResult> {
+        if inputs.kind == CallKind::Create {
+            return None;
+        }
+        let address = Self::precompile_address(&inputs.target)?;
+
+        let result = Precompiles::gas_cost(address, &inputs.input)
+            .and_then(|gas_cost| {
+                let mut gas_meter = GasMeter::new(inputs.gas_limit);
+                gas_meter.consume(gas_cost)?;
+                Ok((Precompiles::execute(address, &inputs.input)?, gas_meter.used()))
+            });
+
+        Some(match result {
+            Ok((output, gas_used)) => ExecutionResult {
+                return_data: output,
+                gas_used,
+                gas_refunded: 0,
+                success: true,
+                error: None,
+                agent_witness: None,
+                fee_receipt: None,
+                logs: Vec::new(),
+            },
+            Err(e) => ExecutionResult {
+                return_data: vec![],
+                gas_used: inputs.gas_limit,
+                gas_refunded: 0,
+                success: false,
+                error: Some(e.to_string()),
+                agent_witness: None,
+                fee_receipt: None,
+                logs: Vec::new(),
+            },
+        })
+    }
+
+    fn checkpoint(&mut self) -> usize {
+        self.storage.checkpoint()
+    }
+
+    fn revert_to(&mut self, checkpoint: usize) {
+        self.storage.revert_to(checkpoint)
+    }
+}
+
+impl From<Log> for EvmLog {
+    fn from(log: Log) -> Self {
+        Self {
+            address: log.address,
+            topics: log.topics,
+            data: log.data,
+        }
+    }
+}
+
 impl REvm {
     /// Create a new rEVM instance
     pub fn new(chain_id: u64) -> Self {
@@ -183,6 +414,8 @@ impl REvm {
             enable_agent_apis: false, // Disable for pure EVM compatibility
             enable_crypto_hooks: true,
             debug_mode: false,
+            validate_bytecode: false,
+            chain_id,
         };
 
         Self {
@@ -199,72 +432,357 @@ impl REvm {
                 chain_id,
                 base_fee: 1_000_000_000, // 1 Gwei
             },
-            accounts: HashMap::new(),
             transactions: Vec::new(),
+            pending_transactions: Vec::new(),
+            pending_gas_used: 0,
+            pending_bridge_messages: Vec::new(),
             blocks: Vec::new(),
         }
     }
 
+    /// Bind a handler to a GhostChain opcode
+    pub fn register_opcode<H: OpcodeHandler + 'static>(&mut self, opcode: Opcode, handler: H) -> Result<(), RvmError> {
+        self.runtime.register_opcode(opcode, handler)
+    }
+
     /// Execute an EVM transaction
     pub async fn execute_transaction(&mut self, tx: EvmTransaction) -> Result<EvmResult, RvmError> {
-        // Convert EVM transaction to RVM execution environment
+        self.execute_transaction_with_inspector(tx, &mut NoopInspector).await
+    }
+
+    /// Execute an EVM transaction, reporting execution to an inspector.
+    ///
+    /// Before anything runs, the gas limit must cover the intrinsic gas and fit in the block,
+    /// the max fee must cover the base fee, the nonce must be the sender's next nonce and the
+    /// sender must afford `gas_limit * max fee + value`. The sender buys the whole gas limit
+    /// at the effective gas price and is refunded unused gas. Gas used is net of the storage
+    /// refund, capped at a fifth of it. Of the gas used, the base fee is burned and the tip
+    /// paid to the coinbase; a tip that would overflow the coinbase balance rejects the
+    /// transaction. The value transfer is undone if execution fails. The transaction
+    /// itself is reported as the depth-0 call or create.
+    pub async fn execute_transaction_with_inspector<I: Inspector>(
+        &mut self,
+        tx: EvmTransaction,
+        inspector: &mut I,
+    ) -> Result<EvmResult, RvmError> {
+        let intrinsic_gas = tx.intrinsic_gas();
+        if tx.gas_limit < intrinsic_gas {
+            return Err(RvmError::IntrinsicGasTooLow { gas_limit: tx.gas_limit, intrinsic: intrinsic_gas });
+        }
+        let block_gas_left = self.env.gas_limit.saturating_sub(self.pending_gas_used);
+        if tx.gas_limit > block_gas_left {
+            return Err(RvmError::InvalidEvmTransaction(format!(
+                "gas limit {} exceeds the {} gas left in the block",
+                tx.gas_limit, block_gas_left
+            )));
+        }
+        let base_fee = self.env.base_fee;
+        let gas_price = tx.effective_gas_price(base_fee)?;
+        // EOF containers are validated once, here
+        if tx.to.is_none() && EofContainer::is_eof(&tx.data) {
+            EofContainer::parse(&tx.data)?.validate()?;
+        }
+
+        let storage = self.runtime.storage();
+        let mut storage = storage.write().await;
+        let expected_nonce = storage.get_nonce(&tx.from);
+        if tx.nonce != expected_nonce {
+            return Err(RvmError::InvalidNonce { expected: expected_nonce, got: tx.nonce });
+        }
+        let available = storage.get_balance(&tx.from);
+        let gas_fee = tx.gas_limit.checked_mul(tx.max_gas_price());
+        match gas_fee.and_then(|fee| fee.checked_add(tx.value)) {
+            Some(required) if required <= available => {}
+            required => {
+                return Err(RvmError::InsufficientBalance { available, required: required.unwrap_or(u64::MAX) });
+            }
+        }
+
+        // Relayed bridge messages enter the inbox ahead of the transaction
+        for message in self.pending_bridge_messages.drain(..) {
+            storage.deliver_bridge_message(message);
+        }
+
+        // Buy gas; a transaction that fails to execute leaves no trace
+        let tx_checkpoint = Host::checkpoint(&mut *storage);
+        storage.set_balance(tx.from, available - tx.gas_limit * gas_price);
+        storage.increment_nonce(tx.from);
+
+        let (kind, target) = match tx.to {
+            Some(to) => (CallKind::Call, to),
+            None => (CallKind::Create, RvmCrypto::create_address(&tx.from, tx.nonce)),
+        };
+        let inputs = CallInputs {
+            kind,
+            caller: tx.from,
+            target,
+            value: tx.value,
+            input: tx.data.clone(),
+            gas_limit: tx.gas_limit,
+            depth: 0,
+        };
         let env = ExecutionEnvironment {
-            contract_address: tx.to.unwrap_or([0u8; 20]),
+            contract_address: target,
             caller: tx.from,
             value: tx.value,
-            gas_price: tx.gas_price,
+            input: tx.data.clone(),
+            gas_price,
             block_number: self.env.block_number,
             timestamp: self.env.timestamp,
+            chain_id: self.env.chain_id,
+            coinbase: self.env.coinbase,
         };
 
-        // Execute transaction
-        let result = if let Some(to) = tx.to {
-            // Call existing contract
-            self.runtime.call_contract(to, tx.data.clone(), tx.from, tx.value, tx.gas_limit).await?
+        let checkpoint = Host::checkpoint(&mut *storage);
+        if kind == CallKind::Create {
+            inspector.create(&inputs);
         } else {
-            // Contract creation
-            let deployment_request = crate::runtime::DeploymentRequest {
-                bytecode: tx.data.clone(),
-                constructor_params: vec![],
-                initial_balance: tx.value,
-                gas_limit: tx.gas_limit,
-            };
-            
-            let contract_address = self.runtime.deploy_contract(deployment_request, tx.from).await?;
-            
-            ExecutionResult {
-                return_data: contract_address.to_vec(),
-                gas_used: 21000, // Base contract creation cost
+            inspector.call(&inputs);
+        }
+        let transferred = match tx.value {
+            0 => Ok(()),
+            value => storage.transfer(tx.from, target, value),
+        };
+        let execution = if let Err(e) = transferred {
+            // A value transfer that fails fails the transaction, which still pays for gas
+            Ok(ExecutionResult {
+                return_data: vec![],
+                gas_used: 0,
+                gas_refunded: 0,
+                success: false,
+                error: Some(e.to_string()),
+                agent_witness: None,
+                fee_receipt: None,
+                logs: Vec::new(),
+            })
+        } else if kind == CallKind::Create {
+            // Contract creation deploys the transaction data as code
+            storage.set_code(target, tx.data.clone());
+            Ok(ExecutionResult {
+                return_data: target.to_vec(),
+                gas_used: 0,
+                gas_refunded: 0,
                 success: true,
                 error: None,
+                agent_witness: None,
+                fee_receipt: None,
+                logs: Vec::new(),
+            })
+        } else {
+            let bytecode = storage.code(&target).unwrap_or_default();
+            let mut host = EvmHost::new(&mut storage, &self.blocks);
+            self.runtime
+                .execute_with_inspector(&bytecode, env, tx.gas_limit - intrinsic_gas, &mut host, inspector)
+                .await
+        };
+        let mut result = match execution {
+            Ok(result) => result,
+            Err(e) => {
+                Host::revert_to(&mut *storage, tx_checkpoint);
+                self.runtime.commit_state(&mut storage);
+                return Err(e);
             }
         };
+        if !result.success {
+            Host::revert_to(&mut *storage, checkpoint);
+        }
+
+        // Apply the storage refund, capped at a fifth of the gas used (EIP-3529), then refund
+        // unused gas to the sender, burn the base fee and pay the tip to the coinbase
+        result.gas_used += intrinsic_gas;
+        result.gas_used -= result.gas_refunded.min(result.gas_used / MAX_REFUND_QUOTIENT);
+        let refund = (tx.gas_limit - result.gas_used) * gas_price;
+        let sender_balance = storage.get_balance(&tx.from);
+        storage.set_balance(tx.from, sender_balance + refund);
+        let tip = result.gas_used * (gas_price - base_fee);
+        let Some(coinbase_balance) = storage.get_balance(&self.env.coinbase).checked_add(tip) else {
+            // The sender paid the tip, so it may not vanish into a saturated balance
+            Host::revert_to(&mut *storage, tx_checkpoint);
+            self.runtime.commit_state(&mut storage);
+            return Err(RvmError::InvalidEvmTransaction(format!("tip of {} overflows the coinbase balance", tip)));
+        };
+        storage.set_balance(self.env.coinbase, coinbase_balance);
+
+        result.logs = storage.take_logs();
+        let logs: Vec<EvmLog> = result.logs.iter().cloned().map(EvmLog::from).collect();
+        self.runtime.commit_state(&mut storage);
+        drop(storage);
+
+        if kind == CallKind::Create {
+            inspector.create_end(&inputs, &ExecutionResult { return_data: tx.data.clone(), ..result.clone() });
+        } else {
+            inspector.call_end(&inputs, &result);
+        }
 
         // Create transaction receipt
         let receipt = TransactionReceipt {
             transaction_hash: tx.hash,
             block_number: self.env.block_number,
             gas_used: result.gas_used,
+            effective_gas_price: gas_price,
             success: result.success,
             contract_address: if tx.to.is_none() && result.success {
-                Some(self.bytes_to_address(&result.return_data))
+                Some(target)
             } else {
                 None
             },
-            logs: vec![], // TODO: Extract logs from execution
+            logs: logs.clone(),
         };
 
         // Store transaction
+        self.pending_gas_used += result.gas_used;
+        self.pending_transactions.push(tx.hash);
         self.transactions.push(tx);
 
         Ok(EvmResult {
             result,
-            logs: vec![], // TODO: Extract logs
+            logs,
             state_changes: vec![], // TODO: Track state changes
             receipt,
         })
     }
 
+    /// Execute an EVM transaction and return its call tree in geth `callTracer` shape
+    pub async fn trace_transaction(&mut self, tx: EvmTransaction) -> Result<(EvmResult, CallFrame), RvmError> {
+        let mut tracer = CallTracer::new();
+        let result = self.execute_transaction_with_inspector(tx, &mut tracer).await?;
+        let call_frame = tracer.into_call_frame()
+            .ok_or_else(|| RvmError::ExecutionFailed("No call frame recorded".to_string()))?;
+        Ok((result, call_frame))
+    }
+
+    /// Execute an EVM transaction and collect a gas profile
+    pub async fn profile_transaction(&mut self, tx: EvmTransaction) -> Result<(EvmResult, GasProfiler), RvmError> {
+        let mut profiler = GasProfiler::new();
+        let result = self.execute_transaction_with_inspector(tx, &mut profiler).await?;
+        Ok((result, profiler))
+    }
+
+    /// Estimate the smallest gas limit a transaction succeeds with, like `eth_estimateGas`.
+    ///
+    /// Attempts run like the transaction, buying gas at its effective price, but without the
+    /// nonce check and reverted afterwards; relayed bridge messages that have not been
+    /// delivered yet are visible to them. The search is over success rather than gas used:
+    /// calls receive only 63/64 of the remaining gas and refunds are only granted after
+    /// execution, so the limit can exceed the gas used. The search is capped by the
+    /// transaction's gas limit, or the block's when it is zero, and by the gas the sender can
+    /// afford. Fails with the revert reason if the transaction fails at the cap.
+    pub async fn estimate_gas(&mut self, tx: &EvmTransaction) -> Result<u64, RvmError> {
+        let intrinsic_gas = tx.intrinsic_gas();
+        let mut cap = match tx.gas_limit {
+            0 => self.env.gas_limit,
+            gas_limit => gas_limit.min(self.env.gas_limit),
+        };
+        if cap < intrinsic_gas {
+            return Err(RvmError::IntrinsicGasTooLow { gas_limit: cap, intrinsic: intrinsic_gas });
+        }
+        // Below the intrinsic gas, buying gas reports the shortfall
+        let gas_price = tx.effective_gas_price(self.env.base_fee)?;
+        let balance = self.runtime.storage().read().await.get_balance(&tx.from);
+        if let Some(allowance) = balance.saturating_sub(tx.value).checked_div(gas_price) {
+            cap = cap.min(allowance.max(intrinsic_gas));
+        }
+        let result = self.simulate_transaction(tx, cap).await?;
+        if !result.success {
+            return Err(RvmError::GasEstimationFailed(revert_reason(&result)));
+        }
+
+        // Nothing below the gas used can succeed; try the 63/64 headroom before bisecting
+        let (mut low, mut high) = (result.gas_used - 1, cap);
+        let headroom = (result.gas_used.saturating_mul(64) / 63).min(cap);
+        if headroom < high {
+            if self.simulate_transaction(tx, headroom).await?.success {
+                high = headroom;
+            } else {
+                low = headroom;
+            }
+        }
+        while high - low > 1 {
+            let mid = low + (high - low) / 2;
+            if self.simulate_transaction(tx, mid).await?.success {
+                high = mid;
+            } else {
+                low = mid;
+            }
+        }
+        Ok(high)
+    }
+
+    /// Run a transaction with the given gas limit and revert it, returning its result with
+    /// intrinsic gas included. The execution is not counted in the runtime statistics.
+    async fn simulate_transaction(&mut self, tx: &EvmTransaction, gas_limit: u64) -> Result<ExecutionResult, RvmError> {
+        let storage = self.runtime.storage();
+        let mut storage = storage.write().await;
+        let checkpoint = Host::checkpoint(&mut *storage);
+        let result = self.simulate_against(&mut storage, tx, gas_limit).await;
+        Host::revert_to(&mut *storage, checkpoint);
+        result
+    }
+
+    /// Run a transaction against `storage` without reverting it
+    async fn simulate_against(
+        &mut self,
+        storage: &mut Storage,
+        tx: &EvmTransaction,
+        gas_limit: u64,
+    ) -> Result<ExecutionResult, RvmError> {
+        let intrinsic_gas = tx.intrinsic_gas();
+        let gas_price = tx.effective_gas_price(self.env.base_fee)?;
+        for message in &self.pending_bridge_messages {
+            storage.deliver_bridge_message(message.clone());
+        }
+
+        // Buy gas, then send the value
+        let available = storage.get_balance(&tx.from);
+        let required = gas_limit.checked_mul(gas_price).and_then(|fee| fee.checked_add(tx.value));
+        match required {
+            Some(required) if required <= available => {}
+            required => {
+                return Err(RvmError::InsufficientBalance { available, required: required.unwrap_or(u64::MAX) });
+            }
+        }
+        storage.set_balance(tx.from, available - gas_limit * gas_price);
+        let nonce = storage.get_nonce(&tx.from);
+        storage.increment_nonce(tx.from);
+        let target = tx.to.unwrap_or_else(|| RvmCrypto::create_address(&tx.from, nonce));
+        if tx.value > 0 {
+            storage.transfer(tx.from, target, tx.value)?;
+        }
+
+        let mut result = if tx.to.is_none() {
+            // Contract creation deploys the transaction data as code
+            ExecutionResult {
+                return_data: target.to_vec(),
+                gas_used: 0,
+                gas_refunded: 0,
+                success: true,
+                error: None,
+                agent_witness: None,
+                fee_receipt: None,
+                logs: Vec::new(),
+            }
+        } else {
+            let env = ExecutionEnvironment {
+                contract_address: target,
+                caller: tx.from,
+                value: tx.value,
+                input: tx.data.clone(),
+                gas_price,
+                block_number: self.env.block_number,
+                timestamp: self.env.timestamp,
+                chain_id: self.env.chain_id,
+                coinbase: self.env.coinbase,
+            };
+            let bytecode = storage.code(&target).unwrap_or_default();
+            let mut host = EvmHost::new(storage, &self.blocks);
+            self.runtime
+                .execute_unrecorded(&bytecode, env, gas_limit - intrinsic_gas, &mut host, &mut NoopInspector)
+                .await?
+        };
+        result.gas_used += intrinsic_gas;
+        Ok(result)
+    }
+
     /// Deploy an EVM contract
     pub async fn deploy_contract(
         &mut self,
@@ -280,8 +798,11 @@ impl REvm {
             value,
             data: bytecode,
             gas_limit,
-            gas_price: 1_000_000_000, // 1 Gwei
-            nonce: self.get_account_nonce(&deployer),
+            gas_price: DEFAULT_GAS_PRICE.max(self.env.base_fee),
+            max_fee_per_gas: None,
+            max_priority_fee_per_gas: None,
+            nonce: self.get_account_nonce(&deployer).await,
+            access_list: vec![],
         };
 
         let result = self.execute_transaction(tx).await?;
@@ -311,8 +832,11 @@ impl REvm {
             value,
             data: call_data,
             gas_limit,
-            gas_price: 1_000_000_000,
-            nonce: self.get_account_nonce(&caller),
+            gas_price: DEFAULT_GAS_PRICE.max(self.env.base_fee),
+            max_fee_per_gas: None,
+            max_priority_fee_per_gas: None,
+            nonce: self.get_account_nonce(&caller).await,
+            access_list: vec![],
         };
 
         self.execute_transaction(tx).await
@@ -325,39 +849,70 @@ impl REvm {
         caller: [u8; 20],
         value: u64,
         gas_limit: u64,
+    ) -> Result<ExecutionResult, RvmError> {
+        self.execute_bytecode_with_inspector(bytecode, caller, value, gas_limit, &mut NoopInspector).await
+    }
+
+    /// Execute EVM bytecode directly, reporting execution to an inspector
+    pub async fn execute_bytecode_with_inspector<I: Inspector>(
+        &mut self,
+        bytecode: &[u8],
+        caller: [u8; 20],
+        value: u64,
+        gas_limit: u64,
+        inspector: &mut I,
     ) -> Result<ExecutionResult, RvmError> {
         let env = ExecutionEnvironment {
             contract_address: [0u8; 20],
             caller,
             value,
-            gas_price: 1_000_000_000,
+            input: Vec::new(),
+            gas_price: DEFAULT_GAS_PRICE,
             block_number: self.env.block_number,
             timestamp: self.env.timestamp,
+            chain_id: self.env.chain_id,
+            coinbase: self.env.coinbase,
         };
 
-        self.runtime.execute(bytecode, env).await
+        let storage = self.runtime.storage();
+        let mut storage = storage.write().await;
+        let mut host = EvmHost::new(&mut storage, &self.blocks);
+        let mut result = self.runtime
+            .execute_with_inspector(bytecode, env, gas_limit, &mut host, inspector)
+            .await;
+        if let Ok(result) = &mut result {
+            result.logs = storage.take_logs();
+        }
+        self.runtime.commit_state(&mut storage);
+        result
     }
 
-    /// Get account information
-    pub fn get_account(&self, address: &[u8; 20]) -> EvmAccount {
-        self.accounts.get(address).cloned().unwrap_or_else(|| EvmAccount {
-            balance: 0,
-            nonce: 0,
-            code_hash: [0u8; 32],
+    /// Get account information.
+    ///
+    /// Accounts live in the runtime's shared storage, so this and the other account
+    /// accessors are async: they wait for the storage lock.
+    pub async fn get_account(&self, address: &[u8; 20]) -> EvmAccount {
+        let storage = self.runtime.storage();
+        let storage = storage.read().await;
+        EvmAccount {
+            balance: storage.get_balance(address),
+            nonce: storage.get_nonce(address),
+            code_hash: storage.code(address).map(|code| RvmCrypto::keccak256(&code)).unwrap_or([0u8; 32]),
             storage_root: [0u8; 32],
-        })
+        }
     }
 
-    /// Get account nonce
-    pub fn get_account_nonce(&self, address: &[u8; 20]) -> u64 {
-        self.get_account(address).nonce
+    /// Get account nonce from the shared storage
+    pub async fn get_account_nonce(&self, address: &[u8; 20]) -> u64 {
+        self.runtime.storage().read().await.get_nonce(address)
     }
 
-    /// Set account balance
-    pub fn set_account_balance(&mut self, address: [u8; 20], balance: u64) {
-        let mut account = self.get_account(&address);
-        account.balance = balance;
-        self.accounts.insert(address, account);
+    /// Set account balance in the shared storage, committing it
+    pub async fn set_account_balance(&mut self, address: [u8; 20], balance: u64) {
+        let storage = self.runtime.storage();
+        let mut storage = storage.write().await;
+        storage.set_balance(address, balance);
+        self.runtime.commit_state(&mut storage);
     }
 
     /// Mine a new block
@@ -377,11 +932,13 @@ impl REvm {
             coinbase: self.env.coinbase,
             difficulty: self.env.difficulty,
             gas_limit: self.env.gas_limit,
-            gas_used: 0, // TODO: Calculate from transactions
-            transactions: self.transactions.iter().map(|tx| tx.hash).collect(),
+            gas_used: std::mem::take(&mut self.pending_gas_used),
+            base_fee: self.env.base_fee,
+            transactions: std::mem::take(&mut self.pending_transactions),
         };
 
         self.blocks.push(block.clone());
+        self.env.base_fee = block.next_base_fee();
         self.env.block_number += 1;
         self.env.timestamp += 12; // 12 second block time
         
@@ -403,27 +960,40 @@ impl REvm {
         self.env.chain_id
     }
 
-    /// Convert bytes to address
-    fn bytes_to_address(&self, bytes: &[u8]) -> [u8; 20] {
-        let mut address = [0u8; 20];
-        if bytes.len() >= 20 {
-            address.copy_from_slice(&bytes[0..20]);
-        }
-        address
+    /// Shared world state
+    pub fn storage(&self) -> Arc<RwLock<Storage>> {
+        self.runtime.storage()
+    }
+
+    /// Messages sent from this chain over the bridge
+    pub async fn bridge_outbox(&self) -> Vec<BridgeMessage> {
+        self.runtime.storage().read().await.bridge_outbox().to_vec()
+    }
+
+    /// Queue a relayed message for this chain's bridge inbox, returning the index it will
+    /// have; queued messages are delivered as part of the next valid transaction
+    pub async fn deliver_bridge_message(&mut self, message: SignedBridgeMessage) -> u64 {
+        let delivered = self.runtime.storage().read().await.bridge_inbox().len();
+        self.pending_bridge_messages.push(message);
+        (delivered + self.pending_bridge_messages.len() - 1) as u64
+    }
+
+    /// Trust a relayer key for messages from a chain
+    pub async fn add_bridge_signer(&mut self, chain_id: u64, public_key: Vec<u8>) {
+        self.runtime.storage().write().await.add_bridge_signer(chain_id, public_key);
     }
 
     /// EVM demo: Simple arithmetic computation
     pub async fn evm_demo(&mut self) -> Result<EvmResult, RvmError> {
-        // EVM bytecode: PUSH1 15, PUSH1 25, ADD, PUSH1 2, DIV, STOP
         // This computes (15 + 25) / 2 = 20
-        let bytecode = vec![
-            0x60, 0x0f, // PUSH1 15
-            0x60, 0x19, // PUSH1 25
-            0x01,       // ADD
-            0x60, 0x02, // PUSH1 2
-            0x04,       // DIV
-            0x00,       // STOP
-        ];
+        let bytecode = assemble(
+            "PUSH 15
+             PUSH 25
+             ADD
+             PUSH 2
+             DIV
+             STOP",
+        )?;
 
         let caller = [1u8; 20];
         let result = self.execute_bytecode(&bytecode, caller, 0, 100000).await?;
@@ -436,6 +1006,7 @@ impl REvm {
                 transaction_hash: RvmCrypto::keccak256(&bytecode),
                 block_number: self.env.block_number,
                 gas_used: 0,
+                effective_gas_price: 0,
                 success: true,
                 contract_address: None,
                 logs: vec![],
@@ -444,6 +1015,19 @@ impl REvm {
     }
 }
 
+/// Why a failed execution failed: the message of ABI-encoded `Error(string)` return data,
+/// otherwise the return data in hex, otherwise the execution error
+fn revert_reason(result: &ExecutionResult) -> String {
+    let data = &result.return_data;
+    if let Some(message) = decode_revert_reason(data) {
+        return format!("execution reverted: {}", message);
+    }
+    if !data.is_empty() {
+        return format!("execution reverted: 0x{}", hex::encode(data));
+    }
+    result.error.clone().unwrap_or_else(|| "execution failed".to_string())
+}
+
 impl Default for EvmEnvironment {
     fn default() -> Self {
         Self {
@@ -481,11 +1065,349 @@ mod tests {
         
         let bytecode = vec![0x60, 0x01, 0x60, 0x02, 0x01, 0x00]; // Simple ADD
         let deployer = [1u8; 20];
+        revm.set_account_balance(deployer, 100000 * DEFAULT_GAS_PRICE).await;
         
         let address = revm.deploy_contract(bytecode, deployer, 0, 100000).await.unwrap();
         assert_ne!(address, [0u8; 20]);
     }
 
+    #[tokio::test]
+    async fn test_eof_contract_deployment() {
+        use crate::{core::bytes32_to_word, eof::{TypeSection, NON_RETURNING}};
+
+        let mut revm = REvm::new(1337);
+        let deployer = [1u8; 20];
+        revm.set_account_balance(deployer, 1_000_000 * DEFAULT_GAS_PRICE).await;
+
+        // Section 0: PUSH1 1, RJUMPI +1, INVALID, DATALOADN 0, CALLF 1, PUSH1 0, MSTORE,
+        //            PUSH1 32, PUSH1 0, RETURN
+        // Section 1: doubles its input: DUP1, ADD, RETF
+        let container = EofContainer {
+            types: vec![
+                TypeSection { inputs: 0, outputs: NON_RETURNING, max_stack_height: 2 },
+                TypeSection { inputs: 1, outputs: 1, max_stack_height: 2 },
+            ],
+            code_sections: vec![
+                vec![
+                    0x60, 0x01, 0xe1, 0x00, 0x01, 0xfe, 0xd1, 0x00, 0x00, 0xe3, 0x00, 0x01,
+                    0x60, 0x00, 0x52, 0x60, 0x20, 0x60, 0x00, 0xf3,
+                ],
+                vec![0x80, 0x01, 0xe4],
+            ],
+            data: 21u64.to_be_bytes().repeat(4),
+        };
+        let address = revm.deploy_contract(container.encode(), deployer, 0, 100000).await.unwrap();
+
+        let result = revm.call_contract(address, vec![], deployer, 0, 100000).await.unwrap();
+        assert!(result.result.success, "{:?}", result.result.error);
+        assert_eq!(bytes32_to_word(&result.result.return_data.try_into().unwrap()), 42);
+
+        // Invalid containers are rejected at deployment
+        let mut invalid = container.clone();
+        invalid.code_sections[1] = vec![0x80, 0x01, 0x56];
+        let error = revm.deploy_contract(invalid.encode(), deployer, 0, 100000).await.unwrap_err();
+        assert!(matches!(error, RvmError::InvalidEofContainer(_)));
+        assert!(revm.deploy_contract(vec![0xef, 0x00, 0x02], deployer, 0, 100000).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_transaction_eip3155_trace() {
+        let mut revm = REvm::new(1337);
+        let contract = [7u8; 20];
+        revm.runtime.storage().write().await.set_code(contract, vec![0x60, 0x01, 0x60, 0x02, 0x01, 0x00]);
+        revm.set_account_balance([1u8; 20], 100000 * DEFAULT_GAS_PRICE).await;
+
+        let tx = EvmTransaction {
+            hash: [1u8; 32],
+            from: [1u8; 20],
+            to: Some(contract),
+            value: 0,
+            data: vec![],
+            gas_limit: 100000,
+            gas_price: DEFAULT_GAS_PRICE,
+            max_fee_per_gas: None,
+            max_priority_fee_per_gas: None,
+            nonce: 0,
+            access_list: vec![],
+        };
+        let mut tracer = crate::tracer::Eip3155Tracer::new(Vec::new());
+        let result = revm.execute_transaction_with_inspector(tx, &mut tracer).await.unwrap();
+        tracer.finish(&result.result).unwrap();
+
+        let output = String::from_utf8(tracer.into_inner()).unwrap();
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(lines.len(), 5);
+        // Execution starts with the gas left after the 21000 intrinsic gas
+        assert!(lines[0].starts_with(r#"{"pc":0,"op":96,"gas":"0x13498","gasCost":"0x3""#));
+        assert!(lines[4].contains(r#""gasUsed":"0x5211""#));
+    }
+
+    #[tokio::test]
+    async fn test_transaction_call_trace() {
+        let mut revm = REvm::new(1337);
+        let contract = [7u8; 20];
+        let callee = crate::core::word_to_address(0x42);
+
+        // Callee: REVERT with Error("nope"), written byte by byte with MSTORE8
+        let mut callee_code = Vec::new();
+        let payload = [
+            (0, 0x08), (1, 0xc3), (2, 0x79), (3, 0xa0), (35, 0x20), (67, 4),
+            (68, b'n'), (69, b'o'), (70, b'p'), (71, b'e'),
+        ];
+        for (offset, byte) in payload {
+            callee_code.extend_from_slice(&[0x60, byte, 0x60, offset, 0x53]);
+        }
+        callee_code.extend_from_slice(&[0x60, 100, 0x60, 0x00, 0xfd]);
+
+        // Caller: CALL(gas=50000, to=0x42, value=0, in=0/0, out=0/0), STOP
+        let caller_code = vec![
+            0x60, 0x00, 0x60, 0x00, 0x60, 0x00, 0x60, 0x00, 0x60, 0x00,
+            0x60, 0x42, 0x61, 0xc3, 0x50, 0xf1, 0x00,
+        ];
+        {
+            let storage = revm.runtime.storage();
+            let mut storage = storage.write().await;
+            storage.set_code(contract, caller_code);
+            storage.set_code(callee, callee_code);
+        }
+        revm.set_account_balance([1u8; 20], 100000 * DEFAULT_GAS_PRICE).await;
+
+        let tx = EvmTransaction {
+            hash: [1u8; 32],
+            from: [1u8; 20],
+            to: Some(contract),
+            value: 0,
+            data: vec![0xab],
+            gas_limit: 100000,
+            gas_price: DEFAULT_GAS_PRICE,
+            max_fee_per_gas: None,
+            max_priority_fee_per_gas: None,
+            nonce: 0,
+            access_list: vec![],
+        };
+        let (result, root) = revm.trace_transaction(tx).await.unwrap();
+        assert!(result.result.success);
+
+        assert_eq!(root.call_type, "CALL");
+        assert_eq!(root.from, format!("0x{}", "01".repeat(20)));
+        assert_eq!(root.input, "0xab");
+        assert_eq!(root.gas, "0x186a0");
+        assert_eq!(root.gas_used, format!("{:#x}", result.result.gas_used));
+        assert!(root.error.is_none());
+        assert_eq!(root.calls.len(), 1);
+
+        let call = &root.calls[0];
+        assert_eq!(call.to, format!("0x{}", hex::encode(callee)));
+        assert!(call.error.is_some());
+        assert_eq!(call.revert_reason.as_deref(), Some("nope"));
+
+        let json = serde_json::to_value(&root).unwrap();
+        assert_eq!(json["type"], "CALL");
+        assert_eq!(json["calls"][0]["revertReason"], "nope");
+        assert!(json.get("output").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_transaction_validation_and_fees() {
+        let mut revm = REvm::new(1337);
+        revm.env.coinbase = [0xcb; 20];
+        revm.env.base_fee = 1;
+        let sender = [1u8; 20];
+        let contract = [7u8; 20];
+        let reverter = [8u8; 20];
+        {
+            let storage = revm.runtime.storage();
+            let mut storage = storage.write().await;
+            storage.set_code(contract, vec![0x60, 0x01, 0x60, 0x02, 0x01, 0x00]);
+            storage.set_code(reverter, vec![0x60, 0x00, 0x60, 0x00, 0xfd]);
+        }
+        revm.set_account_balance(sender, 1_000_000).await;
+        let tx = |to, value, data, gas_limit, nonce| EvmTransaction {
+            hash: [0u8; 32],
+            from: sender,
+            to,
+            value,
+            data,
+            gas_limit,
+            gas_price: 2,
+            max_fee_per_gas: None,
+            max_priority_fee_per_gas: None,
+            nonce,
+            access_list: vec![],
+        };
+
+        // 21000 + 4 (zero byte) + 16 (non-zero byte) intrinsic, 9 execution
+        let result = revm.execute_transaction(tx(Some(contract), 500, vec![0, 1], 50_000, 0)).await.unwrap();
+        assert!(result.result.success);
+        assert_eq!(result.receipt.gas_used, 21029);
+        let balance = 1_000_000 - 500 - 21029 * 2;
+        assert_eq!(revm.get_account(&sender).await.balance, balance);
+        assert_eq!(revm.get_account(&contract).await.balance, 500);
+        // Half the price is burned as base fee, the other half tipped
+        assert_eq!(revm.get_account(&[0xcb; 20]).await.balance, 21029);
+        assert_eq!(revm.get_account_nonce(&sender).await, 1);
+
+        // Invalid transactions are rejected without touching state
+        let error = revm.execute_transaction(tx(Some(contract), 0, vec![0, 1], 21019, 1)).await.unwrap_err();
+        assert!(matches!(error, RvmError::IntrinsicGasTooLow { gas_limit: 21019, intrinsic: 21020 }));
+        let error = revm.execute_transaction(tx(Some(contract), 0, vec![], 50_000, 0)).await.unwrap_err();
+        assert!(matches!(error, RvmError::InvalidNonce { expected: 1, got: 0 }));
+        let error = revm.execute_transaction(tx(Some(contract), 1, vec![], 500_000, 1)).await.unwrap_err();
+        assert!(matches!(error, RvmError::InsufficientBalance { required: 1_000_001, .. }));
+        assert_eq!(revm.get_account(&sender).await.balance, balance);
+        assert_eq!(revm.get_account_nonce(&sender).await, 1);
+
+        // A reverted call keeps its value but still pays for gas
+        let result = revm.execute_transaction(tx(Some(reverter), 100, vec![], 50_000, 1)).await.unwrap();
+        assert!(!result.result.success);
+        assert_eq!(result.receipt.gas_used, 21006);
+        let balance = balance - 21006 * 2;
+        assert_eq!(revm.get_account(&sender).await.balance, balance);
+        assert_eq!(revm.get_account(&reverter).await.balance, 0);
+
+        // Creation pays 32000 and 2 per initcode word on top of calldata
+        let code = vec![0x60, 0x01, 0x00];
+        let result = revm.execute_transaction(tx(None, 0, code.clone(), 60_000, 2)).await.unwrap();
+        assert_eq!(result.receipt.gas_used, 21000 + 16 + 16 + 4 + 32000 + 2);
+        let address = result.receipt.contract_address.unwrap();
+        assert_eq!(address, RvmCrypto::create_address(&sender, 2));
+        assert_eq!(revm.get_account(&address).await.code_hash, RvmCrypto::keccak256(&code));
+        assert_eq!(revm.get_account_nonce(&sender).await, 3);
+
+        // A value transfer the recipient cannot hold fails the transaction, which still pays for gas
+        let rich = [0x44; 20];
+        revm.set_account_balance(rich, u64::MAX).await;
+        let balance = revm.get_account(&sender).await.balance;
+        let result = revm.execute_transaction(tx(Some(rich), 100, vec![], 50_000, 3)).await.unwrap();
+        assert!(!result.result.success);
+        assert_eq!(result.receipt.gas_used, 21000);
+        assert_eq!(revm.get_account(&sender).await.balance, balance - 21000 * 2);
+        assert_eq!(revm.get_account(&rich).await.balance, u64::MAX);
+        assert_eq!(revm.get_account_nonce(&sender).await, 4);
+    }
+
+    #[tokio::test]
+    async fn test_storage_refunds() {
+        let mut revm = REvm::new(1337);
+        let sender = [1u8; 20];
+        let (clearer, restorer, caller) = ([7u8; 20], [8u8; 20], [9u8; 20]);
+        let callee = crate::core::word_to_address(0x43);
+        {
+            let storage = revm.runtime.storage();
+            let mut storage = storage.write().await;
+            // SSTORE(0, 0) on a slot holding 5
+            storage.set_code(clearer, vec![0x60, 0x00, 0x60, 0x00, 0x55, 0x00]);
+            storage.set_storage(clearer, 0, 5);
+            // SSTORE(1, 7), SSTORE(1, 0)
+            storage.set_code(restorer, vec![0x60, 0x07, 0x60, 0x01, 0x55, 0x60, 0x00, 0x60, 0x01, 0x55, 0x00]);
+            // SSTORE(0, 0) on a slot holding 5, then REVERT(0, 0)
+            storage.set_code(callee, vec![0x60, 0x00, 0x60, 0x00, 0x55, 0x60, 0x00, 0x60, 0x00, 0xfd]);
+            storage.set_storage(callee, 0, 5);
+            // CALL(gas=50000, to=0x43, value=0, in=0/0, out=0/0)
+            storage.set_code(caller, vec![
+                0x60, 0x00, 0x60, 0x00, 0x60, 0x00, 0x60, 0x00, 0x60, 0x00,
+                0x60, 0x43, 0x61, 0xc3, 0x50, 0xf1, 0x00,
+            ]);
+            storage.commit();
+        }
+        revm.set_account_balance(sender, u64::MAX / 2).await;
+        let base_fee = revm.env.base_fee;
+        let tx = |to, nonce| EvmTransaction {
+            hash: [0u8; 32],
+            from: sender,
+            to: Some(to),
+            value: 0,
+            data: vec![],
+            gas_limit: 100_000,
+            gas_price: base_fee,
+            max_fee_per_gas: None,
+            max_priority_fee_per_gas: None,
+            nonce,
+            access_list: vec![],
+        };
+
+        // Clearing a slot refunds 4800, capped at a fifth of the 21000 + 6 + 2900 used
+        let result = revm.execute_transaction(tx(clearer, 0)).await.unwrap();
+        assert_eq!(result.result.gas_refunded, 4800);
+        assert_eq!(result.receipt.gas_used, 23906 - 23906 / 5);
+
+        // Restoring a fresh slot refunds all but the read cost of setting it, under the cap
+        let result = revm.execute_transaction(tx(restorer, 1)).await.unwrap();
+        assert_eq!(result.result.gas_refunded, 19900);
+        let used = 21000 + 6 + 20000 + 6 + 100;
+        assert_eq!(result.receipt.gas_used, used - used / 5);
+
+        // A reverted frame earns no refund
+        let result = revm.execute_transaction(tx(caller, 2)).await.unwrap();
+        assert!(result.result.success);
+        assert_eq!(result.result.gas_refunded, 0);
+    }
+
+    #[tokio::test]
+    async fn test_fee_market() {
+        let mut revm = REvm::new(1337);
+        revm.env.coinbase = [0xcb; 20];
+        revm.env.base_fee = 1000;
+        revm.env.gas_limit = 100_000;
+        let sender = [1u8; 20];
+        revm.set_account_balance(sender, 1_000_000_000).await;
+        let tx = |gas_limit, gas_price, max_fee, priority_fee, nonce| EvmTransaction {
+            hash: [nonce as u8; 32],
+            from: sender,
+            to: Some([5u8; 20]),
+            value: 0,
+            data: vec![],
+            gas_limit,
+            gas_price,
+            max_fee_per_gas: max_fee,
+            max_priority_fee_per_gas: priority_fee,
+            nonce,
+            access_list: vec![],
+        };
+
+        // Base fee plus tip, then capped at the max fee
+        let result = revm.execute_transaction(tx(21000, 0, Some(1500), Some(200), 0)).await.unwrap();
+        assert_eq!(result.receipt.effective_gas_price, 1200);
+        let result = revm.execute_transaction(tx(21000, 0, Some(1100), Some(200), 1)).await.unwrap();
+        assert_eq!(result.receipt.effective_gas_price, 1100);
+        assert_eq!(revm.get_account(&sender).await.balance, 1_000_000_000 - 21000 * 1200 - 21000 * 1100);
+        assert_eq!(revm.get_account(&[0xcb; 20]).await.balance, 21000 * 200 + 21000 * 100);
+
+        let error = revm.execute_transaction(tx(21000, 0, Some(999), None, 2)).await.unwrap_err();
+        assert!(matches!(error, RvmError::FeeCapTooLow { max_fee: 999, base_fee: 1000 }));
+        let error = revm.execute_transaction(tx(21000, 999, None, None, 2)).await.unwrap_err();
+        assert!(matches!(error, RvmError::FeeCapTooLow { max_fee: 999, base_fee: 1000 }));
+        let error = revm.execute_transaction(tx(21000, 0, Some(1500), Some(1501), 2)).await.unwrap_err();
+        assert!(matches!(error, RvmError::InvalidEvmTransaction(_)));
+        let error = revm.execute_transaction(tx(21000, 1500, None, Some(200), 2)).await.unwrap_err();
+        assert!(matches!(error, RvmError::InvalidEvmTransaction(_)));
+        // A huge tip is capped at the max fee instead of overflowing
+        assert_eq!(tx(21000, 0, Some(u64::MAX), Some(u64::MAX), 2).effective_gas_price(1000).unwrap(), u64::MAX);
+        // 42000 of the 100000 block gas is used
+        let error = revm.execute_transaction(tx(58_001, 1000, None, None, 2)).await.unwrap_err();
+        assert!(matches!(error, RvmError::InvalidEvmTransaction(_)));
+
+        // Below the 50000 target the base fee falls: by 8000/50000/8 of itself, then by 1/8
+        let block = revm.mine_block();
+        assert_eq!((block.gas_used, block.base_fee, block.transactions.len()), (42000, 1000, 2));
+        assert_eq!(revm.env.base_fee, 980);
+        let empty = revm.mine_block();
+        assert!(empty.transactions.is_empty());
+        assert_eq!(revm.env.base_fee, 858);
+
+        // Above it the base fee rises, by at least 1
+        assert_eq!(EvmBlock { gas_used: 100_000, base_fee: 1000, ..block.clone() }.next_base_fee(), 1125);
+        assert_eq!(EvmBlock { gas_used: 50_001, base_fee: 7, ..block }.next_base_fee(), 8);
+
+        // A tip the coinbase cannot hold rejects the transaction without touching state
+        revm.set_account_balance([0xcb; 20], u64::MAX).await;
+        let balance = revm.get_account(&sender).await.balance;
+        let error = revm.execute_transaction(tx(21000, 0, Some(1500), Some(200), 2)).await.unwrap_err();
+        assert!(matches!(error, RvmError::InvalidEvmTransaction(_)));
+        assert_eq!(revm.get_account(&sender).await.balance, balance);
+        assert_eq!(revm.get_account_nonce(&sender).await, 2);
+    }
+
     #[test]
     fn test_block_mining() {
         let mut revm = REvm::new(1337);
@@ -496,4 +1418,108 @@ mod tests {
         assert_eq!(block.number, initial_block);
         assert_eq!(revm.block_number(), initial_block + 1);
     }
+
+    #[tokio::test]
+    async fn test_estimate_gas() {
+        use crate::core::{address_to_word, word_to_address, word_to_bytes32};
+
+        let mut revm = REvm::new(1);
+        let user = [9u8; 20];
+        let (parent, child, reverter) = (word_to_address(0xa1), word_to_address(0xb2), word_to_address(0xc3));
+        {
+            let storage = revm.storage();
+            let mut storage = storage.write().await;
+            storage.set_code(child, assemble(
+                "PUSH 1
+                 PUSH 0
+                 SSTORE
+                 PUSH 200
+                 loop: JUMPDEST
+                 PUSH 1
+                 SUB
+                 DUP1
+                 PUSH loop
+                 JUMPI
+                 STOP"
+            ).unwrap());
+            // Forward all gas to the child and revert if it fails
+            storage.set_code(parent, assemble(&format!(
+                "PUSH 0
+                 PUSH 0
+                 PUSH 0
+                 PUSH 0
+                 PUSH 0
+                 PUSH {}
+                 GAS
+                 CALL
+                 PUSH ok
+                 JUMPI
+                 PUSH 0
+                 PUSH 0
+                 REVERT
+                 ok: JUMPDEST
+                 STOP",
+                address_to_word(&child)
+            )).unwrap());
+            storage.set_code(reverter, assemble("PUSH 42\nPUSH 0\nMSTORE\nPUSH 32\nPUSH 0\nREVERT").unwrap());
+        }
+        let tx = |to, gas_limit, nonce| EvmTransaction {
+            hash: [nonce as u8; 32],
+            from: user,
+            to: Some(to),
+            value: 0,
+            data: vec![],
+            gas_limit,
+            gas_price: DEFAULT_GAS_PRICE,
+            max_fee_per_gas: None,
+            max_priority_fee_per_gas: None,
+            nonce,
+            access_list: vec![],
+        };
+
+        // Estimation buys gas, so it is capped by the sender's funds
+        let error = revm.estimate_gas(&tx(parent, 0, 0)).await.unwrap_err();
+        assert!(matches!(error, RvmError::InsufficientBalance { available: 0, .. }));
+
+        // It leaves no trace and is not counted as an execution
+        revm.set_account_balance(user, 1_000_000 * DEFAULT_GAS_PRICE).await;
+        let executions = revm.runtime.get_stats().total_executions;
+        let estimate = revm.estimate_gas(&tx(parent, 0, 0)).await.unwrap();
+        assert_eq!(revm.storage().read().await.get_storage(&child, 0), 0);
+        assert_eq!(revm.get_account_nonce(&user).await, 0);
+        assert_eq!(revm.get_account(&user).await.balance, 1_000_000 * DEFAULT_GAS_PRICE);
+        assert_eq!(revm.runtime.get_stats().total_executions, executions);
+
+        // The estimate is the smallest limit that succeeds, above the gas used because
+        // the parent keeps 1/64 of its gas back from the call
+        let result = revm.execute_transaction(tx(parent, estimate - 1, 0)).await.unwrap();
+        assert!(!result.result.success);
+        let result = revm.execute_transaction(tx(parent, estimate, 1)).await.unwrap();
+        assert!(result.result.success, "{:?}", result.result.error);
+        assert!(estimate > result.result.gas_used);
+
+        // No limit works for a reverting call; the revert data is reported
+        match revm.estimate_gas(&tx(reverter, 0, 2)).await {
+            Err(RvmError::GasEstimationFailed(reason)) => assert!(reason.ends_with("2a"), "{}", reason),
+            other => panic!("unexpected estimate: {:?}", other),
+        }
+
+        // Error(string) revert data is decoded
+        let mut data = vec![0x08, 0xc3, 0x79, 0xa0];
+        data.extend(word_to_bytes32(32));
+        data.extend(word_to_bytes32(5));
+        data.extend(*b"nope!");
+        data.resize(100, 0);
+        let result = ExecutionResult {
+            return_data: data,
+            gas_used: 0,
+            gas_refunded: 0,
+            success: false,
+            error: Some("Execution reverted".to_string()),
+            agent_witness: None,
+            fee_receipt: None,
+            logs: Vec::new(),
+        };
+        assert_eq!(revert_reason(&result), "execution reverted: nope!");
+    }
 }
@@ -3,11 +3,23 @@
 //! Plugin-friendly runtime with hooks for storage, crypto, and agent APIs.
 
 use crate::{
+    bridge::register_bridge_opcodes,
+    l2::register_l2_opcodes,
+    agents::{register_agent_opcodes, AgentEnabledHost, AgentHost, AgentWitness, RecordingAgentHost, ReplayAgentHost},
     core::{RvmCore, ExecutionEnvironment, ExecutionResult, Contract},
+    asm::assemble,
+    bytecode::CodeCache,
     crypto::{RvmCrypto, Precompiles},
+    custom_opcodes::{OpcodeHandler, OpcodeRegistry},
     error::RvmError,
-    gas::GasMeter,
+    gas::{GasMeter, GasMetering},
+    ghostchain_services::{GhostChainHost, GhostChainServices},
+    host::Host,
+    inspector::{Inspector, NoopInspector},
+    opcodes::Opcode,
+    profiler::GasProfiler,
     storage::Storage,
+    validator::ensure_valid,
 };
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
@@ -21,12 +33,18 @@ pub struct RuntimeConfig {
     pub max_gas_limit: u64,
     /// Enable precompiled contracts
     pub enable_precompiles: bool,
-    /// Enable agent API hooks
+    /// Expose the agent host to the AGENT_* opcodes
     pub enable_agent_apis: bool,
     /// Enable crypto hooks
     pub enable_crypto_hooks: bool,
     /// Debug mode
     pub debug_mode: bool,
+    /// Reject deployments that fail static bytecode validation
+    #[serde(default)]
+    pub validate_bytecode: bool,
+    /// Chain id seen by deployed contracts
+    #[serde(default)]
+    pub chain_id: u64,
 }
 
 /// Runtime hooks for extending functionality
@@ -36,20 +54,32 @@ pub struct RuntimeHooks {
     pub storage_hooks: Vec<fn(&mut Storage) -> Result<(), RvmError>>,
     /// Crypto hooks
     pub crypto_hooks: Vec<fn(&[u8]) -> Result<Vec<u8>, RvmError>>,
-    /// Agent API hooks
-    pub agent_hooks: Vec<fn(&str, &[u8]) -> Result<Vec<u8>, RvmError>>,
 }
 
-/// RVM Runtime with plugin support
-pub struct RvmRuntime {
+/// RVM Runtime with plugin support, metering gas with `G`
+pub struct RvmRuntime<G = GasMeter> {
     /// Core VM instances (pool for concurrent execution)
-    core_pool: Vec<RvmCore>,
+    core_pool: Vec<RvmCore<G>>,
+    /// Meter new cores are metered like
+    gas_meter: G,
+    /// Decoded code shared by all cores
+    code_cache: Arc<CodeCache>,
+    /// GhostChain opcode handlers shared by all cores
+    opcode_registry: Arc<OpcodeRegistry>,
     /// Shared storage
     storage: Arc<RwLock<Storage>>,
     /// Runtime configuration
     config: RuntimeConfig,
     /// Runtime hooks
     hooks: RuntimeHooks,
+    /// GhostID and CNS services served to the GhostChain opcodes
+    ghostchain_services: Option<GhostChainServices>,
+    /// Agents served to the AGENT_* opcodes
+    agent_host: Option<Box<dyn AgentHost>>,
+    /// Attach a witness of agent responses to execution results
+    record_agent_witness: bool,
+    /// Witness answering agent requests of the next execution
+    replay_witness: Option<AgentWitness>,
     /// Deployed contracts
     contracts: HashMap<[u8; 20], Contract>,
     /// Execution statistics
@@ -71,17 +101,6 @@ pub struct ExecutionStats {
     pub avg_gas_per_execution: u64,
 }
 
-/// Agent API call information
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct AgentCall {
-    /// Agent function name
-    pub function: String,
-    /// Call parameters
-    pub params: Vec<u8>,
-    /// Expected return type
-    pub return_type: String,
-}
-
 /// Contract deployment request
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DeploymentRequest {
@@ -98,48 +117,208 @@ pub struct DeploymentRequest {
 impl RvmRuntime {
     /// Create a new runtime instance
     pub fn new(config: RuntimeConfig) -> Self {
+        let gas_meter = GasMeter::new(config.max_gas_limit);
+        Self::with_gas_meter(config, gas_meter)
+    }
+}
+
+impl<G: GasMetering> RvmRuntime<G> {
+    /// Create a new runtime instance whose executions are metered like `gas_meter`
+    pub fn with_gas_meter(config: RuntimeConfig, gas_meter: G) -> Self {
+        let code_cache = Arc::new(CodeCache::new());
+        let mut opcode_registry = OpcodeRegistry::new();
+        register_l2_opcodes(&mut opcode_registry).expect("L2 opcodes are custom opcodes");
+        register_bridge_opcodes(&mut opcode_registry).expect("bridge opcodes are custom opcodes");
+        let opcode_registry = Arc::new(opcode_registry);
         let mut core_pool = Vec::new();
         for _ in 0..4 { // Create a pool of 4 cores
-            core_pool.push(RvmCore::new(config.max_gas_limit));
+            core_pool.push(
+                RvmCore::with_gas_meter(gas_meter.child(config.max_gas_limit), Arc::clone(&code_cache))
+                    .with_opcode_registry(Arc::clone(&opcode_registry)),
+            );
         }
 
         Self {
             core_pool,
+            gas_meter,
+            code_cache,
+            opcode_registry,
             storage: Arc::new(RwLock::new(Storage::new())),
             config,
             hooks: RuntimeHooks {
                 storage_hooks: Vec::new(),
                 crypto_hooks: Vec::new(),
-                agent_hooks: Vec::new(),
             },
+            ghostchain_services: None,
+            agent_host: None,
+            record_agent_witness: false,
+            replay_witness: None,
             contracts: HashMap::new(),
             stats: ExecutionStats::default(),
         }
     }
 
+    /// Create a core sharing the runtime's code cache and opcode handlers
+    fn new_core(&self) -> RvmCore<G> {
+        RvmCore::with_gas_meter(self.gas_meter.child(self.config.max_gas_limit), Arc::clone(&self.code_cache))
+            .with_opcode_registry(Arc::clone(&self.opcode_registry))
+    }
+
+    /// Bind a handler to a GhostChain opcode for all subsequent executions
+    pub fn register_opcode<H: OpcodeHandler + 'static>(&mut self, opcode: Opcode, handler: H) -> Result<(), RvmError> {
+        Arc::make_mut(&mut self.opcode_registry).register(opcode, handler)?;
+        for core in &mut self.core_pool {
+            core.opcode_registry = Arc::clone(&self.opcode_registry);
+        }
+        Ok(())
+    }
+
+    /// Serve the GhostID, CNS and TOKEN opcodes, with GhostChain services for the former
+    pub fn set_ghostchain_services(&mut self, services: GhostChainServices) -> Result<(), RvmError> {
+        GhostChainServices::register_opcodes(Arc::make_mut(&mut self.opcode_registry))?;
+        for core in &mut self.core_pool {
+            core.opcode_registry = Arc::clone(&self.opcode_registry);
+        }
+        self.ghostchain_services = Some(services);
+        Ok(())
+    }
+
+    /// GhostChain services, if set
+    pub fn ghostchain_services(&mut self) -> Option<&mut GhostChainServices> {
+        self.ghostchain_services.as_mut()
+    }
+
+    /// Make the changes of past executions to `storage` and the GhostChain services permanent
+    pub fn commit_state(&mut self, storage: &mut Storage) {
+        storage.commit();
+        if let Some(services) = &mut self.ghostchain_services {
+            services.commit();
+        }
+    }
+
+    /// Serve the AGENT_* opcodes from an agent host
+    pub fn set_agent_host<A: AgentHost + 'static>(&mut self, agents: A) -> Result<(), RvmError> {
+        self.enable_agent_opcodes()?;
+        self.agent_host = Some(Box::new(agents));
+        Ok(())
+    }
+
+    /// Record agent responses into a witness attached to each execution result
+    pub fn set_record_agent_witness(&mut self, enabled: bool) {
+        self.record_agent_witness = enabled;
+    }
+
+    /// Execute bytecode answering agent requests only from a recorded witness
+    pub async fn execute_replaying(
+        &mut self,
+        bytecode: &[u8],
+        env: ExecutionEnvironment,
+        witness: AgentWitness,
+    ) -> Result<ExecutionResult, RvmError> {
+        self.enable_agent_opcodes()?;
+        self.replay_witness = Some(witness);
+        let result = self.execute(bytecode, env).await;
+        self.replay_witness = None;
+        result
+    }
+
+    /// Bind the AGENT_* opcode handlers
+    fn enable_agent_opcodes(&mut self) -> Result<(), RvmError> {
+        register_agent_opcodes(Arc::make_mut(&mut self.opcode_registry))?;
+        for core in &mut self.core_pool {
+            core.opcode_registry = Arc::clone(&self.opcode_registry);
+        }
+        Ok(())
+    }
+
+    /// GhostChain opcode handlers
+    pub fn opcode_registry(&self) -> &Arc<OpcodeRegistry> {
+        &self.opcode_registry
+    }
+
     /// Execute bytecode with the runtime
     pub async fn execute(
         &mut self,
         bytecode: &[u8],
         env: ExecutionEnvironment,
     ) -> Result<ExecutionResult, RvmError> {
-        // Get a core from the pool
-        let mut core = self.core_pool.pop().unwrap_or_else(|| RvmCore::new(self.config.max_gas_limit));
-        
-        // Set up storage
-        {
-            let storage = self.storage.read().await;
-            core.storage = storage.clone();
-        }
+        let gas_limit = self.config.max_gas_limit;
+        self.execute_with_gas_limit(bytecode, env, gas_limit).await
+    }
+
+    /// Execute bytecode, reporting execution to an inspector
+    pub async fn execute_inspected<I: Inspector>(
+        &mut self,
+        bytecode: &[u8],
+        env: ExecutionEnvironment,
+        inspector: &mut I,
+    ) -> Result<ExecutionResult, RvmError> {
+        let gas_limit = self.config.max_gas_limit;
+        self.execute_against_storage(bytecode, env, gas_limit, inspector).await
+    }
 
-        // Execute with hooks
-        let result = self.execute_with_hooks(&mut core, bytecode, env).await;
+    /// Execute bytecode and collect a gas profile
+    pub async fn profile(
+        &mut self,
+        bytecode: &[u8],
+        env: ExecutionEnvironment,
+    ) -> Result<(ExecutionResult, GasProfiler), RvmError> {
+        let mut profiler = GasProfiler::new();
+        let result = self.execute_inspected(bytecode, env, &mut profiler).await?;
+        Ok((result, profiler))
+    }
 
-        // Update storage
-        {
-            let mut storage = self.storage.write().await;
-            *storage = core.storage.clone();
+    /// Execute bytecode against the shared storage with an explicit gas limit
+    async fn execute_with_gas_limit(
+        &mut self,
+        bytecode: &[u8],
+        env: ExecutionEnvironment,
+        gas_limit: u64,
+    ) -> Result<ExecutionResult, RvmError> {
+        self.execute_against_storage(bytecode, env, gas_limit, &mut NoopInspector).await
+    }
+
+    /// Execute bytecode against the shared storage and commit the changes
+    async fn execute_against_storage<I: Inspector>(
+        &mut self,
+        bytecode: &[u8],
+        env: ExecutionEnvironment,
+        gas_limit: u64,
+        inspector: &mut I,
+    ) -> Result<ExecutionResult, RvmError> {
+        let storage = Arc::clone(&self.storage);
+        let mut storage = storage.write().await;
+
+        let mut result = self.execute_with_inspector(bytecode, env, gas_limit, &mut *storage, inspector).await;
+        if let Ok(result) = &mut result {
+            result.logs = storage.take_logs();
         }
+        self.commit_state(&mut storage);
+
+        result
+    }
+
+    /// Execute bytecode against a caller-provided host
+    pub async fn execute_with_host(
+        &mut self,
+        bytecode: &[u8],
+        env: ExecutionEnvironment,
+        gas_limit: u64,
+        host: &mut dyn Host,
+    ) -> Result<ExecutionResult, RvmError> {
+        self.execute_with_inspector(bytecode, env, gas_limit, host, &mut NoopInspector).await
+    }
+
+    /// Execute bytecode against a caller-provided host, reporting execution to an inspector
+    pub async fn execute_with_inspector<I: Inspector>(
+        &mut self,
+        bytecode: &[u8],
+        env: ExecutionEnvironment,
+        gas_limit: u64,
+        host: &mut dyn Host,
+        inspector: &mut I,
+    ) -> Result<ExecutionResult, RvmError> {
+        let result = self.execute_unrecorded(bytecode, env, gas_limit, host, inspector).await;
 
         // Update statistics
         match &result {
@@ -153,10 +332,43 @@ impl RvmRuntime {
             }
         }
 
+        result
+    }
+
+    /// Execute like `execute_with_inspector`, without counting the execution in the
+    /// statistics, for simulations
+    pub(crate) async fn execute_unrecorded<I: Inspector>(
+        &mut self,
+        bytecode: &[u8],
+        env: ExecutionEnvironment,
+        gas_limit: u64,
+        host: &mut dyn Host,
+        inspector: &mut I,
+    ) -> Result<ExecutionResult, RvmError> {
+        // Get a core from the pool
+        let mut core = self.core_pool.pop().unwrap_or_else(|| self.new_core());
+        core.gas.begin(gas_limit.min(self.config.max_gas_limit), env.caller, host);
+        let (payer, validator) = (env.caller, env.coinbase);
+
+        // Execute with hooks, then settle token fees for the gas used; an execution whose
+        // fees cannot be settled is undone
+        let checkpoint = host.checkpoint();
+        let result = match self.execute_with_hooks(&mut core, bytecode, env, host, inspector).await {
+            Ok(mut result) => core.gas.settle(payer, validator, host).map(|receipt| {
+                result.fee_receipt = receipt;
+                result
+            }),
+            Err(e) => Err(e),
+        };
+        if result.is_err() {
+            host.revert_to(checkpoint);
+        }
+
         // Return core to pool
         if self.core_pool.len() < 4 {
             core.pc = 0;
             core.stack.clear();
+            core.memory.clear();
             self.core_pool.push(core);
         }
 
@@ -164,11 +376,13 @@ impl RvmRuntime {
     }
 
     /// Execute with runtime hooks
-    async fn execute_with_hooks(
+    async fn execute_with_hooks<I: Inspector>(
         &mut self,
-        core: &mut RvmCore,
+        core: &mut RvmCore<G>,
         bytecode: &[u8],
         env: ExecutionEnvironment,
+        host: &mut dyn Host,
+        inspector: &mut I,
     ) -> Result<ExecutionResult, RvmError> {
         // Pre-execution hooks
         if self.config.enable_crypto_hooks {
@@ -177,35 +391,55 @@ impl RvmRuntime {
             }
         }
 
-        // Execute the bytecode
-        let mut result = core.execute(bytecode, env).await?;
+        // Execute the bytecode, with GhostChain services if set
+        let mut services = self.ghostchain_services.take();
+        let result = match services.as_mut() {
+            Some(services) => {
+                let mut host = GhostChainHost::new(host, services);
+                self.execute_with_agents(core, bytecode, env, &mut host, inspector).await
+            }
+            None => self.execute_with_agents(core, bytecode, env, host, inspector).await,
+        };
+        self.ghostchain_services = services;
+        result
+    }
 
-        // Post-execution hooks
-        if self.config.enable_agent_apis {
-            result = self.process_agent_calls(result).await?;
+    /// Execute with the agent host if enabled
+    async fn execute_with_agents<I: Inspector>(
+        &mut self,
+        core: &mut RvmCore<G>,
+        bytecode: &[u8],
+        env: ExecutionEnvironment,
+        host: &mut dyn Host,
+        inspector: &mut I,
+    ) -> Result<ExecutionResult, RvmError> {
+        if let Some(witness) = self.replay_witness.take() {
+            let mut agents = ReplayAgentHost::new(witness);
+            let mut host = AgentEnabledHost::new(host, &mut agents);
+            return core.execute_with_inspector(bytecode, env, &mut host, inspector).await;
         }
-
-        Ok(result)
-    }
-
-    /// Process agent API calls
-    async fn process_agent_calls(&mut self, mut result: ExecutionResult) -> Result<ExecutionResult, RvmError> {
-        // Check if return data contains agent call markers
-        if result.return_data.starts_with(b"AGENT_CALL:") {
-            let call_data = &result.return_data[11..]; // Skip "AGENT_CALL:" prefix
-            
-            if let Ok(agent_call) = serde_json::from_slice::<AgentCall>(call_data) {
-                // Execute agent hook
-                for hook in &self.hooks.agent_hooks {
-                    if let Ok(agent_result) = hook(&agent_call.function, &agent_call.params) {
-                        result.return_data = agent_result;
-                        break;
-                    }
+        match self.agent_host.as_deref_mut() {
+            Some(agents) if self.config.enable_agent_apis && self.record_agent_witness => {
+                let mut recorder = RecordingAgentHost::new(&mut *agents);
+                let mut result = {
+                    let mut host = AgentEnabledHost::new(host, &mut recorder);
+                    core.execute_with_inspector(bytecode, env, &mut host, inspector).await
+                };
+                let witness = recorder.into_witness();
+                agents.commit();
+                if let Ok(result) = &mut result {
+                    result.agent_witness = Some(witness);
                 }
+                result
             }
+            Some(agents) if self.config.enable_agent_apis => {
+                let mut host = AgentEnabledHost::new(host, &mut *agents);
+                let result = core.execute_with_inspector(bytecode, env, &mut host, inspector).await;
+                agents.commit();
+                result
+            }
+            _ => core.execute_with_inspector(bytecode, env, host, inspector).await,
         }
-
-        Ok(result)
     }
 
     /// Deploy a contract
@@ -214,36 +448,45 @@ impl RvmRuntime {
         request: DeploymentRequest,
         deployer: [u8; 20],
     ) -> Result<[u8; 20], RvmError> {
+        if self.config.validate_bytecode {
+            ensure_valid(&request.bytecode)?;
+        }
+
         // Create deployment environment
         let env = ExecutionEnvironment {
             contract_address: [0u8; 20], // Will be set by deployment
             caller: deployer,
             value: request.initial_balance,
+            input: request.constructor_params.clone(),
             gas_price: 1,
             block_number: 1,
             timestamp: std::time::SystemTime::now()
                 .duration_since(std::time::UNIX_EPOCH)
                 .unwrap_or_default()
                 .as_secs(),
+            chain_id: self.config.chain_id,
+            coinbase: [0u8; 20],
         };
 
         // Get a core for deployment
-        let mut core = self.core_pool.pop().unwrap_or_else(|| RvmCore::new(self.config.max_gas_limit));
-        
-        // Set up storage
-        {
-            let storage = self.storage.read().await;
-            core.storage = storage.clone();
-        }
+        let mut core = self.core_pool.pop().unwrap_or_else(|| self.new_core());
 
         // Deploy the contract
-        let contract_address = core.deploy_contract(request.bytecode.clone(), env).await?;
-
-        // Update storage
-        {
-            let mut storage = self.storage.write().await;
-            *storage = core.storage.clone();
-        }
+        let contract_address = {
+            let storage = Arc::clone(&self.storage);
+            let mut storage = storage.write().await;
+            let address = match self.agent_host.as_deref_mut() {
+                Some(agents) if self.config.enable_agent_apis => {
+                    let mut host = AgentEnabledHost::new(&mut *storage, &mut *agents);
+                    let address = core.deploy_contract(request.bytecode.clone(), env, &mut host).await;
+                    agents.commit();
+                    address
+                }
+                _ => core.deploy_contract(request.bytecode.clone(), env, &mut *storage).await,
+            }?;
+            self.commit_state(&mut storage);
+            address
+        };
 
         // Store contract in runtime
         let contract = Contract {
@@ -282,16 +525,19 @@ impl RvmRuntime {
             contract_address,
             caller,
             value,
+            input: call_data,
             gas_price: 1,
             block_number: 1,
             timestamp: std::time::SystemTime::now()
                 .duration_since(std::time::UNIX_EPOCH)
                 .unwrap_or_default()
                 .as_secs(),
+            chain_id: self.config.chain_id,
+            coinbase: [0u8; 20],
         };
 
         // Execute the contract
-        self.execute(&bytecode, env).await
+        self.execute_with_gas_limit(&bytecode, env, gas_limit).await
     }
 
     /// Execute a precompiled contract
@@ -308,13 +554,7 @@ impl RvmRuntime {
         let mut gas_meter = GasMeter::new(gas_limit);
         
         // Charge gas for precompile execution
-        let gas_cost = match address {
-            1 => 3000,  // ECRECOVER
-            2 => 60 + 12 * ((input.len() + 31) / 32) as u64, // SHA256
-            3 => 600 + 120 * ((input.len() + 31) / 32) as u64, // RIPEMD160
-            4 => 15 + 3 * ((input.len() + 31) / 32) as u64, // IDENTITY
-            _ => return Err(RvmError::InvalidPrecompile(address)),
-        };
+        let gas_cost = Precompiles::gas_cost(address, input)?;
 
         gas_meter.consume(gas_cost)?;
 
@@ -322,14 +562,22 @@ impl RvmRuntime {
             Ok(output) => Ok(ExecutionResult {
                 return_data: output,
                 gas_used: gas_meter.used(),
+                gas_refunded: 0,
                 success: true,
                 error: None,
+                agent_witness: None,
+                fee_receipt: None,
+                logs: Vec::new(),
             }),
             Err(e) => Ok(ExecutionResult {
                 return_data: vec![],
                 gas_used: gas_meter.used(),
+                gas_refunded: 0,
                 success: false,
                 error: Some(e.to_string()),
+                agent_witness: None,
+                fee_receipt: None,
+                logs: Vec::new(),
             }),
         }
     }
@@ -344,11 +592,6 @@ impl RvmRuntime {
         self.hooks.crypto_hooks.push(hook);
     }
 
-    /// Add an agent API hook
-    pub fn add_agent_hook(&mut self, hook: fn(&str, &[u8]) -> Result<Vec<u8>, RvmError>) {
-        self.hooks.agent_hooks.push(hook);
-    }
-
     /// Get execution statistics
     pub fn get_stats(&self) -> &ExecutionStats {
         &self.stats
@@ -359,6 +602,11 @@ impl RvmRuntime {
         &self.contracts
     }
 
+    /// Get the shared storage
+    pub fn storage(&self) -> Arc<RwLock<Storage>> {
+        Arc::clone(&self.storage)
+    }
+
     /// Update execution statistics
     fn update_stats(&mut self, result: &ExecutionResult) {
         self.stats.total_executions += 1;
@@ -379,16 +627,15 @@ impl RvmRuntime {
 
     /// Create a simple demo execution
     pub async fn demo_execution(&mut self) -> Result<ExecutionResult, RvmError> {
-        // Simple bytecode: PUSH1 10, PUSH1 20, ADD, PUSH1 5, MUL, STOP
         // This computes (10 + 20) * 5 = 150
-        let bytecode = vec![
-            0x60, 0x0a, // PUSH1 10
-            0x60, 0x14, // PUSH1 20  
-            0x01,       // ADD
-            0x60, 0x05, // PUSH1 5
-            0x02,       // MUL
-            0x00,       // STOP
-        ];
+        let bytecode = assemble(
+            "PUSH 10
+             PUSH 20
+             ADD
+             PUSH 5
+             MUL
+             STOP",
+        )?;
 
         let env = ExecutionEnvironment::default();
         self.execute(&bytecode, env).await
@@ -403,6 +650,8 @@ impl Default for RuntimeConfig {
             enable_agent_apis: true,
             enable_crypto_hooks: true,
             debug_mode: false,
+            validate_bytecode: false,
+            chain_id: 0,
         }
     }
 }
@@ -412,7 +661,6 @@ impl Default for RuntimeHooks {
         Self {
             storage_hooks: Vec::new(),
             crypto_hooks: Vec::new(),
-            agent_hooks: Vec::new(),
         }
     }
 }
@@ -449,6 +697,161 @@ mod tests {
         assert!(runtime.contracts.contains_key(&address));
     }
 
+    #[tokio::test]
+    async fn test_contracts_see_configured_chain_id() {
+        let mut runtime = RvmRuntime::new(RuntimeConfig { chain_id: 5, ..RuntimeConfig::default() });
+        let request = DeploymentRequest {
+            bytecode: assemble("PUSH 0\nPUSH 0\nPUSH 0xb2\nPUSH 2\nBRIDGE_SEND\nSTOP").unwrap(),
+            constructor_params: vec![],
+            initial_balance: 0,
+            gas_limit: 100000,
+        };
+        let address = runtime.deploy_contract(request, [1u8; 20]).await.unwrap();
+        let result = runtime.call_contract(address, vec![], [1u8; 20], 0, 100000).await.unwrap();
+        assert!(result.success, "{:?}", result.error);
+        assert_eq!(runtime.storage().read().await.bridge_outbox()[0].source_chain, 5);
+    }
+
+    #[tokio::test]
+    async fn test_deployment_validation() {
+        let config = RuntimeConfig { validate_bytecode: true, ..RuntimeConfig::default() };
+        let mut runtime = RvmRuntime::new(config);
+        let request = |bytecode: Vec<u8>| DeploymentRequest {
+            bytecode,
+            constructor_params: vec![],
+            initial_balance: 0,
+            gas_limit: 100000,
+        };
+
+        // ADD with a single operand underflows
+        let result = runtime.deploy_contract(request(vec![0x60, 0x01, 0x01, 0x00]), [1u8; 20]).await;
+        assert!(matches!(result, Err(RvmError::InvalidBytecode(_))));
+        assert!(runtime.contracts.is_empty());
+
+        let address = runtime.deploy_contract(request(vec![0x60, 0x01, 0x60, 0x02, 0x01, 0x00]), [1u8; 20]).await;
+        assert!(address.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_nested_call_reverts_callee_state() {
+        let mut runtime = RvmRuntime::new(RuntimeConfig::default());
+        let callee = crate::core::word_to_address(0x42);
+
+        // Callee: SSTORE(1, 7), then REVERT(0, 0)
+        let callee_code = vec![0x60, 0x07, 0x60, 0x01, 0x55, 0x60, 0x00, 0x60, 0x00, 0xfd];
+        runtime.storage().write().await.set_code(callee, callee_code);
+
+        // Caller: CALL(gas=50000, to=0x42, value=0, in=0/0, out=0/0), SSTORE(0, success + 5)
+        let caller_code = vec![
+            0x60, 0x00, 0x60, 0x00, 0x60, 0x00, 0x60, 0x00, 0x60, 0x00, // out/in/value
+            0x60, 0x42,                                                 // to
+            0x7f,                                                       // PUSH32 gas
+            0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+            0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0xc3, 0x50,
+            0xf1,                                                       // CALL
+            0x60, 0x05, 0x01,                                           // success + 5
+            0x60, 0x00, 0x55,                                           // SSTORE(0, success + 5)
+            0x00,
+        ];
+        let caller = [9u8; 20];
+        let env = ExecutionEnvironment::new(caller, [1u8; 20], 0);
+        let result = runtime.execute(&caller_code, env).await.unwrap();
+        assert!(result.success);
+
+        let storage = runtime.storage();
+        let storage = storage.read().await;
+        assert_eq!(storage.get_storage(&callee, 1), 0);
+        assert_eq!(storage.get_storage(&caller, 0), 5);
+    }
+
+    #[tokio::test]
+    async fn test_block_gas_matches_per_opcode_metering() {
+        let mut runtime = RvmRuntime::new(RuntimeConfig::default());
+
+        // PUSH1 3, loop: JUMPDEST, PUSH1 1, SUB, DUP1, PUSH1 2, JUMPI, STOP
+        let code = vec![0x60, 0x03, 0x5b, 0x60, 0x01, 0x03, 0x80, 0x60, 0x02, 0x57, 0x00];
+        let env = ExecutionEnvironment::default();
+
+        let result = runtime.execute(&code, env.clone()).await.unwrap();
+        assert!(result.success);
+        assert_eq!(result.gas_used, 3 + 3 * 23);
+
+        // The third iteration no longer fits and stops at the second PUSH1
+        let result = runtime.execute_with_gas_limit(&code, env, 60).await.unwrap();
+        assert!(!result.success);
+        assert_eq!(result.gas_used, 3 + 2 * 23 + 1 + 3 + 3 + 3);
+    }
+
+    #[tokio::test]
+    async fn test_unimplemented_opcode_fails() {
+        let mut runtime = RvmRuntime::new(RuntimeConfig::default());
+        let result = runtime.execute(&[0x32, 0x00], ExecutionEnvironment::default()).await.unwrap();
+        assert!(!result.success);
+        assert_eq!(result.error.as_deref(), Some("Invalid opcode: 0x32"));
+    }
+
+    #[tokio::test]
+    async fn test_eof_rjumpv() {
+        use crate::eof::{EofContainer, TypeSection, NON_RETURNING};
+
+        let mut runtime = RvmRuntime::new(RuntimeConfig::default());
+        // Case 0 returns 10, case 1 returns 20, anything else falls through to 7
+        for (case, expected) in [(0, 10), (1, 20), (2, 7), (255, 7)] {
+            let source = format!(
+                "PUSH1 {}\nRJUMPV 5,10\nPUSH1 7\nRJUMP 7\nPUSH1 10\nRJUMP 2\nPUSH1 20\n\
+                 PUSH1 0\nMSTORE\nPUSH1 32\nPUSH1 0\nRETURN",
+                case
+            );
+            let container = EofContainer {
+                types: vec![TypeSection { inputs: 0, outputs: NON_RETURNING, max_stack_height: 2 }],
+                code_sections: vec![crate::asm::assemble(&source).unwrap()],
+                data: Vec::new(),
+            };
+            container.validate().unwrap();
+
+            let result = runtime.execute(&container.encode(), ExecutionEnvironment::default()).await.unwrap();
+            assert!(result.success, "{:?}", result.error);
+            assert_eq!(crate::core::bytes32_to_word(&result.return_data.try_into().unwrap()), expected);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_ghostchain_services_follow_block_time_and_commits() {
+        let mut runtime = RvmRuntime::new(RuntimeConfig::default());
+        runtime.set_ghostchain_services(GhostChainServices::new().unwrap()).unwrap();
+
+        // Register "a.ghost" (stored at 25) -> 0x4242 and return the flag
+        let code = assemble(
+            "PUSH 0x612e67686f7374\nPUSH 0\nMSTORE\nPUSH 0x4242\nPUSH 7\nPUSH 25\nCNS_REGISTER\n\
+             PUSH 0\nMSTORE\nPUSH 32\nPUSH 0\nRETURN",
+        )
+        .unwrap();
+        let env = ExecutionEnvironment { timestamp: 1_000, ..Default::default() };
+        let result = runtime.execute(&code, env).await.unwrap();
+        assert!(result.success, "{:?}", result.error);
+        assert_eq!(crate::core::bytes32_to_word(&result.return_data.try_into().unwrap()), 1);
+
+        // The registration is dated by the block and committed with the storage
+        let services = runtime.ghostchain_services().unwrap();
+        services.cns.revert_to(0);
+        let record = services.cns.resolve_domain("a.ghost", 1_000).unwrap().record.unwrap();
+        assert_eq!(record.registered_at, 1_000);
+        assert_eq!(record.address[12..], 0x4242u64.to_be_bytes());
+    }
+
+    #[tokio::test]
+    async fn test_execution_drains_logs() {
+        let mut runtime = RvmRuntime::new(RuntimeConfig::default());
+        let code = crate::asm::assemble("PUSH 1\nPUSH 0\nLOG0\nSTOP").unwrap();
+
+        // Each execution returns its own logs and leaves none behind
+        for _ in 0..2 {
+            let result = runtime.execute(&code, ExecutionEnvironment::default()).await.unwrap();
+            assert_eq!(result.logs.len(), 1);
+        }
+        assert!(runtime.storage().write().await.take_logs().is_empty());
+    }
+
     #[tokio::test]
     async fn test_precompile_execution() {
         let runtime = RvmRuntime::new(RuntimeConfig::default());
@@ -2,9 +2,16 @@
 //!
 //! Manages contract storage, account state, and persistent data.
 
-use crate::{error::RvmError, core::Contract};
+use crate::{
+    bridge::{BridgeMessage, SignedBridgeMessage},
+    error::RvmError,
+    core::Contract,
+    ghostchain_gas::{TokenBalances, TokenType},
+    host::{Host, Log},
+    l2::{L2Checkpoint, L2State, L2Transaction},
+};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use tokio::sync::RwLock;
 
 /// Storage backend for contracts and state
@@ -18,8 +25,57 @@ pub struct Storage {
     balances: HashMap<[u8; 20], u64>,
     /// Nonces for accounts
     nonces: HashMap<[u8; 20], u64>,
+    /// GhostChain token ledger
+    token_balances: HashMap<[u8; 20], TokenBalances>,
+    /// Addresses allowed to mint and burn tokens
+    token_minters: HashSet<[u8; 20]>,
+    /// Tokens a holder lets a minter burn, by (holder, burner, token)
+    token_allowances: HashMap<([u8; 20], [u8; 20], TokenType), u64>,
+    /// Rollup inboxes, batches and checkpoints, by rollup contract
+    l2: HashMap<[u8; 20], L2State>,
+    /// Cross-chain outbox, inbox and consumed message ids
+    bridge: BridgeState,
     /// Storage state for gas calculations
     original_storage: HashMap<([u8; 20], u64), u64>,
+    /// Logs emitted since the last `take_logs`
+    logs: Vec<Log>,
+    /// Undo journal for frame-level reverts
+    journal: Vec<JournalEntry>,
+}
+
+/// Undo record for a single state mutation
+#[derive(Debug, Clone)]
+enum JournalEntry {
+    /// Storage slot changed
+    StorageChanged { address: [u8; 20], key: u64, previous: Option<u64> },
+    /// Balance changed
+    BalanceChanged { address: [u8; 20], previous: Option<u64> },
+    /// Nonce changed
+    NonceChanged { address: [u8; 20], previous: Option<u64> },
+    /// Contract code changed
+    CodeChanged { address: [u8; 20], previous: Option<Contract> },
+    /// Token balance changed
+    TokenBalanceChanged { address: [u8; 20], token: TokenType, previous: u64 },
+    /// Minter authorization changed
+    MinterChanged { address: [u8; 20], previous: bool },
+    /// Burn allowance changed
+    AllowanceChanged { key: ([u8; 20], [u8; 20], TokenType), previous: u64 },
+    /// Transaction appended to a rollup's inbox
+    L2Submitted { rollup: [u8; 20] },
+    /// Batch root posted for a rollup
+    L2BatchPosted { rollup: [u8; 20], batch_root: [u8; 32] },
+    /// L2 transaction inclusion verified, making its batch verified if it was not yet
+    L2Included { rollup: [u8; 20], index: u64, first_in_batch: bool },
+    /// L2 state root checkpointed
+    L2Synced { rollup: [u8; 20] },
+    /// Message appended to the bridge outbox
+    BridgeSent,
+    /// Message delivered to the bridge inbox
+    BridgeDelivered,
+    /// Bridge message consumed
+    BridgeConsumed { id: [u8; 32] },
+    /// Log emitted at this position in the log list
+    LogEmitted { index: usize },
 }
 
 /// Account information
@@ -56,7 +112,14 @@ impl Storage {
             contracts: HashMap::new(),
             balances: HashMap::new(),
             nonces: HashMap::new(),
+            token_balances: HashMap::new(),
+            token_minters: HashSet::new(),
+            token_allowances: HashMap::new(),
+            l2: HashMap::new(),
+            bridge: BridgeState::default(),
             original_storage: HashMap::new(),
+            logs: Vec::new(),
+            journal: Vec::new(),
         }
     }
 
@@ -76,18 +139,7 @@ impl Storage {
     /// Set storage value for a contract
     pub async fn set(&mut self, key: u64, value: u64) -> Result<(), RvmError> {
         let address = [0u8; 20];
-        
-        // Track original value for gas calculations
-        if !self.original_storage.contains_key(&(address, key)) {
-            let original_value = self.get_raw(&address, key);
-            self.original_storage.insert((address, key), original_value);
-        }
-
-        self.contract_storage
-            .entry(address)
-            .or_insert_with(HashMap::new)
-            .insert(key, value);
-        
+        self.set_storage(address, key, value);
         Ok(())
     }
 
@@ -100,6 +152,16 @@ impl Storage {
             .unwrap_or(0)
     }
 
+    /// All storage slots of an address, sorted by key
+    pub fn storage_slots(&self, address: &[u8; 20]) -> Vec<(u64, u64)> {
+        let mut slots: Vec<(u64, u64)> = self.contract_storage
+            .get(address)
+            .map(|storage| storage.iter().map(|(key, value)| (*key, *value)).collect())
+            .unwrap_or_default();
+        slots.sort_unstable();
+        slots
+    }
+
     /// Set storage value for a specific address
     pub fn set_storage(&mut self, address: [u8; 20], key: u64, value: u64) {
         // Track original value for gas calculations
@@ -108,10 +170,11 @@ impl Storage {
             self.original_storage.insert((address, key), original_value);
         }
 
-        self.contract_storage
+        let previous = self.contract_storage
             .entry(address)
             .or_insert_with(HashMap::new)
             .insert(key, value);
+        self.journal.push(JournalEntry::StorageChanged { address, key, previous });
     }
 
     /// Get raw storage value without async
@@ -133,10 +196,16 @@ impl Storage {
 
     /// Store a contract
     pub async fn set_contract(&mut self, address: [u8; 20], contract: Contract) -> Result<(), RvmError> {
-        self.contracts.insert(address, contract);
+        self.insert_contract(address, contract);
         Ok(())
     }
 
+    /// Store a contract, recording the previous one in the journal
+    fn insert_contract(&mut self, address: [u8; 20], contract: Contract) {
+        let previous = self.contracts.insert(address, contract);
+        self.journal.push(JournalEntry::CodeChanged { address, previous });
+    }
+
     /// Get a contract
     pub fn get_contract(&self, address: &[u8; 20]) -> Option<&Contract> {
         self.contracts.get(address)
@@ -149,7 +218,8 @@ impl Storage {
 
     /// Set account balance
     pub fn set_balance(&mut self, address: [u8; 20], balance: u64) {
-        self.balances.insert(address, balance);
+        let previous = self.balances.insert(address, balance);
+        self.journal.push(JournalEntry::BalanceChanged { address, previous });
     }
 
     /// Transfer balance between accounts
@@ -162,14 +232,189 @@ impl Storage {
             });
         }
 
-        let to_balance = self.get_balance(&to);
-        
+        if from == to {
+            return Ok(());
+        }
+        let to_balance = self.get_balance(&to)
+            .checked_add(amount)
+            .ok_or_else(|| RvmError::StorageError("balance overflow".to_string()))?;
         self.set_balance(from, from_balance - amount);
-        self.set_balance(to, to_balance + amount);
-        
+        self.set_balance(to, to_balance);
+        Ok(())
+    }
+
+    /// Get a token balance
+    pub fn get_token_balance(&self, address: &[u8; 20], token: TokenType) -> u64 {
+        self.token_balances
+            .get(address)
+            .map(|balances| balances.get_balance(token))
+            .unwrap_or(0)
+    }
+
+    /// Set a token balance
+    pub fn set_token_balance(&mut self, address: [u8; 20], token: TokenType, amount: u64) {
+        let balances = self.token_balances.entry(address).or_default();
+        let previous = balances.get_balance(token);
+        balances.set_balance(token, amount);
+        self.journal.push(JournalEntry::TokenBalanceChanged { address, token, previous });
+    }
+
+    /// Move tokens between accounts
+    pub fn transfer_tokens(&mut self, from: [u8; 20], to: [u8; 20], token: TokenType, amount: u64) -> Result<(), RvmError> {
+        let from_balance = self.get_token_balance(&from, token);
+        if from_balance < amount {
+            return Err(RvmError::InsufficientTokenBalance {
+                token: format!("{:?}", token),
+                required: amount,
+                available: from_balance,
+            });
+        }
+        if from == to {
+            return Ok(());
+        }
+        let to_balance = self.get_token_balance(&to, token)
+            .checked_add(amount)
+            .ok_or_else(|| RvmError::TokenTransferFailed(format!("{:?} balance overflow", token)))?;
+        self.set_token_balance(from, token, from_balance - amount);
+        self.set_token_balance(to, token, to_balance);
+        Ok(())
+    }
+
+    /// Whether an address may mint and burn tokens
+    pub fn is_token_minter(&self, address: &[u8; 20]) -> bool {
+        self.token_minters.contains(address)
+    }
+
+    /// Grant or revoke minting rights
+    pub fn set_token_minter(&mut self, address: [u8; 20], authorized: bool) {
+        let previous = if authorized {
+            !self.token_minters.insert(address)
+        } else {
+            self.token_minters.remove(&address)
+        };
+        self.journal.push(JournalEntry::MinterChanged { address, previous });
+    }
+
+    /// Create tokens; the minter must be authorized
+    pub fn mint_tokens(&mut self, minter: [u8; 20], to: [u8; 20], token: TokenType, amount: u64) -> Result<(), RvmError> {
+        if !self.is_token_minter(&minter) {
+            return Err(RvmError::TokenMintUnauthorized(hex::encode(minter)));
+        }
+        self.issue_tokens(to, token, amount)
+    }
+
+    /// Create tokens as protocol issuance, without a minter
+    pub fn issue_tokens(&mut self, to: [u8; 20], token: TokenType, amount: u64) -> Result<(), RvmError> {
+        let balance = self.get_token_balance(&to, token)
+            .checked_add(amount)
+            .ok_or_else(|| RvmError::TokenTransferFailed(format!("{:?} balance overflow", token)))?;
+        self.set_token_balance(to, token, balance);
         Ok(())
     }
 
+    /// Tokens `holder` lets `burner` burn
+    pub fn token_allowance(&self, holder: &[u8; 20], burner: &[u8; 20], token: TokenType) -> u64 {
+        self.token_allowances.get(&(*holder, *burner, token)).copied().unwrap_or(0)
+    }
+
+    /// Let `burner` burn up to `amount` of the holder's tokens
+    pub fn approve_tokens(&mut self, holder: [u8; 20], burner: [u8; 20], token: TokenType, amount: u64) {
+        let key = (holder, burner, token);
+        let previous = self.token_allowances.insert(key, amount).unwrap_or(0);
+        self.journal.push(JournalEntry::AllowanceChanged { key, previous });
+    }
+
+    /// Destroy tokens; the burner must be an authorized minter and either hold the tokens
+    /// or have the holder's allowance, which the burn uses up
+    pub fn burn_tokens(&mut self, burner: [u8; 20], from: [u8; 20], token: TokenType, amount: u64) -> Result<(), RvmError> {
+        if !self.is_token_minter(&burner) {
+            return Err(RvmError::TokenMintUnauthorized(hex::encode(burner)));
+        }
+        let allowance = self.token_allowance(&from, &burner, token);
+        if burner != from && allowance < amount {
+            return Err(RvmError::TokenBurnFailed(format!(
+                "{:?} allowance {} is below {}",
+                token, allowance, amount
+            )));
+        }
+        let balance = self.get_token_balance(&from, token);
+        if balance < amount {
+            return Err(RvmError::TokenBurnFailed(format!(
+                "{:?} balance {} is below {}",
+                token, balance, amount
+            )));
+        }
+        if burner != from {
+            self.approve_tokens(from, burner, token, allowance - amount);
+        }
+        self.set_token_balance(from, token, balance - amount);
+        Ok(())
+    }
+
+    /// Transactions in a rollup's L2 inbox
+    pub fn l2_inbox(&self, rollup: &[u8; 20]) -> &[L2Transaction] {
+        self.l2.get(rollup).map_or(&[], |l2| &l2.inbox)
+    }
+
+    /// Batch root an L2 inbox transaction was verified against
+    pub fn l2_batch_of(&self, rollup: &[u8; 20], index: u64) -> Option<[u8; 32]> {
+        self.l2.get(rollup)?.included.get(&index).copied()
+    }
+
+    /// Recorded L2 state roots of a rollup, oldest first
+    pub fn l2_checkpoints(&self, rollup: &[u8; 20]) -> &[L2Checkpoint] {
+        self.l2.get(rollup).map_or(&[], |l2| &l2.checkpoints)
+    }
+
+    /// Let an account post batch roots for a rollup
+    pub fn set_l2_sequencer(&mut self, rollup: [u8; 20], sequencer: [u8; 20]) {
+        self.l2.entry(rollup).or_default().sequencer = Some(sequencer);
+    }
+
+    /// Post a sequencer batch root that inbox transactions can then be verified against;
+    /// only the rollup's sequencer and the rollup contract may post
+    pub fn post_l2_batch(&mut self, rollup: [u8; 20], poster: [u8; 20], batch_root: [u8; 32]) -> Result<(), RvmError> {
+        let l2 = self.l2.entry(rollup).or_default();
+        if poster != rollup && l2.sequencer != Some(poster) {
+            return Err(RvmError::L2BatchVerificationFailed(format!(
+                "{} may not post batches for rollup {}",
+                hex::encode(poster),
+                hex::encode(rollup)
+            )));
+        }
+        if l2.batches.insert(batch_root) {
+            self.journal.push(JournalEntry::L2BatchPosted { rollup, batch_root });
+        }
+        Ok(())
+    }
+
+    /// Messages sent to other chains, by nonce
+    pub fn bridge_outbox(&self) -> &[BridgeMessage] {
+        &self.bridge.outbox
+    }
+
+    /// Relayed messages, by inbox index
+    pub fn bridge_inbox(&self) -> &[SignedBridgeMessage] {
+        &self.bridge.inbox
+    }
+
+    /// Deliver a relayed message to the bridge inbox, returning its index
+    pub fn deliver_bridge_message(&mut self, message: SignedBridgeMessage) -> u64 {
+        self.bridge.inbox.push(message);
+        self.journal.push(JournalEntry::BridgeDelivered);
+        self.bridge.inbox.len() as u64 - 1
+    }
+
+    /// Trust a relayer key (uncompressed secp256k1, without prefix) for messages from a chain
+    pub fn add_bridge_signer(&mut self, chain_id: u64, public_key: Vec<u8>) {
+        self.bridge.signers.entry(chain_id).or_default().insert(public_key);
+    }
+
+    /// Whether a bridge message id has been consumed
+    pub fn is_bridge_message_consumed(&self, id: &[u8; 32]) -> bool {
+        self.bridge.consumed.contains(id)
+    }
+
     /// Get account nonce
     pub fn get_nonce(&self, address: &[u8; 20]) -> u64 {
         self.nonces.get(address).copied().unwrap_or(0)
@@ -177,7 +422,8 @@ impl Storage {
 
     /// Set account nonce
     pub fn set_nonce(&mut self, address: [u8; 20], nonce: u64) {
-        self.nonces.insert(address, nonce);
+        let previous = self.nonces.insert(address, nonce);
+        self.journal.push(JournalEntry::NonceChanged { address, previous });
     }
 
     /// Increment account nonce
@@ -238,10 +484,93 @@ impl Storage {
         changes
     }
 
+    /// Take the logs emitted since the last call
+    pub fn take_logs(&mut self) -> Vec<Log> {
+        std::mem::take(&mut self.logs)
+    }
+
     /// Commit storage changes (finalize transaction)
     pub fn commit(&mut self) {
         // In a real implementation, this would write to persistent storage
         self.original_storage.clear();
+        self.journal.clear();
+        self.logs.clear();
+    }
+
+    /// Undo journal entries down to the given length
+    fn revert_journal(&mut self, checkpoint: usize) {
+        while self.journal.len() > checkpoint {
+            let Some(entry) = self.journal.pop() else { break };
+            match entry {
+                JournalEntry::StorageChanged { address, key, previous } => {
+                    let storage = self.contract_storage.entry(address).or_default();
+                    match previous {
+                        Some(value) => { storage.insert(key, value); }
+                        None => { storage.remove(&key); }
+                    }
+                }
+                JournalEntry::BalanceChanged { address, previous } => {
+                    match previous {
+                        Some(balance) => { self.balances.insert(address, balance); }
+                        None => { self.balances.remove(&address); }
+                    }
+                }
+                JournalEntry::NonceChanged { address, previous } => {
+                    match previous {
+                        Some(nonce) => { self.nonces.insert(address, nonce); }
+                        None => { self.nonces.remove(&address); }
+                    }
+                }
+                JournalEntry::CodeChanged { address, previous } => {
+                    match previous {
+                        Some(contract) => { self.contracts.insert(address, contract); }
+                        None => { self.contracts.remove(&address); }
+                    }
+                }
+                JournalEntry::TokenBalanceChanged { address, token, previous } => {
+                    self.token_balances.entry(address).or_default().set_balance(token, previous);
+                }
+                JournalEntry::MinterChanged { address, previous } => {
+                    if previous {
+                        self.token_minters.insert(address);
+                    } else {
+                        self.token_minters.remove(&address);
+                    }
+                }
+                JournalEntry::AllowanceChanged { key, previous } => {
+                    self.token_allowances.insert(key, previous);
+                }
+                JournalEntry::L2Submitted { rollup } => {
+                    self.l2.entry(rollup).or_default().inbox.pop();
+                }
+                JournalEntry::L2BatchPosted { rollup, batch_root } => {
+                    self.l2.entry(rollup).or_default().batches.remove(&batch_root);
+                }
+                JournalEntry::L2Included { rollup, index, first_in_batch } => {
+                    let l2 = self.l2.entry(rollup).or_default();
+                    if let Some(root) = l2.included.remove(&index) {
+                        if first_in_batch {
+                            l2.verified.remove(&root);
+                        }
+                    }
+                }
+                JournalEntry::L2Synced { rollup } => {
+                    self.l2.entry(rollup).or_default().checkpoints.pop();
+                }
+                JournalEntry::BridgeSent => {
+                    self.bridge.outbox.pop();
+                }
+                JournalEntry::BridgeDelivered => {
+                    self.bridge.inbox.pop();
+                }
+                JournalEntry::BridgeConsumed { id } => {
+                    self.bridge.consumed.remove(&id);
+                }
+                JournalEntry::LogEmitted { index } => {
+                    self.logs.truncate(index);
+                }
+            }
+        }
     }
 
     /// Revert storage changes (rollback transaction)
@@ -270,6 +599,11 @@ impl Storage {
             contracts: self.contracts.clone(),
             balances: self.balances.clone(),
             nonces: self.nonces.clone(),
+            token_balances: self.token_balances.clone(),
+            token_minters: self.token_minters.clone(),
+            token_allowances: self.token_allowances.clone(),
+            l2: self.l2.clone(),
+            bridge: self.bridge.clone(),
             original_storage: self.original_storage.clone(),
         }
     }
@@ -280,7 +614,151 @@ impl Storage {
         self.contracts = snapshot.contracts;
         self.balances = snapshot.balances;
         self.nonces = snapshot.nonces;
+        self.token_balances = snapshot.token_balances;
+        self.token_minters = snapshot.token_minters;
+        self.token_allowances = snapshot.token_allowances;
+        self.l2 = snapshot.l2;
+        self.bridge = snapshot.bridge;
         self.original_storage = snapshot.original_storage;
+        self.journal.clear();
+    }
+}
+
+impl Host for Storage {
+    fn balance(&self, address: &[u8; 20]) -> u64 {
+        self.get_balance(address)
+    }
+
+    fn transfer(&mut self, from: [u8; 20], to: [u8; 20], amount: u64) -> Result<(), RvmError> {
+        Storage::transfer(self, from, to, amount)
+    }
+
+    fn nonce(&self, address: &[u8; 20]) -> u64 {
+        self.get_nonce(address)
+    }
+
+    fn increment_nonce(&mut self, address: [u8; 20]) {
+        Storage::increment_nonce(self, address)
+    }
+
+    fn code(&self, address: &[u8; 20]) -> Option<Vec<u8>> {
+        self.contracts.get(address).map(|contract| contract.bytecode.clone())
+    }
+
+    fn set_code(&mut self, address: [u8; 20], code: Vec<u8>) {
+        let contract = Contract {
+            bytecode: code,
+            address,
+            storage: HashMap::new(),
+            balance: self.get_balance(&address),
+        };
+        self.insert_contract(address, contract);
+    }
+
+    fn sload(&self, address: &[u8; 20], key: u64) -> u64 {
+        self.get_storage(address, key)
+    }
+
+    fn sstore(&mut self, address: [u8; 20], key: u64, value: u64) {
+        self.set_storage(address, key, value)
+    }
+
+    fn sload_original(&self, address: &[u8; 20], key: u64) -> u64 {
+        self.get_original_storage(address, key)
+    }
+
+    fn block_hash(&self, _number: u64) -> [u8; 32] {
+        // Plain storage keeps no block history
+        [0u8; 32]
+    }
+
+    fn log(&mut self, log: Log) {
+        self.journal.push(JournalEntry::LogEmitted { index: self.logs.len() });
+        self.logs.push(log);
+    }
+
+    fn token_balance(&self, address: &[u8; 20], token: TokenType) -> u64 {
+        self.get_token_balance(address, token)
+    }
+
+    fn transfer_tokens(&mut self, from: [u8; 20], to: [u8; 20], token: TokenType, amount: u64) -> Result<(), RvmError> {
+        Storage::transfer_tokens(self, from, to, token, amount)
+    }
+
+    fn mint_tokens(&mut self, minter: [u8; 20], to: [u8; 20], token: TokenType, amount: u64) -> Result<(), RvmError> {
+        Storage::mint_tokens(self, minter, to, token, amount)
+    }
+
+    fn issue_tokens(&mut self, to: [u8; 20], token: TokenType, amount: u64) -> Result<(), RvmError> {
+        Storage::issue_tokens(self, to, token, amount)
+    }
+
+    fn burn_tokens(&mut self, burner: [u8; 20], from: [u8; 20], token: TokenType, amount: u64) -> Result<(), RvmError> {
+        Storage::burn_tokens(self, burner, from, token, amount)
+    }
+
+    fn l2_submit(&mut self, rollup: [u8; 20], data: Vec<u8>, l1_block: u64) -> Result<u64, RvmError> {
+        let inbox = &mut self.l2.entry(rollup).or_default().inbox;
+        let index = inbox.len() as u64;
+        inbox.push(L2Transaction { index, sender: rollup, data, l1_block });
+        self.journal.push(JournalEntry::L2Submitted { rollup });
+        Ok(index)
+    }
+
+    fn l2_transaction(&self, rollup: [u8; 20], index: u64) -> Option<L2Transaction> {
+        self.l2_inbox(&rollup).get(index as usize).cloned()
+    }
+
+    fn l2_include(&mut self, rollup: [u8; 20], index: u64, batch_root: [u8; 32]) -> Result<(), RvmError> {
+        let l2 = self.l2.entry(rollup).or_default();
+        l2.check_inclusion(index, &batch_root)?;
+        if l2.included.insert(index, batch_root).is_none() {
+            let first_in_batch = l2.verified.insert(batch_root);
+            self.journal.push(JournalEntry::L2Included { rollup, index, first_in_batch });
+        }
+        Ok(())
+    }
+
+    fn l2_sync_state(&mut self, rollup: [u8; 20], checkpoint: L2Checkpoint) -> Result<(), RvmError> {
+        let l2 = self.l2.entry(rollup).or_default();
+        l2.check_checkpoint(&checkpoint)?;
+        l2.checkpoints.push(checkpoint);
+        self.journal.push(JournalEntry::L2Synced { rollup });
+        Ok(())
+    }
+
+    fn bridge_nonce(&self) -> u64 {
+        self.bridge.outbox.len() as u64
+    }
+
+    fn bridge_send(&mut self, message: BridgeMessage) -> Result<(), RvmError> {
+        self.bridge.outbox.push(message);
+        self.journal.push(JournalEntry::BridgeSent);
+        Ok(())
+    }
+
+    fn bridge_message(&self, index: u64) -> Option<SignedBridgeMessage> {
+        self.bridge.inbox.get(index as usize).cloned()
+    }
+
+    fn is_bridge_signer(&self, chain_id: u64, public_key: &[u8]) -> bool {
+        self.bridge.signers.get(&chain_id).is_some_and(|signers| signers.contains(public_key))
+    }
+
+    fn bridge_consume(&mut self, id: [u8; 32]) -> bool {
+        if !self.bridge.consumed.insert(id) {
+            return false;
+        }
+        self.journal.push(JournalEntry::BridgeConsumed { id });
+        true
+    }
+
+    fn checkpoint(&mut self) -> usize {
+        self.journal.len()
+    }
+
+    fn revert_to(&mut self, checkpoint: usize) {
+        self.revert_journal(checkpoint)
     }
 }
 
@@ -291,9 +769,27 @@ pub struct StorageSnapshot {
     contracts: HashMap<[u8; 20], Contract>,
     balances: HashMap<[u8; 20], u64>,
     nonces: HashMap<[u8; 20], u64>,
+    token_balances: HashMap<[u8; 20], TokenBalances>,
+    token_minters: HashSet<[u8; 20]>,
+    token_allowances: HashMap<([u8; 20], [u8; 20], TokenType), u64>,
+    l2: HashMap<[u8; 20], L2State>,
+    bridge: BridgeState,
     original_storage: HashMap<([u8; 20], u64), u64>,
 }
 
+/// Cross-chain messaging state
+#[derive(Debug, Clone, Default)]
+struct BridgeState {
+    /// Sent messages, by nonce
+    outbox: Vec<BridgeMessage>,
+    /// Relayed messages awaiting BRIDGE_RECEIVE
+    inbox: Vec<SignedBridgeMessage>,
+    /// Ids of received messages
+    consumed: HashSet<[u8; 32]>,
+    /// Trusted relayer keys by source chain
+    signers: HashMap<u64, HashSet<Vec<u8>>>,
+}
+
 impl Default for Storage {
     fn default() -> Self {
         Self::new()
@@ -332,6 +828,103 @@ mod tests {
         assert_eq!(storage.get_balance(&addr2), 800);
     }
 
+    #[test]
+    fn test_journal_revert() {
+        let mut storage = Storage::new();
+        let addr = [1u8; 20];
+
+        storage.set_storage(addr, 1, 10);
+        storage.set_balance(addr, 100);
+        let checkpoint = storage.checkpoint();
+
+        storage.set_storage(addr, 1, 20);
+        storage.set_storage(addr, 2, 30);
+        storage.set_balance(addr, 50);
+        storage.set_code(addr, vec![0x00]);
+        storage.log(Log { address: addr, topics: vec![], data: vec![1] });
+
+        storage.revert_to(checkpoint);
+
+        assert_eq!(storage.get_storage(&addr, 1), 10);
+        assert_eq!(storage.get_storage(&addr, 2), 0);
+        assert_eq!(storage.get_balance(&addr), 100);
+        assert!(storage.code(&addr).is_none());
+        assert!(storage.take_logs().is_empty());
+
+        // A revert across a drain only removes logs emitted after the checkpoint
+        let log = |data| Log { address: addr, topics: vec![], data: vec![data] };
+        storage.log(log(1));
+        let checkpoint = storage.checkpoint();
+        storage.log(log(2));
+        assert_eq!(storage.take_logs().len(), 2);
+        storage.log(log(3));
+        storage.revert_to(checkpoint);
+        assert!(storage.take_logs().is_empty());
+
+        storage.log(log(4));
+        storage.commit();
+        assert!(storage.take_logs().is_empty());
+    }
+
+    #[test]
+    fn test_token_ledger() {
+        let mut storage = Storage::new();
+        let minter = [1u8; 20];
+        let holder = [2u8; 20];
+
+        assert!(matches!(
+            storage.mint_tokens(minter, holder, TokenType::GCC, 100),
+            Err(RvmError::TokenMintUnauthorized(_))
+        ));
+        storage.set_token_minter(minter, true);
+        storage.mint_tokens(minter, holder, TokenType::GCC, 100).unwrap();
+        let checkpoint = storage.checkpoint();
+
+        storage.transfer_tokens(holder, minter, TokenType::GCC, 40).unwrap();
+        // Burning another holder's tokens takes their allowance
+        assert!(matches!(
+            storage.burn_tokens(minter, holder, TokenType::GCC, 60),
+            Err(RvmError::TokenBurnFailed(_))
+        ));
+        storage.approve_tokens(holder, minter, TokenType::GCC, 70);
+        storage.burn_tokens(minter, holder, TokenType::GCC, 60).unwrap();
+        assert_eq!(storage.token_allowance(&holder, &minter, TokenType::GCC), 10);
+        storage.burn_tokens(minter, minter, TokenType::GCC, 0).unwrap();
+        storage.set_token_minter(minter, false);
+        assert_eq!(storage.get_token_balance(&holder, TokenType::GCC), 0);
+        assert_eq!(storage.get_token_balance(&minter, TokenType::GCC), 40);
+        assert!(matches!(
+            storage.transfer_tokens(holder, minter, TokenType::GCC, 1),
+            Err(RvmError::InsufficientTokenBalance { .. })
+        ));
+
+        storage.revert_to(checkpoint);
+
+        assert_eq!(storage.get_token_balance(&holder, TokenType::GCC), 100);
+        assert_eq!(storage.get_token_balance(&minter, TokenType::GCC), 0);
+        assert_eq!(storage.token_allowance(&holder, &minter, TokenType::GCC), 0);
+        assert!(storage.is_token_minter(&minter));
+    }
+
+    #[test]
+    fn test_token_transfer_overflow_leaves_balances() {
+        let mut storage = Storage::new();
+        let (from, to) = ([1u8; 20], [2u8; 20]);
+        storage.set_token_balance(from, TokenType::GCC, 10);
+        storage.set_token_balance(to, TokenType::GCC, u64::MAX);
+
+        assert!(matches!(
+            storage.transfer_tokens(from, to, TokenType::GCC, 5),
+            Err(RvmError::TokenTransferFailed(_))
+        ));
+        assert_eq!(storage.get_token_balance(&from, TokenType::GCC), 10);
+        assert_eq!(storage.get_token_balance(&to, TokenType::GCC), u64::MAX);
+
+        // Sending to oneself changes nothing
+        storage.transfer_tokens(from, from, TokenType::GCC, 10).unwrap();
+        assert_eq!(storage.get_token_balance(&from, TokenType::GCC), 10);
+    }
+
     #[test]
     fn test_nonce_operations() {
         let mut storage = Storage::new();
@@ -0,0 +1,20 @@
+//! Helpers shared by the unit tests
+
+use crate::core::bytes32_to_word;
+
+/// Assembly storing bytes in memory one byte at a time
+pub(crate) fn store_bytes(offset: usize, bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .enumerate()
+        .map(|(i, byte)| format!("PUSH {}\nPUSH {}\nMSTORE8\n", byte, offset + i))
+        .collect()
+}
+
+/// Words of return data, one per 32 bytes
+pub(crate) fn words(return_data: &[u8]) -> Vec<u64> {
+    return_data
+        .chunks(32)
+        .map(|word| bytes32_to_word(word.try_into().unwrap()))
+        .collect()
+}
@@ -0,0 +1,173 @@
+//! EIP-3155 Tracer
+//!
+//! Line-delimited JSON execution traces in the EIP-3155 format, for diffing RVM
+//! executions against geth and other clients.
+
+use crate::{
+    core::ExecutionResult,
+    inspector::{Inspector, Step},
+};
+use serde::{Deserialize, Serialize};
+use std::io::{self, Write};
+
+/// One EIP-3155 trace line, describing the state before an instruction
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TraceStep {
+    /// Program counter
+    pub pc: u64,
+    /// Opcode byte
+    pub op: u8,
+    /// Gas remaining before the instruction (hex)
+    pub gas: String,
+    /// Gas cost of the instruction, including dynamic costs and gas spent by calls (hex)
+    pub gas_cost: String,
+    /// Memory size in bytes
+    pub mem_size: u64,
+    /// Stack, bottom first (hex)
+    pub stack: Vec<String>,
+    /// Call depth, starting at 1
+    pub depth: u64,
+    /// Refund counter
+    pub refund: u64,
+    /// Opcode name
+    pub op_name: String,
+}
+
+/// Final EIP-3155 trace line
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TraceSummary {
+    /// Return data (hex)
+    pub output: String,
+    /// Gas used (hex)
+    pub gas_used: String,
+    /// Whether execution succeeded
+    pub pass: bool,
+    /// Error message, if execution failed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Inspector writing EIP-3155 traces.
+///
+/// A line is only complete once its instruction has finished (CALL and CREATE costs
+/// include the callee), so lines are buffered until the outermost pending instruction
+/// ends and then written in execution order.
+pub struct Eip3155Tracer<W: Write> {
+    /// Trace output
+    writer: W,
+    /// Lines not yet written
+    buffered: Vec<TraceStep>,
+    /// Indices of buffered lines whose instruction has not finished
+    pending: Vec<usize>,
+    /// First write error, reported by `finish`
+    error: Option<io::Error>,
+}
+
+impl<W: Write> Eip3155Tracer<W> {
+    /// Create a tracer writing to `writer`
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer,
+            buffered: Vec::new(),
+            pending: Vec::new(),
+            error: None,
+        }
+    }
+
+    /// Write the summary line and flush the output
+    pub fn finish(&mut self, result: &ExecutionResult) -> io::Result<()> {
+        self.flush_buffered();
+        if let Some(error) = self.error.take() {
+            return Err(error);
+        }
+        let summary = TraceSummary {
+            output: format!("0x{}", hex::encode(&result.return_data)),
+            gas_used: format!("{:#x}", result.gas_used),
+            pass: result.success,
+            error: result.error.clone(),
+        };
+        self.write_line(&summary)?;
+        self.writer.flush()
+    }
+
+    /// Consume the tracer, returning the writer
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+
+    /// Write all buffered lines
+    fn flush_buffered(&mut self) {
+        let lines = std::mem::take(&mut self.buffered);
+        for line in &lines {
+            if self.error.is_some() {
+                break;
+            }
+            if let Err(e) = self.write_line(line) {
+                self.error = Some(e);
+            }
+        }
+    }
+
+    /// Serialize one JSON line
+    fn write_line<T: Serialize>(&mut self, value: &T) -> io::Result<()> {
+        serde_json::to_writer(&mut self.writer, value)?;
+        self.writer.write_all(b"\n")
+    }
+}
+
+impl<W: Write> Inspector for Eip3155Tracer<W> {
+    fn step(&mut self, step: &Step<'_>) {
+        self.pending.push(self.buffered.len());
+        self.buffered.push(TraceStep {
+            pc: step.pc as u64,
+            op: step.opcode.byte(),
+            gas: format!("{:#x}", step.gas_remaining),
+            gas_cost: String::new(),
+            mem_size: step.memory.len() as u64,
+            stack: step.stack.iter().map(|value| format!("{:#x}", value)).collect(),
+            depth: step.depth as u64 + 1,
+            refund: step.gas_refunded,
+            op_name: format!("{:?}", step.opcode),
+        });
+    }
+
+    fn step_end(&mut self, _step: &Step<'_>, gas_cost: u64) {
+        if let Some(index) = self.pending.pop() {
+            self.buffered[index].gas_cost = format!("{:#x}", gas_cost);
+        }
+        if self.pending.is_empty() {
+            self.flush_buffered();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{core::{ExecutionEnvironment, RvmCore}, storage::Storage};
+
+    #[tokio::test]
+    async fn test_eip3155_trace_lines() {
+        // PUSH1 2, PUSH1 3, ADD, STOP
+        let code = vec![0x60, 0x02, 0x60, 0x03, 0x01, 0x00];
+        let mut storage = Storage::new();
+        let mut tracer = Eip3155Tracer::new(Vec::new());
+        let mut core = RvmCore::new(100);
+        let result = core
+            .execute_with_inspector(&code, ExecutionEnvironment::default(), &mut storage, &mut tracer)
+            .await
+            .unwrap();
+        tracer.finish(&result).unwrap();
+
+        let output = String::from_utf8(tracer.into_inner()).unwrap();
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(lines.len(), 5);
+        assert_eq!(
+            lines[2],
+            r#"{"pc":4,"op":1,"gas":"0x5e","gasCost":"0x3","memSize":0,"stack":["0x2","0x3"],"depth":1,"refund":0,"opName":"ADD"}"#
+        );
+        assert_eq!(lines[4], r#"{"output":"0x","gasUsed":"0x9","pass":true}"#);
+    }
+}
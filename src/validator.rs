@@ -0,0 +1,257 @@
+//! Bytecode Validator
+//!
+//! Static checks run before deployment: truncated PUSH data, undefined opcodes and
+//! constant jumps to non-JUMPDEST offsets in reachable code, and stack heights. Stack
+//! heights are propagated along the control-flow graph from an empty stack at offset 0
+//! using the stack signatures of the opcode table; a jump computed at runtime is assumed
+//! to reach every JUMPDEST. The stack analysis gives up on the paths left once it has
+//! visited `STACK_ANALYSIS_BUDGET` instructions and edges, leaving their heights unknown
+//! rather than blowing up on code with many dynamic jumps. EOF containers are checked by
+//! their own validation instead.
+
+use crate::{
+    disasm::{Disassembly, Edge},
+    eof::EofContainer,
+    error::RvmError,
+    opcodes::Opcode,
+    MAX_STACK_SIZE,
+};
+use std::collections::BTreeSet;
+use std::fmt;
+
+/// Instructions and edges the stack analysis visits before leaving the remaining paths unchecked
+pub const STACK_ANALYSIS_BUDGET: usize = 1 << 20;
+
+/// Problem found by the validator
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ValidationError {
+    /// PUSH immediate runs past the end of the code
+    TruncatedPush { offset: usize },
+    /// Reachable byte that is not a defined opcode
+    InvalidOpcode { offset: usize, byte: u8 },
+    /// Constant jump target that is not a JUMPDEST
+    InvalidJump { offset: usize, target: u64 },
+    /// Instruction needs more stack items than some path provides
+    StackUnderflow { offset: usize, height: usize, required: usize },
+    /// Some path grows the stack beyond `MAX_STACK_SIZE`
+    StackOverflow { offset: usize },
+    /// Code starts with the EOF magic but is not a valid container
+    InvalidEof { message: String },
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ValidationError::TruncatedPush { offset } => write!(f, "{:#06x}: truncated PUSH data", offset),
+            ValidationError::InvalidOpcode { offset, byte } => write!(f, "{:#06x}: invalid opcode 0x{:02x}", offset, byte),
+            ValidationError::InvalidJump { offset, target } => {
+                write!(f, "{:#06x}: jump to {:#x} is not a JUMPDEST", offset, target)
+            }
+            ValidationError::StackUnderflow { offset, height, required } => {
+                write!(f, "{:#06x}: stack underflow ({} items, {} required)", offset, height, required)
+            }
+            ValidationError::StackOverflow { offset } => {
+                write!(f, "{:#06x}: stack exceeds {} items", offset, MAX_STACK_SIZE)
+            }
+            ValidationError::InvalidEof { message } => write!(f, "{}", message),
+        }
+    }
+}
+
+/// Validate bytecode, returning every problem found in code order
+pub fn validate(code: &[u8]) -> Vec<ValidationError> {
+    if EofContainer::is_eof(code) {
+        return match EofContainer::parse(code).and_then(|container| container.validate()) {
+            Ok(()) => Vec::new(),
+            Err(e) => vec![ValidationError::InvalidEof { message: e.to_string() }],
+        };
+    }
+
+    let disassembly = Disassembly::new(code);
+    let mut errors = Vec::new();
+
+    for block in disassembly.blocks.iter().filter(|block| block.reachable) {
+        let instructions = disassembly.block_instructions(block);
+        for instruction in instructions {
+            if instruction.opcode.is_none() {
+                errors.push(ValidationError::InvalidOpcode { offset: instruction.offset, byte: instruction.byte });
+            }
+        }
+        for edge in &block.edges {
+            if let (Edge::InvalidJump(target), Some(jump)) = (edge, instructions.last()) {
+                errors.push(ValidationError::InvalidJump { offset: jump.offset, target: *target });
+            }
+        }
+    }
+    if let Some(instruction) = disassembly.instructions.last().filter(|i| i.truncated) {
+        errors.push(ValidationError::TruncatedPush { offset: instruction.offset });
+    }
+
+    errors.extend(check_stack(&disassembly));
+    errors.sort_by_key(|error| match error {
+        ValidationError::TruncatedPush { offset }
+        | ValidationError::InvalidOpcode { offset, .. }
+        | ValidationError::InvalidJump { offset, .. }
+        | ValidationError::StackUnderflow { offset, .. }
+        | ValidationError::StackOverflow { offset } => *offset,
+        ValidationError::InvalidEof { .. } => 0,
+    });
+    errors
+}
+
+/// Validate bytecode, failing with every problem found
+pub fn ensure_valid(code: &[u8]) -> Result<(), RvmError> {
+    let errors = validate(code);
+    if errors.is_empty() {
+        return Ok(());
+    }
+    let messages: Vec<String> = errors.iter().map(ToString::to_string).collect();
+    Err(RvmError::InvalidBytecode(messages.join("; ")))
+}
+
+/// Propagate stack heights through the control-flow graph
+fn check_stack(disassembly: &Disassembly) -> Vec<ValidationError> {
+    let blocks = &disassembly.blocks;
+    let starts: Vec<usize> = blocks.iter().map(|block| block.start).collect();
+    let jumpdests: Vec<usize> = blocks
+        .iter()
+        .enumerate()
+        .filter(|(_, block)| {
+            disassembly.block_instructions(block).first().and_then(|i| i.opcode) == Some(Opcode::JUMPDEST)
+        })
+        .map(|(index, _)| index)
+        .collect();
+
+    let mut errors = BTreeSet::new();
+    let mut seen: Vec<BTreeSet<usize>> = vec![BTreeSet::new(); blocks.len()];
+    let mut pending: Vec<(usize, usize)> = if blocks.is_empty() { Vec::new() } else { vec![(0, 0)] };
+    let mut budget = STACK_ANALYSIS_BUDGET;
+
+    // Every entry height is at most MAX_STACK_SIZE, so this terminates; the budget bounds
+    // the blocks x heights x JUMPDESTs worst case of dynamic jumps
+    while let Some((index, entry)) = pending.pop() {
+        if !seen[index].insert(entry) {
+            continue;
+        }
+        let fan_out = blocks[index].edges.iter().map(|edge| match edge {
+            Edge::Dynamic => jumpdests.len(),
+            _ => 1,
+        });
+        let cost = disassembly.block_instructions(&blocks[index]).len() + fan_out.sum::<usize>();
+        let Some(left) = budget.checked_sub(cost) else { break };
+        budget = left;
+
+        let mut height = entry;
+        let mut failed = false;
+        for instruction in disassembly.block_instructions(&blocks[index]) {
+            let Some(opcode) = instruction.opcode else {
+                failed = true;
+                break;
+            };
+            let (inputs, outputs) = opcode.stack_io();
+            if height < inputs {
+                errors.insert((instruction.offset, Some((height, inputs))));
+                failed = true;
+                break;
+            }
+            height = height - inputs + outputs;
+            if height > MAX_STACK_SIZE {
+                errors.insert((instruction.offset, None));
+                failed = true;
+                break;
+            }
+        }
+        if failed {
+            continue;
+        }
+
+        for edge in &blocks[index].edges {
+            match edge {
+                Edge::FallThrough(target) | Edge::Jump(target) => {
+                    if let Ok(next) = starts.binary_search(target) {
+                        pending.push((next, height));
+                    }
+                }
+                Edge::Dynamic => pending.extend(jumpdests.iter().map(|next| (*next, height))),
+                Edge::InvalidJump(_) => {}
+            }
+        }
+    }
+
+    // Report each instruction once, with the lowest height that underflows it
+    let mut reported = BTreeSet::new();
+    errors
+        .into_iter()
+        .filter(|(offset, _)| reported.insert(*offset))
+        .map(|(offset, underflow)| match underflow {
+            Some((height, required)) => ValidationError::StackUnderflow { offset, height, required },
+            None => ValidationError::StackOverflow { offset },
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::asm::assemble;
+
+    #[test]
+    fn test_dynamic_jumps_stay_within_budget() {
+        // Each block grows the stack by one and jumps to a computed target, so every
+        // height up to the limit reaches every JUMPDEST
+        let mut source = String::from("PUSH 0\n");
+        for _ in 0..500 {
+            source.push_str("JUMPDEST\nPUSH 1\nDUP1\nJUMP\n");
+        }
+        let code = assemble(&source).unwrap();
+        let start = std::time::Instant::now();
+        validate(&code);
+        assert!(start.elapsed() < std::time::Duration::from_secs(10));
+    }
+
+    #[test]
+    fn test_valid_loop() {
+        let code = assemble(
+            "PUSH 3
+             loop: JUMPDEST
+             PUSH 1
+             SUB
+             DUP1
+             PUSH loop
+             JUMPI
+             STOP",
+        )
+        .unwrap();
+        assert_eq!(validate(&code), vec![]);
+        assert!(ensure_valid(&code).is_ok());
+    }
+
+    #[test]
+    fn test_reports_each_problem() {
+        // ADD on an empty stack, jump into PUSH data, dead 0x0c, truncated PUSH2
+        let code = vec![0x01, 0x60, 0x00, 0x60, 0x01, 0x56, 0x0c, 0x61, 0x00];
+        assert_eq!(
+            validate(&code),
+            vec![
+                ValidationError::StackUnderflow { offset: 0, height: 0, required: 2 },
+                ValidationError::InvalidJump { offset: 5, target: 1 },
+                ValidationError::TruncatedPush { offset: 7 },
+            ]
+        );
+
+        let code = vec![0x0c];
+        assert_eq!(validate(&code), vec![ValidationError::InvalidOpcode { offset: 0, byte: 0x0c }]);
+
+        let code = vec![0xef, 0x00, 0x01];
+        assert!(matches!(&validate(&code)[..], [ValidationError::InvalidEof { message }] if message.contains("truncated")));
+    }
+
+    #[test]
+    fn test_loop_growing_the_stack_overflows() {
+        let code = assemble("loop: JUMPDEST\nPUSH 1\nPUSH loop\nJUMP").unwrap();
+        assert_eq!(validate(&code), vec![ValidationError::StackOverflow { offset: 3 }]);
+
+        let error = ensure_valid(&code).unwrap_err();
+        assert!(matches!(error, RvmError::InvalidBytecode(message) if message.contains("exceeds 1024")));
+    }
+}
@@ -5,7 +5,7 @@
 
 use crate::{
     error::RvmError,
-    gas::GasMeter,
+    gas::{GasMeter, GasMetering},
     core::{ExecutionResult, ExecutionEnvironment},
 };
 use serde::{Deserialize, Serialize};
@@ -136,7 +136,7 @@ pub struct WasmLiteModule {
 
 /// WASM-lite execution context
 #[derive(Debug, Clone)]
-pub struct WasmLiteContext {
+pub struct WasmLiteContext<G = GasMeter> {
     /// Value stack
     stack: Vec<WasmLiteValue>,
     /// Local variables
@@ -150,7 +150,7 @@ pub struct WasmLiteContext {
     /// Call stack
     call_stack: Vec<CallFrame>,
     /// Gas meter
-    gas: GasMeter,
+    gas: G,
     /// Execution environment
     env: ExecutionEnvironment,
 }
@@ -168,12 +168,14 @@ struct CallFrame {
     stack_pointer: usize,
 }
 
-/// WASM-lite virtual machine
-pub struct WasmLiteVM {
+/// WASM-lite virtual machine, metering gas with `G`
+pub struct WasmLiteVM<G = GasMeter> {
     /// Loaded modules
     modules: HashMap<String, WasmLiteModule>,
+    /// Meter executions are metered like
+    gas_meter: G,
     /// Execution context
-    context: Option<WasmLiteContext>,
+    context: Option<WasmLiteContext<G>>,
 }
 
 impl WasmLiteInstruction {
@@ -334,8 +336,46 @@ impl WasmLiteValue {
 impl WasmLiteVM {
     /// Create a new WASM-lite VM
     pub fn new() -> Self {
+        Self::with_gas_meter(GasMeter::new(0))
+    }
+
+    /// Create a simple WASM-lite demo module
+    pub fn create_demo_module() -> WasmLiteModule {
+        // Simple function that adds two numbers: (a: i32, b: i32) -> i32
+        // This function takes two parameters from the stack and adds them
+        let add_function = WasmLiteFunction {
+            name: "add".to_string(),
+            params: vec![WasmLiteValueType::I32, WasmLiteValueType::I32],
+            returns: vec![WasmLiteValueType::I32],
+            body: vec![
+                0x20, 0x00,  // local.get 0 (first parameter)
+                0x20, 0x01,  // local.get 1 (second parameter)
+                0x6a,        // i32.add
+                0x0f,        // return
+            ],
+            locals: vec![],
+        };
+
+        let mut exports = HashMap::new();
+        exports.insert("add".to_string(), 0);
+
+        WasmLiteModule {
+            version: 1,
+            functions: vec![add_function],
+            globals: vec![],
+            memory_pages: 1,
+            exports,
+            imports: HashMap::new(),
+        }
+    }
+}
+
+impl<G: GasMetering> WasmLiteVM<G> {
+    /// Create a new WASM-lite VM whose executions are metered like `gas_meter`
+    pub fn with_gas_meter(gas_meter: G) -> Self {
         Self {
             modules: HashMap::new(),
+            gas_meter,
             context: None,
         }
     }
@@ -396,7 +436,7 @@ impl WasmLiteVM {
             memory: vec![0; module.memory_pages as usize * crate::WASM_LITE_PAGE_SIZE],
             pc: 0,
             call_stack: Vec::new(),
-            gas: GasMeter::new(gas_limit),
+            gas: self.gas_meter.child(gas_limit),
             env,
         };
 
@@ -406,8 +446,12 @@ impl WasmLiteVM {
         Ok(ExecutionResult {
             return_data: result,
             gas_used: context.gas.used(),
+            gas_refunded: context.gas.refunded(),
             success: true,
             error: None,
+            agent_witness: None,
+            fee_receipt: None,
+            logs: Vec::new(),
         })
     }
 
@@ -415,7 +459,7 @@ impl WasmLiteVM {
     async fn execute_function_body(
         &self,
         bytecode: &[u8],
-        context: &mut WasmLiteContext,
+        context: &mut WasmLiteContext<G>,
     ) -> Result<Vec<u8>, RvmError> {
         while context.pc < bytecode.len() {
             let instruction = WasmLiteInstruction::from_byte(bytecode[context.pc])?;
@@ -450,7 +494,7 @@ impl WasmLiteVM {
         &self,
         instruction: WasmLiteInstruction,
         bytecode: &[u8],
-        context: &mut WasmLiteContext,
+        context: &mut WasmLiteContext<G>,
     ) -> Result<bool, RvmError> {
         match instruction {
             WasmLiteInstruction::Nop => {
@@ -545,35 +589,6 @@ impl WasmLiteVM {
         Ok(true)
     }
 
-    /// Create a simple WASM-lite demo module
-    pub fn create_demo_module() -> WasmLiteModule {
-        // Simple function that adds two numbers: (a: i32, b: i32) -> i32
-        // This function takes two parameters from the stack and adds them
-        let add_function = WasmLiteFunction {
-            name: "add".to_string(),
-            params: vec![WasmLiteValueType::I32, WasmLiteValueType::I32],
-            returns: vec![WasmLiteValueType::I32],
-            body: vec![
-                0x20, 0x00,  // local.get 0 (first parameter)
-                0x20, 0x01,  // local.get 1 (second parameter)
-                0x6a,        // i32.add
-                0x0f,        // return
-            ],
-            locals: vec![],
-        };
-
-        let mut exports = HashMap::new();
-        exports.insert("add".to_string(), 0);
-
-        WasmLiteModule {
-            version: 1,
-            functions: vec![add_function],
-            globals: vec![],
-            memory_pages: 1,
-            exports,
-            imports: HashMap::new(),
-        }
-    }
 }
 
 impl Default for WasmLiteVM {